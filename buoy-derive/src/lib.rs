@@ -0,0 +1,136 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, LitStr};
+
+// Implements 'buoy::device::Device' for a type, eg:
+//
+//     #[derive(Device)]
+//     #[buoy(uuid = "4018ce29-15ba-4a8a-93f9-a14263d39733", package = "my_widgets")]
+//     pub struct Button { .. }
+//
+// 'uuid' is required, and must be a hyphenated uuid string. 'package' defaults to the name of the
+// crate deriving the device, and 'name' defaults to the name of the type.
+#[proc_macro_derive(Device, attributes(buoy))]
+pub fn derive_device(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut uuid = None;
+    let mut package = None;
+    let mut name = None;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("buoy"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let slot = if meta.path.is_ident("uuid") {
+                &mut uuid
+            } else if meta.path.is_ident("package") {
+                &mut package
+            } else if meta.path.is_ident("name") {
+                &mut name
+            } else {
+                return Err(meta.error("expected 'uuid', 'package' or 'name'"));
+            };
+
+            if slot.is_some() {
+                return Err(meta.error("duplicate attribute"));
+            }
+            *slot = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        })?;
+    }
+
+    let uuid = match uuid {
+        Some(uuid) => uuid,
+        None => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "missing #[buoy(uuid = \"...\")] attribute",
+            ))
+        }
+    };
+    let id = parse_uuid(&uuid.value()).map_err(|msg| syn::Error::new(uuid.span(), msg))?;
+
+    let package = match package {
+        Some(package) => quote!(#package),
+        None => quote!(env!("CARGO_PKG_NAME")),
+    };
+    let type_name = match name {
+        Some(name) => name,
+        None => LitStr::new(&input.ident.to_string(), input.ident.span()),
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::buoy::device::Device for #ident #ty_generics #where_clause {
+            fn type_id() -> ::buoy::device::TypeId {
+                ::buoy::device::TypeId::new(#id)
+            }
+
+            fn package_name() -> &'static str {
+                #package
+            }
+
+            fn type_name() -> &'static str {
+                #type_name
+            }
+        }
+    })
+}
+
+// Parses a uuid in the hyphenated 8-4-4-4-12 form
+fn parse_uuid(uuid: &str) -> Result<u128, String> {
+    let groups: Vec<&str> = uuid.split('-').collect();
+    let lengths: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+    if lengths != [8, 4, 4, 4, 12] {
+        return Err(format!(
+            "'{}' is not a hyphenated uuid (expected xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx)",
+            uuid
+        ));
+    }
+
+    let mut id = 0_u128;
+    for c in groups.concat().chars() {
+        let digit = c
+            .to_digit(16)
+            .ok_or_else(|| format!("'{}' contains a non-hex digit '{}'", uuid, c))?;
+        id = (id << 4) | digit as u128;
+    }
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_uuid;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_uuid("4018ce29-15ba-4a8a-93f9-a14263d39733"),
+            Ok(0x4018ce29_15ba_4a8a_93f9_a14263d39733)
+        );
+        assert_eq!(
+            parse_uuid("4018CE29-15BA-4A8A-93F9-A14263D39733"),
+            Ok(0x4018ce29_15ba_4a8a_93f9_a14263d39733)
+        );
+
+        assert!(parse_uuid("4018ce2915ba4a8a93f9a14263d39733").is_err());
+        assert!(parse_uuid("4018ce29-15ba-4a8a-93f9-a14263d3973").is_err());
+        assert!(parse_uuid("4018ce29-15ba-4a8a-93f9-a14263d3973g").is_err());
+        assert!(parse_uuid("+018ce29-15ba-4a8a-93f9-a14263d39733").is_err());
+    }
+}
@@ -0,0 +1,39 @@
+// Canvases for renderers to draw to. Renderers can target any canvas type, but those written against
+// 'Draw' work with 'DisplayList' (which backends can replay), 'SvgCanvas' and 'TerminalCanvas'.
+
+mod color;
+pub use self::color::Color;
+
+mod display_list;
+pub use self::display_list::{
+    Backend, Command, DisplayList, Draw, GlyphRun, ImageId, PositionedGlyph, Stroke, TextRun,
+};
+
+mod path;
+pub use self::path::{Path, PathCommand};
+
+mod png;
+
+mod raster;
+pub use self::raster::{Pixmap, Rasterizer};
+
+mod svg;
+pub use self::svg::SvgCanvas;
+
+mod terminal;
+pub use self::terminal::{BorderStyle, Cell, TerminalCanvas};
+
+mod transform;
+pub use self::transform::Transform;
+
+use crate::space::Region;
+
+// Implemented by canvases that can restrict drawing to a region, for devices like scroll viewports
+// that need to hide whatever falls outside of them.
+pub trait Clip {
+    // Restricts drawing to the given region (intersected with any clip already in place), until the
+    // matching call to 'pop_clip'.
+    fn push_clip(&mut self, region: Region);
+
+    fn pop_clip(&mut self);
+}
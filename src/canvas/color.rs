@@ -0,0 +1,29 @@
+// An 8-bit RGBA color, not premultiplied
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+
+    // Scales the alpha by the given opacity (between 0 and 1)
+    pub fn with_opacity(self, opacity: f32) -> Self {
+        let a = (self.a as f32 * opacity.clamp(0_f32, 1_f32)).round() as u8;
+        Color { a, ..self }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::canvas::{Clip, Color, Path, Transform};
+use crate::device::DeviceInfo;
+use crate::space::{Point, Region};
+use crate::text::FontId;
+use crate::RenderHooks;
+
+// Identifies an image owned by whatever is going to replay the display list
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageId(pub u64);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stroke {
+    pub color: Color,
+    pub width: f32,
+}
+
+impl Stroke {
+    pub fn new(color: Color, width: f32) -> Self {
+        Stroke { color, width }
+    }
+}
+
+// A run of text in a single style, positioned by the start of its baseline
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    pub origin: Point,
+    pub font_size: f32,
+    pub color: Color,
+}
+
+// Glyphs laid out with one of the fonts in a 'FontCollection', each positioned by the start of its
+// baseline. The character each glyph is for is kept, for backends that draw text with their own fonts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphRun {
+    pub font: FontId,
+    pub font_size: f32,
+    pub color: Color,
+    pub glyphs: Vec<PositionedGlyph>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionedGlyph {
+    pub id: u16,
+    pub ch: char,
+    pub origin: Point,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Rect {
+        region: Region,
+        color: Color,
+    },
+    RoundedRect {
+        region: Region,
+        radius: f32,
+        color: Color,
+    },
+    Path {
+        path: Path,
+        fill: Option<Color>,
+        stroke: Option<Stroke>,
+    },
+    Text(TextRun),
+    Glyphs(GlyphRun),
+    Image {
+        image: ImageId,
+        region: Region,
+    },
+
+    // Each push is matched by a pop of the same kind, and they're properly nested
+    PushClip(Region),
+    PopClip,
+    // Transforms apply to everything up until the matching pop, on top of the transforms already pushed
+    PushTransform(Transform),
+    PopTransform,
+    // Everything up until the matching pop is composited together, then drawn with the given opacity
+    PushLayer {
+        opacity: f32,
+    },
+    PopLayer,
+    // Marks everything up until the matching end as drawn by one device. Backends that only produce
+    // pixels can ignore these.
+    BeginDevice(DeviceInfo),
+    EndDevice,
+}
+
+// Implemented by anything that can draw the commands in a display list
+pub trait Backend {
+    fn execute(&mut self, command: &Command);
+}
+
+// The drawing API shared by canvases built on top of commands, so renderers can be written once for
+// all of them. Only 'draw' needs implementing.
+pub trait Draw: Clip {
+    fn draw(&mut self, command: Command);
+
+    fn fill_rect(&mut self, region: Region, color: Color) {
+        self.draw(Command::Rect { region, color });
+    }
+
+    fn fill_rounded_rect(&mut self, region: Region, radius: f32, color: Color) {
+        self.draw(Command::RoundedRect {
+            region,
+            radius,
+            color,
+        });
+    }
+
+    fn fill_path(&mut self, path: Path, color: Color) {
+        self.draw_path(path, Some(color), None);
+    }
+
+    fn stroke_path(&mut self, path: Path, stroke: Stroke) {
+        self.draw_path(path, None, Some(stroke));
+    }
+
+    fn draw_path(&mut self, path: Path, fill: Option<Color>, stroke: Option<Stroke>) {
+        self.draw(Command::Path { path, fill, stroke });
+    }
+
+    fn draw_text(&mut self, text: TextRun) {
+        self.draw(Command::Text(text));
+    }
+
+    fn draw_glyphs(&mut self, glyphs: GlyphRun) {
+        self.draw(Command::Glyphs(glyphs));
+    }
+
+    fn draw_image(&mut self, image: ImageId, region: Region) {
+        self.draw(Command::Image { image, region });
+    }
+
+    fn push_transform(&mut self, transform: Transform) {
+        self.draw(Command::PushTransform(transform));
+    }
+
+    fn pop_transform(&mut self) {
+        self.draw(Command::PopTransform);
+    }
+
+    fn push_layer(&mut self, opacity: f32) {
+        self.draw(Command::PushLayer { opacity });
+    }
+
+    fn pop_layer(&mut self) {
+        self.draw(Command::PopLayer);
+    }
+
+    fn begin_device(&mut self, device: &DeviceInfo) {
+        self.draw(Command::BeginDevice(*device));
+    }
+
+    fn end_device(&mut self, _device: &DeviceInfo) {
+        self.draw(Command::EndDevice);
+    }
+}
+
+impl<C: Draw> RenderHooks<C> {
+    // Wraps what each device draws in 'BeginDevice' and 'EndDevice' commands
+    pub fn draw() -> Self {
+        RenderHooks {
+            begin: C::begin_device,
+            end: C::end_device,
+        }
+    }
+}
+
+// A canvas that just records what's drawn to it, so it can be replayed by a backend later on
+// (or compared against another frame).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DisplayList {
+    commands: Vec<Command>,
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn replay<B: Backend + ?Sized>(&self, backend: &mut B) {
+        for command in &self.commands {
+            backend.execute(command);
+        }
+    }
+}
+
+impl Draw for DisplayList {
+    fn draw(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+}
+
+impl Clip for DisplayList {
+    fn push_clip(&mut self, region: Region) {
+        self.draw(Command::PushClip(region));
+    }
+
+    fn pop_clip(&mut self) {
+        self.draw(Command::PopClip);
+    }
+}
+
+// Replaying into another display list appends to it
+impl Backend for DisplayList {
+    fn execute(&mut self, command: &Command) {
+        self.commands.push(command.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::space::{Size, Vector};
+
+    #[test]
+    fn record_and_replay() {
+        let region = Region::new(Point::new(1_f32, 2_f32), Size::new(3_f32, 4_f32));
+        let triangle = Path::new()
+            .move_to(Point::zero())
+            .line_to(Point::new(4_f32, 0_f32))
+            .line_to(Point::new(0_f32, 4_f32))
+            .close();
+
+        let mut list = DisplayList::new();
+        list.push_transform(Transform::translate(Vector::new(5_f32, 5_f32)));
+        list.push_clip(region);
+        list.fill_rect(region, Color::BLACK);
+        list.push_layer(0.5_f32);
+        list.fill_path(triangle.clone(), Color::WHITE);
+        list.pop_layer();
+        list.pop_clip();
+        list.pop_transform();
+
+        assert_eq!(list.len(), 8);
+        assert_eq!(
+            list.commands()[4],
+            Command::Path {
+                path: triangle,
+                fill: Some(Color::WHITE),
+                stroke: None,
+            }
+        );
+
+        let mut copy = DisplayList::new();
+        list.replay(&mut copy);
+        assert_eq!(copy, list);
+    }
+
+    #[test]
+    fn transform() {
+        let transform =
+            Transform::scale(2_f32, 3_f32).then(Transform::translate(Vector::new(1_f32, 1_f32)));
+        assert_eq!(
+            transform.apply(Point::new(1_f32, 1_f32)),
+            Point::new(3_f32, 4_f32)
+        );
+
+        let rotate = Transform::rotate(std::f32::consts::FRAC_PI_2);
+        let point = rotate.apply(Point::new(1_f32, 0_f32));
+        assert!(point.x.abs() < 1e-6 && (point.y - 1_f32).abs() < 1e-6);
+    }
+}
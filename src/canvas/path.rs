@@ -0,0 +1,56 @@
+use crate::space::Point;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathCommand {
+    MoveTo(Point),
+    LineTo(Point),
+    QuadTo(Point, Point),
+    CubicTo(Point, Point, Point),
+    Close,
+}
+
+// A sequence of (possibly closed) subpaths made of lines and bezier curves
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    commands: Vec<PathCommand>,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn move_to(mut self, point: Point) -> Self {
+        self.commands.push(PathCommand::MoveTo(point));
+        self
+    }
+
+    pub fn line_to(mut self, point: Point) -> Self {
+        self.commands.push(PathCommand::LineTo(point));
+        self
+    }
+
+    pub fn quad_to(mut self, control: Point, point: Point) -> Self {
+        self.commands.push(PathCommand::QuadTo(control, point));
+        self
+    }
+
+    pub fn cubic_to(mut self, control1: Point, control2: Point, point: Point) -> Self {
+        self.commands
+            .push(PathCommand::CubicTo(control1, control2, point));
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.commands.push(PathCommand::Close);
+        self
+    }
+
+    pub fn commands(&self) -> &[PathCommand] {
+        &self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
@@ -0,0 +1,179 @@
+// A minimal PNG encoder, writing 8-bit RGBA images as uncompressed deflate blocks.
+// The files are larger than they need to be, but that doesn't matter much for snapshots.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Deflate's stored blocks can hold at most this many bytes
+const MAX_STORED_BLOCK: usize = 0xffff;
+
+pub(crate) fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    assert_eq!(rgba.len(), width as usize * height as usize * 4);
+
+    let mut png = SIGNATURE.to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    // Each row starts with its filter type, which is always 'none'
+    let stride = width as usize * 4;
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in rgba.chunks(stride.max(1)).take(height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32K window (the largest, and the only one PNG allows) at the fastest level, which
+    // doesn't matter for stored blocks. The low bits make the pair a multiple of 31, as zlib requires.
+    let mut out = vec![0x78, 0x01];
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    // Reads the chunks back (checking their CRCs), and inflates the image data
+    fn decode(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(&png[..8], &SIGNATURE);
+        let (mut header, mut data) = (Vec::new(), Vec::new());
+        let mut rest = &png[8..];
+        loop {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, chunk) = (&rest[4..8], &rest[8..8 + len]);
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc32(&rest[4..8 + len]), crc);
+            rest = &rest[12 + len..];
+            match kind {
+                b"IHDR" => header.extend_from_slice(chunk),
+                b"IDAT" => data.extend_from_slice(chunk),
+                b"IEND" => break,
+                _ => panic!("unexpected chunk"),
+            }
+        }
+        assert!(rest.is_empty());
+
+        assert_eq!(&header[8..], &[8, 6, 0, 0, 0]);
+        let width = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+
+        // A 32K window, and a valid check
+        assert_eq!(data[0], 0x78);
+        assert_eq!(u16::from_be_bytes([data[0], data[1]]) % 31, 0);
+        let mut raw = Vec::new();
+        let mut pos = 2;
+        loop {
+            let last = data[pos] & 1 == 1;
+            assert_eq!(data[pos] >> 1, 0, "only stored blocks are written");
+            let len = u16::from_le_bytes([data[pos + 1], data[pos + 2]]);
+            let nlen = u16::from_le_bytes([data[pos + 3], data[pos + 4]]);
+            assert_eq!(len, !nlen);
+            pos += 5;
+            raw.extend_from_slice(&data[pos..pos + len as usize]);
+            pos += len as usize;
+            if last {
+                break;
+            }
+        }
+        let adler = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap());
+        assert_eq!(adler32(&raw), adler);
+        assert_eq!(pos + 4, data.len());
+
+        let stride = width as usize * 4 + 1;
+        assert_eq!(raw.len(), stride * height as usize);
+        let mut rgba = Vec::new();
+        for row in raw.chunks(stride) {
+            assert_eq!(row[0], 0);
+            rgba.extend_from_slice(&row[1..]);
+        }
+        (width, height, rgba)
+    }
+
+    #[test]
+    fn encode_pixel() {
+        let png = encode(1, 1, &[255, 0, 0, 255]);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(
+            &png[png.len() - 12..],
+            &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+        assert_eq!(decode(&png), (1, 1, vec![255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        // Big enough to need more than one stored block
+        let (width, height) = (150, 120);
+        let rgba: Vec<u8> = (0..width * height * 4)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect();
+        assert!(rgba.len() > MAX_STORED_BLOCK);
+        assert_eq!(decode(&encode(width, height, &rgba)), (width, height, rgba));
+        assert_eq!(decode(&encode(0, 0, &[])), (0, 0, vec![]));
+    }
+}
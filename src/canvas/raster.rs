@@ -0,0 +1,704 @@
+use crate::canvas::{png, Backend, Color, Command, DisplayList, ImageId, Path, PathCommand};
+use crate::canvas::{Stroke, Transform};
+use crate::space::{Point, Region, Size, Vector};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::io;
+
+// Vertical samples taken per row of pixels. Horizontal coverage is computed exactly.
+const SAMPLES: usize = 4;
+
+// Line segments used to approximate each curve, arc and circle
+const CURVE_SEGMENTS: usize = 16;
+
+// An 8-bit RGBA image (not premultiplied), stored row by row from the top left
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pixmap {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Pixmap {
+    pub fn new(width: u32, height: u32) -> Self {
+        Pixmap {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> Option<Self> {
+        if data.len() != width as usize * height as usize * 4 {
+            return None;
+        }
+
+        Some(Pixmap {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let p = &self.data[i..i + 4];
+        Color::rgba(p[0], p[1], p[2], p[3])
+    }
+
+    pub fn encode_png(&self) -> Vec<u8> {
+        png::encode(self.width, self.height, &self.data)
+    }
+
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::write(path, self.encode_png())
+    }
+}
+
+// A premultiplied RGBA color, for blending
+#[derive(Clone, Copy, Default)]
+struct Premul([f32; 4]);
+
+impl Premul {
+    fn from_color(color: Color) -> Self {
+        let a = color.a as f32 / 255_f32;
+        Premul([
+            color.r as f32 / 255_f32 * a,
+            color.g as f32 / 255_f32 * a,
+            color.b as f32 / 255_f32 * a,
+            a,
+        ])
+    }
+
+    fn scale(self, amount: f32) -> Self {
+        let Premul([r, g, b, a]) = self;
+        Premul([r * amount, g * amount, b * amount, a * amount])
+    }
+
+    // Draws 'src' over this
+    fn blend(&mut self, src: Premul) {
+        let inverse = 1_f32 - src.0[3];
+        for (dst, src) in self.0.iter_mut().zip(&src.0) {
+            *dst = src + *dst * inverse;
+        }
+    }
+
+    fn to_rgba(self) -> [u8; 4] {
+        let Premul([r, g, b, a]) = self;
+        if a <= 0_f32 {
+            return [0; 4];
+        }
+
+        let channel = |c: f32| (c.clamp(0_f32, 1_f32) * 255_f32).round() as u8;
+        [channel(r / a), channel(g / a), channel(b / a), channel(a)]
+    }
+}
+
+// An axis-aligned rectangle in pixel coordinates
+#[derive(Clone, Copy)]
+struct Rect {
+    x0: f32,
+    y0: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl Rect {
+    fn bounds(points: impl Iterator<Item = Point>) -> Self {
+        let mut rect = Rect {
+            x0: f32::INFINITY,
+            y0: f32::INFINITY,
+            x1: f32::NEG_INFINITY,
+            y1: f32::NEG_INFINITY,
+        };
+        for point in points {
+            rect.x0 = rect.x0.min(point.x);
+            rect.y0 = rect.y0.min(point.y);
+            rect.x1 = rect.x1.max(point.x);
+            rect.y1 = rect.y1.max(point.y);
+        }
+
+        rect
+    }
+
+    // May be empty (with the far edges before the near ones) if they don't overlap
+    fn intersect(self, other: Rect) -> Self {
+        Rect {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !(self.x1 > self.x0 && self.y1 > self.y0)
+    }
+
+    // How much of the pixel at (x, y) is inside this
+    fn coverage(&self, x: usize, y: usize) -> f32 {
+        let (x, y) = (x as f32, y as f32);
+        let width = (self.x1.min(x + 1_f32) - self.x0.max(x)).max(0_f32);
+        let height = (self.y1.min(y + 1_f32) - self.y0.max(y)).max(0_f32);
+        width * height
+    }
+}
+
+struct Layer {
+    pixels: Vec<Premul>,
+    opacity: f32,
+}
+
+// Draws display lists into a pixel buffer covering a region of the window, one pixel per unit.
+// Fills and strokes are anti-aliased. Text isn't drawn, since there aren't any fonts to draw it with.
+// Clips are axis-aligned, so a clip pushed under a rotation clips to the bounds of the rotated region.
+pub struct Rasterizer {
+    origin: Point,
+    width: usize,
+    height: usize,
+    layers: Vec<Layer>,
+    transforms: Vec<Transform>,
+    clips: Vec<Rect>,
+    images: HashMap<ImageId, Pixmap>,
+}
+
+impl Rasterizer {
+    // Covers the given region, which would usually be the one passed to 'GuiContext::render_window'
+    pub fn new(region: Region) -> Self {
+        let width = region.size.width.max(0_f32).ceil() as usize;
+        let height = region.size.height.max(0_f32).ceil() as usize;
+        let bounds = Rect {
+            x0: 0_f32,
+            y0: 0_f32,
+            x1: width as f32,
+            y1: height as f32,
+        };
+
+        Rasterizer {
+            origin: region.pos,
+            width,
+            height,
+            layers: vec![Layer {
+                pixels: vec![Premul::default(); width * height],
+                opacity: 1_f32,
+            }],
+            transforms: vec![Transform::identity()],
+            clips: vec![bounds],
+            images: HashMap::new(),
+        }
+    }
+
+    // Rasterizes a whole display list
+    pub fn rasterize(list: &DisplayList, region: Region) -> Pixmap {
+        let mut rasterizer = Rasterizer::new(region);
+        list.replay(&mut rasterizer);
+        rasterizer.to_pixmap()
+    }
+
+    // Makes an image available to 'Command::Image'
+    pub fn add_image(&mut self, id: ImageId, image: Pixmap) {
+        self.images.insert(id, image);
+    }
+
+    // Fills the current layer with the given color, ignoring clips
+    pub fn clear(&mut self, color: Color) {
+        let color = Premul::from_color(color);
+        let layer = self.layers.last_mut().unwrap();
+        layer.pixels.iter_mut().for_each(|p| *p = color);
+    }
+
+    pub fn to_pixmap(&self) -> Pixmap {
+        let mut data = Vec::with_capacity(self.width * self.height * 4);
+        for pixel in &self.layers[0].pixels {
+            data.extend_from_slice(&pixel.to_rgba());
+        }
+
+        Pixmap::from_rgba(self.width as u32, self.height as u32, data).unwrap()
+    }
+
+    fn transform(&self) -> Transform {
+        *self.transforms.last().unwrap()
+    }
+
+    fn clip(&self) -> Rect {
+        *self.clips.last().unwrap()
+    }
+
+    // Maps from the coordinates of the current transform to pixels
+    fn to_pixels(&self) -> Transform {
+        self.transform().then(Transform::translate(Vector::new(
+            -self.origin.x,
+            -self.origin.y,
+        )))
+    }
+
+    fn blend(&mut self, x: usize, y: usize, color: Premul, coverage: f32) {
+        let coverage = coverage * self.clip().coverage(x, y);
+        if coverage <= 0_f32 {
+            return;
+        }
+
+        let i = y * self.width + x;
+        let layer = self.layers.last_mut().unwrap();
+        layer.pixels[i].blend(color.scale(coverage.min(1_f32)));
+    }
+
+    // Fills the given polygons (already in pixels) with the nonzero winding rule
+    fn fill_polygons(&mut self, polygons: &[Vec<Point>], color: Color) {
+        let color = Premul::from_color(color);
+        let bounds = Rect::bounds(polygons.iter().flatten().copied()).intersect(self.clip());
+        if bounds.is_empty() {
+            return;
+        }
+
+        let x0 = bounds.x0.floor() as usize;
+        let x1 = (bounds.x1.ceil() as usize).min(self.width);
+        let y0 = bounds.y0.floor() as usize;
+        let y1 = (bounds.y1.ceil() as usize).min(self.height);
+
+        let mut coverage = vec![0_f32; x1 - x0];
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for y in y0..y1 {
+            coverage.iter_mut().for_each(|c| *c = 0_f32);
+
+            for sample in 0..SAMPLES {
+                let sample_y = y as f32 + (sample as f32 + 0.5_f32) / SAMPLES as f32;
+
+                // Find where each edge crosses this sample line, and which way it's going
+                crossings.clear();
+                for polygon in polygons {
+                    let edges = polygon.iter().zip(polygon.iter().cycle().skip(1));
+                    for (a, b) in edges {
+                        if (a.y <= sample_y) == (b.y <= sample_y) {
+                            continue;
+                        }
+
+                        // Points that aren't numbers can't cross anything
+                        let x = a.x + (sample_y - a.y) * (b.x - a.x) / (b.y - a.y);
+                        if !x.is_nan() {
+                            crossings.push((x, if b.y > a.y { 1 } else { -1 }));
+                        }
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let mut winding = 0;
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+                    if winding != 0 {
+                        add_span(&mut coverage, x0, pair[0].0, pair[1].0);
+                    }
+                }
+            }
+
+            for x in x0..x1 {
+                let c = coverage[x - x0] / SAMPLES as f32;
+                self.blend(x, y, color, c);
+            }
+        }
+    }
+
+    fn fill_shape(&mut self, polygons: Vec<Vec<Point>>, color: Color) {
+        let transform = self.to_pixels();
+        let polygons: Vec<Vec<Point>> = polygons
+            .into_iter()
+            .map(|polygon| polygon.into_iter().map(|p| transform.apply(p)).collect())
+            .collect();
+        self.fill_polygons(&polygons, color);
+    }
+
+    fn stroke_shape(&mut self, subpaths: Vec<(Vec<Point>, bool)>, stroke: Stroke) {
+        let transform = self.to_pixels();
+        let radius = stroke.width / 2_f32;
+
+        // Each segment and join is its own polygon, all wound the same way so they union together
+        let mut polygons = Vec::new();
+        for (points, closed) in subpaths {
+            let segments = if closed {
+                points.len()
+            } else {
+                points.len().saturating_sub(1)
+            };
+            for i in 0..segments {
+                let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                let (dx, dy) = (b.x - a.x, b.y - a.y);
+                let len = (dx * dx + dy * dy).sqrt();
+                if len == 0_f32 {
+                    continue;
+                }
+
+                let (nx, ny) = (-dy / len * radius, dx / len * radius);
+                polygons.push(vec![
+                    Point::new(a.x + nx, a.y + ny),
+                    Point::new(b.x + nx, b.y + ny),
+                    Point::new(b.x - nx, b.y - ny),
+                    Point::new(a.x - nx, a.y - ny),
+                ]);
+            }
+
+            let joins = if closed {
+                0..points.len()
+            } else {
+                1..points.len().saturating_sub(1)
+            };
+            for i in joins {
+                polygons.push(ellipse(points[i], radius, radius));
+            }
+        }
+
+        let mut polygons: Vec<Vec<Point>> = polygons
+            .into_iter()
+            .map(|polygon| polygon.into_iter().map(|p| transform.apply(p)).collect())
+            .collect();
+        for polygon in &mut polygons {
+            if signed_area(polygon) < 0_f32 {
+                polygon.reverse();
+            }
+        }
+
+        self.fill_polygons(&polygons, stroke.color);
+    }
+
+    fn draw_image(&mut self, id: ImageId, region: Region) {
+        let image = match self.images.get(&id) {
+            Some(image) if image.width > 0 && image.height > 0 => image.clone(),
+            _ => return,
+        };
+
+        let to_pixels = self.to_pixels();
+        let from_pixels = match to_pixels.invert() {
+            Some(transform) => transform,
+            None => return,
+        };
+
+        let bounds = Rect::bounds(corners(region).into_iter().map(|p| to_pixels.apply(p)))
+            .intersect(self.clip());
+        if bounds.is_empty() {
+            return;
+        }
+
+        let x0 = bounds.x0.floor().max(0_f32) as usize;
+        let x1 = (bounds.x1.ceil().max(0_f32) as usize).min(self.width);
+        let y0 = bounds.y0.floor().max(0_f32) as usize;
+        let y1 = (bounds.y1.ceil().max(0_f32) as usize).min(self.height);
+
+        // Sample the nearest texel to the center of each pixel
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = from_pixels.apply(Point::new(x as f32 + 0.5_f32, y as f32 + 0.5_f32));
+                let u = (p.x - region.pos.x) / region.size.width;
+                let v = (p.y - region.pos.y) / region.size.height;
+                if !(0_f32..1_f32).contains(&u) || !(0_f32..1_f32).contains(&v) {
+                    continue;
+                }
+
+                let tx = (u * image.width as f32) as u32;
+                let ty = (v * image.height as f32) as u32;
+                let texel = Premul::from_color(image.pixel(tx, ty));
+                self.blend(x, y, texel, 1_f32);
+            }
+        }
+    }
+
+    fn pop_layer(&mut self) {
+        // The base layer is never popped
+        if self.layers.len() < 2 {
+            return;
+        }
+
+        let layer = self.layers.pop().unwrap();
+        let below = self.layers.last_mut().unwrap();
+        for (dst, src) in below.pixels.iter_mut().zip(layer.pixels) {
+            dst.blend(src.scale(layer.opacity));
+        }
+    }
+}
+
+impl Backend for Rasterizer {
+    fn execute(&mut self, command: &Command) {
+        match command {
+            Command::Rect { region, color } => self.fill_shape(vec![corners(*region)], *color),
+            Command::RoundedRect {
+                region,
+                radius,
+                color,
+            } => self.fill_shape(vec![rounded_rect(*region, *radius)], *color),
+            Command::Path { path, fill, stroke } => {
+                let subpaths = flatten(path);
+                if let Some(color) = fill {
+                    let polygons = subpaths.iter().map(|(points, _)| points.clone()).collect();
+                    self.fill_shape(polygons, *color);
+                }
+                if let Some(stroke) = stroke {
+                    self.stroke_shape(subpaths, *stroke);
+                }
+            }
+            Command::Text(_)
+            | Command::Glyphs(_)
+            | Command::BeginDevice(_)
+            | Command::EndDevice => (),
+            Command::Image { image, region } => self.draw_image(*image, *region),
+            Command::PushClip(region) => {
+                let transform = self.to_pixels();
+                let clip = Rect::bounds(corners(*region).into_iter().map(|p| transform.apply(p)));
+                self.clips.push(clip.intersect(self.clip()));
+            }
+            Command::PopClip => {
+                if self.clips.len() > 1 {
+                    self.clips.pop();
+                }
+            }
+            Command::PushTransform(transform) => {
+                self.transforms.push(transform.then(self.transform()));
+            }
+            Command::PopTransform => {
+                if self.transforms.len() > 1 {
+                    self.transforms.pop();
+                }
+            }
+            Command::PushLayer { opacity } => self.layers.push(Layer {
+                pixels: vec![Premul::default(); self.width * self.height],
+                opacity: opacity.clamp(0_f32, 1_f32),
+            }),
+            Command::PopLayer => self.pop_layer(),
+        }
+    }
+}
+
+// Adds the coverage of the span from 'start' to 'end' to the pixels it crosses
+fn add_span(coverage: &mut [f32], offset: usize, start: f32, end: f32) {
+    let min = offset as f32;
+    let max = (offset + coverage.len()) as f32;
+    let (start, end) = (start.max(min), end.min(max));
+    if start >= end {
+        return;
+    }
+
+    for x in start.floor() as usize..end.ceil() as usize {
+        let left = start.max(x as f32);
+        let right = end.min(x as f32 + 1_f32);
+        coverage[x - offset] += right - left;
+    }
+}
+
+fn signed_area(polygon: &[Point]) -> f32 {
+    let edges = polygon.iter().zip(polygon.iter().cycle().skip(1));
+    edges.map(|(a, b)| a.x * b.y - b.x * a.y).sum::<f32>() / 2_f32
+}
+
+fn corners(region: Region) -> Vec<Point> {
+    let Region { pos, size } = region;
+    vec![
+        pos,
+        Point::new(pos.x + size.width, pos.y),
+        Point::new(pos.x + size.width, pos.y + size.height),
+        Point::new(pos.x, pos.y + size.height),
+    ]
+}
+
+fn ellipse(center: Point, rx: f32, ry: f32) -> Vec<Point> {
+    (0..CURVE_SEGMENTS)
+        .map(|i| {
+            let angle = i as f32 / CURVE_SEGMENTS as f32 * 2_f32 * PI;
+            Point::new(center.x + angle.cos() * rx, center.y + angle.sin() * ry)
+        })
+        .collect()
+}
+
+fn rounded_rect(region: Region, radius: f32) -> Vec<Point> {
+    let Region { pos, size } = region;
+    let radius = radius
+        .min(size.width / 2_f32)
+        .min(size.height / 2_f32)
+        .max(0_f32);
+    if radius == 0_f32 {
+        return corners(region);
+    }
+
+    // The center of each corner's arc, starting from the top left and going clockwise
+    let Size { width, height } = size;
+    let centers = [
+        Point::new(pos.x + radius, pos.y + radius),
+        Point::new(pos.x + width - radius, pos.y + radius),
+        Point::new(pos.x + width - radius, pos.y + height - radius),
+        Point::new(pos.x + radius, pos.y + height - radius),
+    ];
+
+    let steps = CURVE_SEGMENTS / 4;
+    let mut points = Vec::with_capacity(4 * (steps + 1));
+    for (corner, center) in centers.iter().enumerate() {
+        for step in 0..=steps {
+            let angle = PI + (corner as f32 + step as f32 / steps as f32) * PI / 2_f32;
+            points.push(Point::new(
+                center.x + angle.cos() * radius,
+                center.y + angle.sin() * radius,
+            ));
+        }
+    }
+
+    points
+}
+
+// Turns a path into polylines, and whether each of them was closed
+fn flatten(path: &Path) -> Vec<(Vec<Point>, bool)> {
+    let mut subpaths = Vec::new();
+    let mut current: Vec<Point> = Vec::new();
+    let mut last = Point::zero();
+
+    let finish = |current: &mut Vec<Point>, subpaths: &mut Vec<(Vec<Point>, bool)>, closed| {
+        if current.len() > 1 {
+            subpaths.push((std::mem::take(current), closed));
+        }
+        current.clear();
+    };
+
+    for command in path.commands() {
+        match *command {
+            PathCommand::MoveTo(point) => {
+                finish(&mut current, &mut subpaths, false);
+                current.push(point);
+                last = point;
+            }
+            PathCommand::LineTo(point) => {
+                if current.is_empty() {
+                    current.push(last);
+                }
+                current.push(point);
+                last = point;
+            }
+            PathCommand::QuadTo(control, point) => {
+                if current.is_empty() {
+                    current.push(last);
+                }
+                let start = last;
+                for i in 1..=CURVE_SEGMENTS {
+                    let t = i as f32 / CURVE_SEGMENTS as f32;
+                    let u = 1_f32 - t;
+                    current.push(Point::new(
+                        u * u * start.x + 2_f32 * u * t * control.x + t * t * point.x,
+                        u * u * start.y + 2_f32 * u * t * control.y + t * t * point.y,
+                    ));
+                }
+                last = point;
+            }
+            PathCommand::CubicTo(control1, control2, point) => {
+                if current.is_empty() {
+                    current.push(last);
+                }
+                let start = last;
+                for i in 1..=CURVE_SEGMENTS {
+                    let t = i as f32 / CURVE_SEGMENTS as f32;
+                    let u = 1_f32 - t;
+                    let (a, b, c, d) = (u * u * u, 3_f32 * u * u * t, 3_f32 * u * t * t, t * t * t);
+                    current.push(Point::new(
+                        a * start.x + b * control1.x + c * control2.x + d * point.x,
+                        a * start.y + b * control1.y + c * control2.y + d * point.y,
+                    ));
+                }
+                last = point;
+            }
+            PathCommand::Close => {
+                if let Some(&first) = current.first() {
+                    last = first;
+                }
+                finish(&mut current, &mut subpaths, true);
+            }
+        }
+    }
+    finish(&mut current, &mut subpaths, false);
+
+    subpaths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Clip, Draw};
+
+    fn region(x: f32, y: f32, width: f32, height: f32) -> Region {
+        Region::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    #[test]
+    fn fill_and_blend() {
+        let mut list = DisplayList::new();
+        list.fill_rect(region(0_f32, 0_f32, 4_f32, 4_f32), Color::WHITE);
+        // Covers half of the pixels in the second column
+        list.fill_rect(
+            region(1.5_f32, 0_f32, 2.5_f32, 4_f32),
+            Color::rgb(255, 0, 0),
+        );
+
+        list.push_layer(0.5_f32);
+        list.fill_rect(region(0_f32, 3_f32, 4_f32, 1_f32), Color::BLACK);
+        list.pop_layer();
+
+        let pixmap = Rasterizer::rasterize(&list, region(0_f32, 0_f32, 4_f32, 4_f32));
+        assert_eq!(pixmap.pixel(0, 0), Color::WHITE);
+        assert_eq!(pixmap.pixel(1, 0), Color::rgb(255, 128, 128));
+        assert_eq!(pixmap.pixel(2, 0), Color::rgb(255, 0, 0));
+        assert_eq!(pixmap.pixel(0, 3), Color::rgb(128, 128, 128));
+    }
+
+    #[test]
+    fn clip_and_transform() {
+        let mut list = DisplayList::new();
+        list.push_transform(Transform::translate(Vector::new(2_f32, 0_f32)));
+        list.push_clip(region(0_f32, 0_f32, 2_f32, 2_f32));
+        list.fill_rounded_rect(region(0_f32, 0_f32, 8_f32, 8_f32), 1_f32, Color::BLACK);
+        list.pop_clip();
+        list.pop_transform();
+
+        // The window starts at (1, 0), so everything is shifted one pixel left
+        let pixmap = Rasterizer::rasterize(&list, region(1_f32, 0_f32, 4_f32, 4_f32));
+        assert_eq!(pixmap.pixel(0, 1), Color::TRANSPARENT);
+        assert_eq!(pixmap.pixel(2, 1), Color::BLACK);
+        assert_eq!(pixmap.pixel(3, 3), Color::TRANSPARENT);
+
+        // The top left corner is rounded off
+        assert!(pixmap.pixel(1, 0).a < 255 && pixmap.pixel(1, 0).a > 0);
+    }
+
+    #[test]
+    fn stroke() {
+        let mut list = DisplayList::new();
+        let line = Path::new()
+            .move_to(Point::new(0_f32, 2_f32))
+            .line_to(Point::new(4_f32, 2_f32));
+        list.stroke_path(line, Stroke::new(Color::BLACK, 2_f32));
+
+        let pixmap = Rasterizer::rasterize(&list, region(0_f32, 0_f32, 4_f32, 4_f32));
+        assert_eq!(pixmap.pixel(0, 0), Color::TRANSPARENT);
+        assert_eq!(pixmap.pixel(0, 1), Color::BLACK);
+        assert_eq!(pixmap.pixel(3, 2), Color::BLACK);
+        assert_eq!(pixmap.pixel(3, 3), Color::TRANSPARENT);
+        assert!(pixmap.encode_png().starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn nan_points() {
+        // The edges through the point that isn't a number are skipped, rather than panicking
+        let mut list = DisplayList::new();
+        let path = Path::new()
+            .move_to(Point::new(0_f32, 0_f32))
+            .line_to(Point::new(4_f32, 0_f32))
+            .line_to(Point::new(f32::NAN, 4_f32))
+            .line_to(Point::new(0_f32, 4_f32));
+        list.fill_path(path, Color::BLACK);
+
+        let pixmap = Rasterizer::rasterize(&list, region(0_f32, 0_f32, 4_f32, 4_f32));
+        assert_eq!(pixmap.width(), 4);
+    }
+}
@@ -0,0 +1,499 @@
+use crate::canvas::{Backend, Clip, Color, Command, Draw, ImageId, Path, PathCommand};
+use crate::space::{Point, Region};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+
+// A canvas that writes what's drawn to it as an SVG document, so layouts can be looked at in a browser
+// (or diffed as text). SVG coordinates are the same as the window's, with the view box covering the
+// region the canvas was created with.
+// Each push opens a group that's closed by the matching pop, and with 'RenderHooks::draw' set on the
+// GuiContext each device's output gets a group of its own, annotated with the device's names. A device
+// drawn on a higher layer comes after its ancestors' groups have closed, so it gets copies of them.
+pub struct SvgCanvas {
+    region: Region,
+    body: String,
+    // Groups that have been opened but not closed yet
+    depth: usize,
+    clips: usize,
+    images: HashMap<ImageId, String>,
+}
+
+impl SvgCanvas {
+    pub fn new(region: Region) -> Self {
+        SvgCanvas {
+            region,
+            body: String::new(),
+            depth: 0,
+            clips: 0,
+            images: HashMap::new(),
+        }
+    }
+
+    // Sets what 'Command::Image' links to for the given image (eg, the path of a png next to the svg).
+    // Images without one are drawn as an empty rect with the image's id.
+    pub fn set_image_href(&mut self, id: ImageId, href: &str) {
+        self.images.insert(id, href.to_string());
+    }
+
+    pub fn clear(&mut self) {
+        self.body.clear();
+        self.depth = 0;
+        self.clips = 0;
+    }
+
+    // The whole document, with any groups that are still open closed at the end
+    pub fn to_svg(&self) -> String {
+        let Region { pos, size } = self.region;
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+            size.width, size.height, pos.x, pos.y, size.width, size.height
+        )
+        .unwrap();
+        svg.push_str(&self.body);
+        for depth in (0..self.depth).rev() {
+            indent(&mut svg, depth + 1);
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn save_svg<P: AsRef<std::path::Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::write(path, self.to_svg())
+    }
+
+    fn write(&mut self, command: &Command) {
+        match command {
+            Command::Rect { region, color } => {
+                let element = format!("<rect {}{}/>", rect(*region), fill(*color));
+                self.element(&element);
+            }
+            Command::RoundedRect {
+                region,
+                radius,
+                color,
+            } => {
+                let element = format!(
+                    r#"<rect {} rx="{}"{}/>"#,
+                    rect(*region),
+                    radius,
+                    fill(*color)
+                );
+                self.element(&element);
+            }
+            Command::Path { path, fill, stroke } => {
+                if path.is_empty() {
+                    return;
+                }
+
+                let mut element = format!(r#"<path d="{}""#, path_data(path));
+                match fill {
+                    Some(color) => element.push_str(&paint("fill", *color)),
+                    None => element.push_str(r#" fill="none""#),
+                }
+                if let Some(stroke) = stroke {
+                    element.push_str(&paint("stroke", stroke.color));
+                    write!(
+                        element,
+                        r#" stroke-width="{}" stroke-linejoin="round""#,
+                        stroke.width
+                    )
+                    .unwrap();
+                }
+                element.push_str("/>");
+                self.element(&element);
+            }
+            Command::Text(run) => {
+                let element = format!(
+                    r#"<text x="{}" y="{}" font-size="{}"{}>{}</text>"#,
+                    run.origin.x,
+                    run.origin.y,
+                    run.font_size,
+                    fill(run.color),
+                    escape(&run.text)
+                );
+                self.element(&element);
+            }
+            Command::Glyphs(run) => {
+                // Each glyph gets its own position, so the viewer's font doesn't move them around
+                let xs: Vec<String> = run.glyphs.iter().map(|g| g.origin.x.to_string()).collect();
+                let ys: Vec<String> = run.glyphs.iter().map(|g| g.origin.y.to_string()).collect();
+                let text: String = run.glyphs.iter().map(|glyph| glyph.ch).collect();
+                let element = format!(
+                    r#"<text x="{}" y="{}" font-size="{}"{}>{}</text>"#,
+                    xs.join(" "),
+                    ys.join(" "),
+                    run.font_size,
+                    fill(run.color),
+                    escape(&text)
+                );
+                self.element(&element);
+            }
+            Command::Image { image, region } => {
+                let element = match self.images.get(image) {
+                    Some(href) => format!(r#"<image {} href="{}"/>"#, rect(*region), escape(href)),
+                    None => format!(
+                        r#"<rect {} fill="none" data-image="{}"/>"#,
+                        rect(*region),
+                        image.0
+                    ),
+                };
+                self.element(&element);
+            }
+            Command::PushClip(region) => {
+                let id = format!("clip{}", self.clips);
+                self.clips += 1;
+
+                let clip_path = format!(
+                    r#"<clipPath id="{}"><rect {}/></clipPath>"#,
+                    id,
+                    rect(*region)
+                );
+                self.element(&clip_path);
+                self.open(&format!(r#"<g clip-path="url(#{})">"#, id));
+            }
+            Command::PushTransform(t) => self.open(&format!(
+                r#"<g transform="matrix({} {} {} {} {} {})">"#,
+                t.a, t.b, t.c, t.d, t.e, t.f
+            )),
+            Command::PushLayer { opacity } => self.open(&format!(r#"<g opacity="{}">"#, opacity)),
+            Command::BeginDevice(device) => {
+                let element = if device.is_unnamed() {
+                    format!(r#"<g data-type-id="{}">"#, device.type_id)
+                } else {
+                    format!(
+                        r#"<g data-device="{}" data-package="{}" data-type-id="{}">"#,
+                        escape(device.type_name),
+                        escape(device.package_name),
+                        device.type_id
+                    )
+                };
+                self.open(&element);
+            }
+            Command::PopClip | Command::PopTransform | Command::PopLayer | Command::EndDevice => {
+                self.close()
+            }
+        }
+    }
+
+    fn element(&mut self, element: &str) {
+        indent(&mut self.body, self.depth + 1);
+        self.body.push_str(element);
+        self.body.push('\n');
+    }
+
+    fn open(&mut self, element: &str) {
+        self.element(element);
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        // Unmatched pops are ignored, rather than producing a broken document
+        if self.depth > 0 {
+            self.depth -= 1;
+            self.element("</g>");
+        }
+    }
+}
+
+impl Draw for SvgCanvas {
+    fn draw(&mut self, command: Command) {
+        self.write(&command);
+    }
+}
+
+impl Clip for SvgCanvas {
+    fn push_clip(&mut self, region: Region) {
+        self.write(&Command::PushClip(region));
+    }
+
+    fn pop_clip(&mut self) {
+        self.write(&Command::PopClip);
+    }
+}
+
+impl Backend for SvgCanvas {
+    fn execute(&mut self, command: &Command) {
+        self.write(command);
+    }
+}
+
+fn indent(out: &mut String, depth: usize) {
+    out.push_str(&"  ".repeat(depth));
+}
+
+fn rect(region: Region) -> String {
+    format!(
+        r#"x="{}" y="{}" width="{}" height="{}""#,
+        region.pos.x, region.pos.y, region.size.width, region.size.height
+    )
+}
+
+fn fill(color: Color) -> String {
+    paint("fill", color)
+}
+
+// A color attribute, plus an opacity attribute for colors that aren't opaque
+fn paint(attribute: &str, color: Color) -> String {
+    let mut paint = format!(
+        r##" {}="#{:02x}{:02x}{:02x}""##,
+        attribute, color.r, color.g, color.b
+    );
+    if color.a != 255 {
+        write!(
+            paint,
+            r#" {}-opacity="{}""#,
+            attribute,
+            color.a as f32 / 255_f32
+        )
+        .unwrap();
+    }
+    paint
+}
+
+fn path_data(path: &Path) -> String {
+    let point = |p: &Point| format!("{} {}", p.x, p.y);
+    let parts: Vec<String> = path
+        .commands()
+        .iter()
+        .map(|command| match command {
+            PathCommand::MoveTo(p) => format!("M {}", point(p)),
+            PathCommand::LineTo(p) => format!("L {}", point(p)),
+            PathCommand::QuadTo(c, p) => format!("Q {} {}", point(c), point(p)),
+            PathCommand::CubicTo(c1, c2, p) => {
+                format!("C {} {} {}", point(c1), point(c2), point(p))
+            }
+            PathCommand::Close => "Z".to_string(),
+        })
+        .collect();
+    parts.join(" ")
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Stroke, TextRun};
+    use crate::devices::{Padding, PaddingRenderer};
+    use crate::prelude::*;
+    use crate::util::ref_move::Ext;
+    use std::rc::Rc;
+
+    fn region(x: f32, y: f32, width: f32, height: f32) -> Region {
+        Region::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    #[test]
+    fn elements() {
+        let mut svg = SvgCanvas::new(region(0_f32, 0_f32, 10_f32, 10_f32));
+        svg.push_clip(region(1_f32, 1_f32, 8_f32, 8_f32));
+        svg.fill_rect(
+            region(0_f32, 0_f32, 4_f32, 2.5_f32),
+            Color::rgba(255, 0, 0, 51),
+        );
+        svg.draw_path(
+            Path::new()
+                .move_to(Point::zero())
+                .line_to(Point::new(4_f32, 0_f32))
+                .close(),
+            None,
+            Some(Stroke::new(Color::BLACK, 2_f32)),
+        );
+        svg.draw_text(TextRun {
+            text: "a < b".to_string(),
+            origin: Point::new(1_f32, 9_f32),
+            font_size: 8_f32,
+            color: Color::WHITE,
+        });
+        svg.draw_image(ImageId(3), region(0_f32, 0_f32, 1_f32, 1_f32));
+        // Left open, so it's closed when the document is finished
+        svg.push_layer(0.5_f32);
+
+        let expected = [
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10" viewBox="0 0 10 10">"#,
+            r#"  <clipPath id="clip0"><rect x="1" y="1" width="8" height="8"/></clipPath>"#,
+            r#"  <g clip-path="url(#clip0)">"#,
+            r##"    <rect x="0" y="0" width="4" height="2.5" fill="#ff0000" fill-opacity="0.2"/>"##,
+            concat!(
+                r##"    <path d="M 0 0 L 4 0 Z" fill="none" stroke="#000000" stroke-width="2" "##,
+                r#"stroke-linejoin="round"/>"#
+            ),
+            r##"    <text x="1" y="9" font-size="8" fill="#ffffff">a &lt; b</text>"##,
+            r#"    <rect x="0" y="0" width="1" height="1" fill="none" data-image="3"/>"#,
+            r#"    <g opacity="0.5">"#,
+            "    </g>",
+            "  </g>",
+            "</svg>",
+        ];
+        assert_eq!(svg.to_svg().lines().collect::<Vec<_>>(), expected);
+    }
+
+    // Draws a background, with a swatch inset into it (on the popup layer, if it's true)
+    struct Frame(bool);
+    struct Swatch(Color);
+
+    impl Device for Frame {
+        fn type_id() -> TypeId {
+            TypeId::new(0xb8cd000c_cc69_4933_9998_bda5c4729a03)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Frame"
+        }
+    }
+
+    impl Device for Swatch {
+        fn type_id() -> TypeId {
+            TypeId::new(0xf9f50199_4b94_4cb0_9c71_21b3396a0af4)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Swatch"
+        }
+    }
+
+    struct FrameRenderer;
+    struct SwatchRenderer;
+
+    impl<'frm> Renderer<'frm, SvgCanvas> for FrameRenderer {
+        type Device = Frame;
+        type Layout = (bool, LayoutNode);
+
+        fn layout<'thrd>(
+            &self,
+            Frame(popup): Frame,
+            ctx: &mut LayoutContext<'thrd, 'frm, SvgCanvas>,
+        ) -> LayoutResult<(bool, LayoutNode)> {
+            let padding = Padding(Thickness::uniform(2_f32)).move_anchor();
+            let tree = |mut visitor: LayoutTreeVisitor<SvgCanvas>| {
+                let swatch = Swatch(Color::BLACK).move_anchor();
+                visitor.device(SocketName::default(), swatch);
+            };
+
+            match ctx.device_tree(ctx.max_size(), padding, tree) {
+                LayoutResult::CompleteNode(node) => ctx.layout(node.min_size, (popup, node)),
+                _ => LayoutResult::None,
+            }
+        }
+
+        fn render<'ctx>(
+            &self,
+            (popup, node): (bool, LayoutNode),
+            ctx: RenderContext<'ctx, 'frm, SvgCanvas>,
+            canvas: &mut SvgCanvas,
+        ) {
+            canvas.fill_rect(ctx.region(), Color::WHITE);
+            match popup {
+                true => ctx.render_in_layer(Layer::POPUP, node, ctx.region(), canvas),
+                false => ctx.render(node, ctx.region(), canvas),
+            }
+        }
+    }
+
+    impl<'frm> Renderer<'frm, SvgCanvas> for SwatchRenderer {
+        type Device = Swatch;
+        type Layout = Color;
+
+        fn layout<'thrd>(
+            &self,
+            device: Swatch,
+            ctx: &mut LayoutContext<'thrd, 'frm, SvgCanvas>,
+        ) -> LayoutResult<Color> {
+            ctx.layout(Size::new(1_f32, 1_f32), device.0)
+        }
+
+        fn render<'ctx>(
+            &self,
+            color: Color,
+            ctx: RenderContext<'ctx, 'frm, SvgCanvas>,
+            canvas: &mut SvgCanvas,
+        ) {
+            canvas.fill_rect(ctx.region(), color);
+        }
+    }
+
+    fn render(popup: bool) -> Vec<String> {
+        let mut gui = GuiContext::<SvgCanvas>::default();
+        gui.register::<Frame>(Rc::new(FrameRenderer));
+        gui.register::<Padding>(Rc::new(PaddingRenderer));
+        gui.register::<Swatch>(Rc::new(SwatchRenderer));
+        gui.set_render_hooks(Some(RenderHooks::draw()));
+
+        let window = region(0_f32, 0_f32, 8_f32, 6_f32);
+        let mut svg = SvgCanvas::new(window);
+        gui.render_window(window, Frame(popup).move_anchor(), &mut svg)
+            .unwrap();
+        svg.to_svg().lines().map(str::to_string).collect()
+    }
+
+    fn open(name: &str, type_id: TypeId) -> String {
+        format!(
+            r#"<g data-device="{}" data-package="buoy" data-type-id="{}">"#,
+            name, type_id
+        )
+    }
+
+    #[test]
+    fn device_groups() {
+        let expected = [
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="6" viewBox="0 0 8 6">"#
+                .to_string(),
+            format!("  {}", open("Frame", Frame::type_id())),
+            r##"    <rect x="0" y="0" width="8" height="6" fill="#ffffff"/>"##.to_string(),
+            format!("    {}", open("Padding", Padding::type_id())),
+            format!("      {}", open("Swatch", Swatch::type_id())),
+            r##"        <rect x="2" y="2" width="4" height="2" fill="#000000"/>"##.to_string(),
+            "      </g>".to_string(),
+            "    </g>".to_string(),
+            "  </g>".to_string(),
+            "</svg>".to_string(),
+        ];
+        assert_eq!(render(false), expected);
+    }
+
+    #[test]
+    fn popup_groups() {
+        // The swatch is drawn after the frame's group is closed, so the group is opened again around it
+        let expected = [
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="6" viewBox="0 0 8 6">"#
+                .to_string(),
+            format!("  {}", open("Frame", Frame::type_id())),
+            r##"    <rect x="0" y="0" width="8" height="6" fill="#ffffff"/>"##.to_string(),
+            "  </g>".to_string(),
+            format!("  {}", open("Frame", Frame::type_id())),
+            format!("    {}", open("Padding", Padding::type_id())),
+            format!("      {}", open("Swatch", Swatch::type_id())),
+            r##"        <rect x="2" y="2" width="4" height="2" fill="#000000"/>"##.to_string(),
+            "      </g>".to_string(),
+            "    </g>".to_string(),
+            "  </g>".to_string(),
+            "</svg>".to_string(),
+        ];
+        assert_eq!(render(true), expected);
+    }
+}
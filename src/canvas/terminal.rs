@@ -0,0 +1,449 @@
+use crate::canvas::{Backend, Clip, Color, Command, Draw};
+use crate::space::{Point, Region, Size, Vector};
+use std::fmt::Write;
+
+// A character cell, with 'None' for the terminal's own default colors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            ch: ' ',
+            fg: None,
+            bg: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BorderStyle {
+    Single,
+    Double,
+    Rounded,
+    Heavy,
+}
+
+impl BorderStyle {
+    // Horizontal, vertical, then the corners clockwise from the top left
+    fn chars(self) -> [char; 6] {
+        match self {
+            BorderStyle::Single => ['─', '│', '┌', '┐', '┘', '└'],
+            BorderStyle::Double => ['═', '║', '╔', '╗', '╝', '╚'],
+            BorderStyle::Rounded => ['─', '│', '╭', '╮', '╯', '╰'],
+            BorderStyle::Heavy => ['━', '┃', '┏', '┓', '┛', '┗'],
+        }
+    }
+}
+
+// A range of cells, as [left, right) and [top, bottom)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CellRect {
+    left: isize,
+    top: isize,
+    right: isize,
+    bottom: isize,
+}
+
+impl CellRect {
+    fn intersect(self, other: CellRect) -> CellRect {
+        CellRect {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        }
+    }
+
+    fn contains(&self, column: isize, row: isize) -> bool {
+        column >= self.left && column < self.right && row >= self.top && row < self.bottom
+    }
+}
+
+// A canvas for text UIs, made of a grid of character cells. Regions are mapped onto the grid by
+// dividing by the cell size (one unit per cell, by default) and rounding to the nearest cell boundary.
+// Each character is assumed to take up a single cell.
+// As a 'Draw' canvas it fills rects with their color and draws text runs on the row the middle of
+// their line falls in. Only the translation part of transforms is used, and paths, images and layer
+// opacity are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TerminalCanvas {
+    columns: usize,
+    rows: usize,
+    cell_size: Size,
+    cells: Vec<Cell>,
+    clips: Vec<CellRect>,
+    offsets: Vec<Vector>,
+}
+
+impl TerminalCanvas {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        TerminalCanvas {
+            columns,
+            rows,
+            cell_size: Size::new(1_f32, 1_f32),
+            cells: vec![Cell::default(); columns * rows],
+            clips: Vec::new(),
+            offsets: Vec::new(),
+        }
+    }
+
+    // For layouts that aren't measured in cells (eg, to reuse a layout made for pixels)
+    pub fn with_cell_size(mut self, cell_size: Size) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cell_size(&self) -> Size {
+        self.cell_size
+    }
+
+    // The region covering the whole grid, for passing to 'GuiContext::render_window'
+    pub fn region(&self) -> Region {
+        Region::new(
+            Point::zero(),
+            Size::new(
+                self.columns as f32 * self.cell_size.width,
+                self.rows as f32 * self.cell_size.height,
+            ),
+        )
+    }
+
+    pub fn cell(&self, column: usize, row: usize) -> Cell {
+        self.cells[row * self.columns + column]
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    // The characters on one row, without their colors
+    pub fn row_text(&self, row: usize) -> String {
+        let start = row * self.columns;
+        self.cells[start..start + self.columns]
+            .iter()
+            .map(|cell| cell.ch)
+            .collect()
+    }
+
+    // Resets every cell, and any clips or offsets left over from the last frame
+    pub fn clear(&mut self) {
+        self.cells
+            .iter_mut()
+            .for_each(|cell| *cell = Cell::default());
+        self.clips.clear();
+        self.offsets.clear();
+    }
+
+    // Sets the background of the covered cells, and blanks them out
+    pub fn fill(&mut self, region: Region, bg: Color) {
+        let rect = self.cell_rect(region);
+        self.for_each_cell(rect, |cell| {
+            *cell = Cell {
+                ch: ' ',
+                fg: cell.fg,
+                bg: Some(bg),
+            }
+        });
+    }
+
+    // Draws a box around the inside edge of the region, leaving the backgrounds alone
+    pub fn border(&mut self, region: Region, style: BorderStyle, fg: Color) {
+        let rect = self.cell_rect(region);
+        if rect.right - rect.left < 2 || rect.bottom - rect.top < 2 {
+            return;
+        }
+
+        let [horizontal, vertical, top_left, top_right, bottom_right, bottom_left] = style.chars();
+        let (right, bottom) = (rect.right - 1, rect.bottom - 1);
+        for column in rect.left + 1..right {
+            self.put(column, rect.top, horizontal, fg);
+            self.put(column, bottom, horizontal, fg);
+        }
+        for row in rect.top + 1..bottom {
+            self.put(rect.left, row, vertical, fg);
+            self.put(right, row, vertical, fg);
+        }
+        self.put(rect.left, rect.top, top_left, fg);
+        self.put(right, rect.top, top_right, fg);
+        self.put(right, bottom, bottom_right, fg);
+        self.put(rect.left, bottom, bottom_left, fg);
+    }
+
+    // Writes text starting in the cell containing 'pos', leaving the backgrounds alone.
+    // Text doesn't wrap, and anything outside of the grid or the clip is dropped.
+    pub fn text(&mut self, pos: Point, text: &str, fg: Color) {
+        let pos = self.translate(pos);
+        let column = (pos.x / self.cell_size.width).floor() as isize;
+        let row = (pos.y / self.cell_size.height).floor() as isize;
+        for (i, ch) in text.chars().enumerate() {
+            self.put(column + i as isize, row, ch, fg);
+        }
+    }
+
+    // The ANSI escape sequences that turn a terminal showing 'previous' into this frame, only
+    // touching the cells that changed. Without a previous frame (or if its size is different),
+    // the screen is cleared and every cell is written.
+    pub fn diff(&self, previous: Option<&TerminalCanvas>) -> String {
+        let previous = previous.filter(|p| p.columns == self.columns && p.rows == self.rows);
+        let mut out = String::new();
+        if previous.is_none() {
+            out.push_str("\x1b[0m\x1b[2J");
+        }
+
+        // Where the terminal's cursor is and which colors it's using, once known
+        let mut cursor = None;
+        let mut style = None;
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let cell = self.cell(column, row);
+                if previous.is_some_and(|p| p.cell(column, row) == cell) {
+                    continue;
+                }
+
+                if cursor != Some((column, row)) {
+                    write!(out, "\x1b[{};{}H", row + 1, column + 1).unwrap();
+                }
+                write_style(&mut out, style, (cell.fg, cell.bg));
+                out.push(cell.ch);
+
+                style = Some((cell.fg, cell.bg));
+                // Writing into the last column leaves the cursor somewhere that depends on the terminal
+                cursor = if column + 1 < self.columns {
+                    Some((column + 1, row))
+                } else {
+                    None
+                };
+            }
+        }
+
+        if style.is_some() {
+            out.push_str("\x1b[0m");
+        }
+        out
+    }
+
+    fn offset(&self) -> Vector {
+        self.offsets.last().copied().unwrap_or_else(Vector::zero)
+    }
+
+    fn translate(&self, point: Point) -> Point {
+        let offset = self.offset();
+        Point::new(point.x + offset.x, point.y + offset.y)
+    }
+
+    fn clip(&self) -> CellRect {
+        let bounds = CellRect {
+            left: 0,
+            top: 0,
+            right: self.columns as isize,
+            bottom: self.rows as isize,
+        };
+        self.clips.last().copied().unwrap_or(bounds)
+    }
+
+    fn cell_rect(&self, region: Region) -> CellRect {
+        let pos = self.translate(region.pos);
+        let (width, height) = (self.cell_size.width, self.cell_size.height);
+        CellRect {
+            left: (pos.x / width).round() as isize,
+            top: (pos.y / height).round() as isize,
+            right: ((pos.x + region.size.width) / width).round() as isize,
+            bottom: ((pos.y + region.size.height) / height).round() as isize,
+        }
+    }
+
+    fn for_each_cell<F: FnMut(&mut Cell)>(&mut self, rect: CellRect, mut f: F) {
+        let rect = rect.intersect(self.clip());
+        for row in rect.top.max(0)..rect.bottom {
+            for column in rect.left.max(0)..rect.right {
+                f(&mut self.cells[row as usize * self.columns + column as usize]);
+            }
+        }
+    }
+
+    fn put(&mut self, column: isize, row: isize, ch: char, fg: Color) {
+        if self.clip().contains(column, row) {
+            let cell = &mut self.cells[row as usize * self.columns + column as usize];
+            cell.ch = ch;
+            cell.fg = Some(fg);
+        }
+    }
+}
+
+impl Draw for TerminalCanvas {
+    fn draw(&mut self, command: Command) {
+        self.execute(&command);
+    }
+}
+
+impl Clip for TerminalCanvas {
+    fn push_clip(&mut self, region: Region) {
+        let rect = self.cell_rect(region).intersect(self.clip());
+        self.clips.push(rect);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+}
+
+impl Backend for TerminalCanvas {
+    fn execute(&mut self, command: &Command) {
+        match command {
+            Command::Rect { region, color } | Command::RoundedRect { region, color, .. } => {
+                self.fill(*region, *color)
+            }
+            Command::Text(run) => {
+                let pos = Point::new(run.origin.x, run.origin.y - run.font_size / 2_f32);
+                self.text(pos, &run.text, run.color);
+            }
+            Command::Glyphs(run) => {
+                for glyph in &run.glyphs {
+                    let pos = Point::new(glyph.origin.x, glyph.origin.y - run.font_size / 2_f32);
+                    self.text(pos, glyph.ch.encode_utf8(&mut [0; 4]), run.color);
+                }
+            }
+            Command::PushClip(region) => self.push_clip(*region),
+            Command::PopClip => self.pop_clip(),
+            Command::PushTransform(transform) => {
+                let offset = self.offset() + Vector::new(transform.e, transform.f);
+                self.offsets.push(offset);
+            }
+            Command::PopTransform => {
+                self.offsets.pop();
+            }
+            Command::Path { .. }
+            | Command::Image { .. }
+            | Command::PushLayer { .. }
+            | Command::PopLayer
+            | Command::BeginDevice(_)
+            | Command::EndDevice => (),
+        }
+    }
+}
+
+// Switches the terminal from one pair of colors to another, writing nothing for unchanged ones
+fn write_style(
+    out: &mut String,
+    from: Option<(Option<Color>, Option<Color>)>,
+    (fg, bg): (Option<Color>, Option<Color>),
+) {
+    let mut codes = Vec::new();
+    if from.map(|(fg, _)| fg) != Some(fg) {
+        codes.push(match fg {
+            Some(c) => format!("38;2;{};{};{}", c.r, c.g, c.b),
+            None => "39".to_string(),
+        });
+    }
+    if from.map(|(_, bg)| bg) != Some(bg) {
+        codes.push(match bg {
+            Some(c) => format!("48;2;{};{};{}", c.r, c.g, c.b),
+            None => "49".to_string(),
+        });
+    }
+
+    if !codes.is_empty() {
+        write!(out, "\x1b[{}m", codes.join(";")).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{DisplayList, TextRun, Transform};
+
+    fn region(x: f32, y: f32, width: f32, height: f32) -> Region {
+        Region::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    fn text(canvas: &TerminalCanvas) -> Vec<String> {
+        (0..canvas.rows()).map(|row| canvas.row_text(row)).collect()
+    }
+
+    #[test]
+    fn cells() {
+        let blue = Color::rgb(0, 0, 255);
+        let mut canvas = TerminalCanvas::new(8, 4);
+        canvas.fill(region(0.4_f32, 0_f32, 3.2_f32, 1_f32), blue);
+        canvas.border(
+            region(1_f32, 0_f32, 6_f32, 4_f32),
+            BorderStyle::Rounded,
+            Color::WHITE,
+        );
+
+        canvas.push_clip(region(2_f32, 1_f32, 3_f32, 2_f32));
+        canvas.text(Point::new(2_f32, 1.5_f32), "clipped", Color::WHITE);
+        canvas.pop_clip();
+
+        assert_eq!(
+            text(&canvas),
+            vec![" ╭────╮ ", " │cli │ ", " │    │ ", " ╰────╯ "]
+        );
+        assert_eq!(canvas.cell(0, 0).bg, Some(blue));
+        assert_eq!(canvas.cell(4, 0).bg, None);
+        assert_eq!(canvas.cell(2, 1).fg, Some(Color::WHITE));
+    }
+
+    #[test]
+    fn replay() {
+        let mut list = DisplayList::new();
+        list.push_transform(Transform::translate(Vector::new(10_f32, 0_f32)));
+        list.fill_rect(region(0_f32, 0_f32, 20_f32, 10_f32), Color::BLACK);
+        list.draw_text(TextRun {
+            text: "hi".to_string(),
+            origin: Point::new(10_f32, 20_f32),
+            font_size: 10_f32,
+            color: Color::WHITE,
+        });
+        list.pop_transform();
+
+        let mut canvas = TerminalCanvas::new(4, 2).with_cell_size(Size::new(10_f32, 10_f32));
+        list.replay(&mut canvas);
+        assert_eq!(text(&canvas), vec!["    ", "  hi"]);
+        assert_eq!(canvas.cell(1, 0).bg, Some(Color::BLACK));
+        assert_eq!(canvas.cell(2, 0).bg, Some(Color::BLACK));
+        assert_eq!(canvas.cell(3, 0).bg, None);
+    }
+
+    #[test]
+    fn diff() {
+        let red = Color::rgb(255, 0, 0);
+        let mut first = TerminalCanvas::new(3, 2);
+        first.text(Point::new(0_f32, 0_f32), "ab", red);
+        assert_eq!(
+            first.diff(None),
+            concat!(
+                "\x1b[0m\x1b[2J\x1b[1;1H\x1b[38;2;255;0;0;49mab\x1b[39m \x1b[2;1H   ",
+                "\x1b[0m"
+            )
+        );
+
+        // Only the changed cells are written, moving the cursor when they aren't next to each other
+        let mut second = first.clone();
+        second.text(Point::new(1_f32, 0_f32), "c", red);
+        second.fill(region(0_f32, 1_f32, 1_f32, 1_f32), red);
+        second.text(Point::new(2_f32, 1_f32), "d", red);
+        assert_eq!(
+            second.diff(Some(&first)),
+            concat!(
+                "\x1b[1;2H\x1b[38;2;255;0;0;49mc",
+                "\x1b[2;1H\x1b[39;48;2;255;0;0m ",
+                "\x1b[2;3H\x1b[38;2;255;0;0;49md\x1b[0m"
+            )
+        );
+        assert_eq!(second.diff(Some(&second)), "");
+    }
+}
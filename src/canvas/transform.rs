@@ -0,0 +1,97 @@
+use crate::space::{Point, Vector};
+
+// A 2D affine transform, mapping (x, y) to (a*x + c*y + e, b*x + d*y + f)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform::scale(1_f32, 1_f32)
+    }
+
+    pub fn translate(offset: Vector) -> Self {
+        Transform {
+            e: offset.x,
+            f: offset.y,
+            ..Transform::identity()
+        }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Self {
+        Transform {
+            a: x,
+            b: 0_f32,
+            c: 0_f32,
+            d: y,
+            e: 0_f32,
+            f: 0_f32,
+        }
+    }
+
+    // Rotates clockwise (since y points down) by the given angle in radians
+    pub fn rotate(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Transform {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            e: 0_f32,
+            f: 0_f32,
+        }
+    }
+
+    // The transform that applies 'self', and then 'next'
+    pub fn then(self, next: Transform) -> Self {
+        Transform {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        Point::new(
+            self.a * point.x + self.c * point.y + self.e,
+            self.b * point.x + self.d * point.y + self.f,
+        )
+    }
+
+    pub fn invert(&self) -> Option<Transform> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0_f32 {
+            return None;
+        }
+
+        Some(Transform {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
+    // Whether this only translates and scales, so regions stay axis-aligned
+    pub fn is_axis_aligned(&self) -> bool {
+        self.b == 0_f32 && self.c == 0_f32
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
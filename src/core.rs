@@ -1,5 +1,11 @@
 pub mod device;
+pub mod error;
 pub mod id;
+pub mod input;
 pub mod message;
+pub mod persist;
+pub mod state;
+pub mod topic;
+pub mod trace;
 
 pub mod context;
@@ -1,17 +1,24 @@
 mod gui;
-pub use gui::GuiContext;
+pub use gui::{GuiContext, MissingRendererPolicy};
 
 mod frame;
 pub use frame::FrameContext;
 
 mod thread;
+pub(in crate::core) use thread::LocalRenderers;
 pub use thread::ThreadContext;
 
-mod layout;
+pub(in crate::core) mod layout;
 pub use layout::{LayoutContext, LayoutTree, LayoutTreeVisitor};
 
 // TODO: Should this be part of a different module?
 pub use layout::{LayoutNode, LayoutResult};
 
+mod parallel;
+
+mod shared;
+pub(in crate::core) use shared::{SharedContext, SharedRenderers};
+
 mod render;
-pub use render::RenderContext;
+pub use render::{Layer, RenderContext, RenderHooks};
+pub(in crate::core) use render::{LayeredNodes, RenderOutput};
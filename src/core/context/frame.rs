@@ -1,15 +1,84 @@
-use crate::core::message::{Inbox, Message, MessageMap};
+use crate::core::id::Id;
+use crate::core::input::focused_id;
+use crate::core::message::{Inbox, Message, MessageMap, Outbox};
+use std::sync::{Mutex, MutexGuard};
 
 pub struct FrameContext {
-    incoming_messages: MessageMap,
+    // Messages only have to be 'Send', so layout threads take turns reading them
+    messages: Mutex<FrameMessages>,
+}
+
+struct FrameMessages {
+    incoming: MessageMap,
+
+    // Messages written by each pass so far this frame, in order
+    passes: Vec<MessageMap>,
+}
+
+impl FrameMessages {
+    fn read_pass_message<T: Message>(&self, inbox: Inbox<T>) -> Option<T> {
+        let id = inbox.id();
+        let messages = self.passes.iter().rev().find(|m| m.contains(id))?;
+        messages.read(inbox)
+    }
+
+    fn has_pass_message(&self, id: Id) -> bool {
+        self.passes.iter().any(|m| m.contains(id))
+    }
 }
 
 impl FrameContext {
     pub fn new(incoming_messages: MessageMap) -> Self {
-        FrameContext { incoming_messages }
+        FrameContext {
+            messages: Mutex::new(FrameMessages {
+                incoming: incoming_messages,
+                passes: Vec::new(),
+            }),
+        }
+    }
+
+    fn messages(&self) -> MutexGuard<'_, FrameMessages> {
+        self.messages.lock().unwrap()
     }
 
     pub fn read_message<T: Message, I: Into<Inbox<T>>>(&self, inbox: I) -> Option<T> {
-        self.incoming_messages.read(inbox.into())
+        let inbox = inbox.into();
+        let messages = self.messages();
+
+        // Later passes take precedence over earlier ones, which take precedence over the last frame
+        if messages.has_pass_message(inbox.id()) {
+            messages.read_pass_message(inbox)
+        } else {
+            messages.incoming.read(inbox)
+        }
+    }
+
+    // The Id that has keyboard focus this frame, if any
+    pub fn focused(&self) -> Option<Id> {
+        self.read_message(&Outbox::new(focused_id()))
+    }
+
+    // Reads a message written by an earlier pass in this frame, ignoring the last frame's messages.
+    pub fn read_pass_message<T: Message>(&self, inbox: Inbox<T>) -> Option<T> {
+        self.messages().read_pass_message(inbox)
+    }
+
+    pub(in crate::core) fn push_pass_messages(&mut self, messages: MessageMap) {
+        self.messages.get_mut().unwrap().passes.push(messages);
+    }
+
+    pub(in crate::core) fn take_incoming_messages(&mut self) -> MessageMap {
+        std::mem::take(&mut self.messages.get_mut().unwrap().incoming)
+    }
+
+    // Combines the messages written by every pass, to be sent to the next frame. Where passes wrote
+    // to the same Id, the last pass's message is kept.
+    pub(in crate::core) fn take_pass_messages(&mut self) -> MessageMap {
+        let mut result = MessageMap::default();
+        for mut messages in self.messages.get_mut().unwrap().passes.drain(..) {
+            result.overlay(&mut messages);
+        }
+
+        result
     }
 }
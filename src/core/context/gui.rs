@@ -1,15 +1,58 @@
+use crate::core::context::layout::SubDevice;
+use crate::core::context::parallel::LayoutThreads;
 use crate::core::context::*;
 use crate::core::device::*;
+use crate::core::error::BuoyError;
+use crate::core::id::Id;
+use crate::core::input::{FocusMove, InputState, Key, Modifiers, MouseButton, Preedit};
+use crate::core::persist::{Persist, PersistError, PersistRegistry};
+use crate::core::state::{State, StateSnapshot, StateStore, StateValue};
+use crate::core::topic::{Topic, TopicEvent, TopicStore};
+use crate::core::trace::{FrameTrace, MessageTrace, TraceRecord};
 use crate::message::*;
 use crate::space::*;
-use crate::util::arena::Arena;
-use crate::util::ref_move::{ref_move, Anchor};
-use std::collections::{hash_map::Entry, HashMap};
+use crate::util::ref_move::Anchor;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingRendererPolicy {
+    // Devices without a renderer produce no layout, and their children are dropped
+    Skip,
+    // Devices without a renderer are swapped out for a 'MissingDevice', so that whatever renderer is
+    // registered for that can draw a placeholder. Contexts made with 'GuiContext::new' draw a labelled
+    // box with 'PlaceholderRenderer'; others fail the frame unless a renderer is registered.
+    Placeholder,
+    // The frame is abandoned, and 'render_window' returns an error
+    #[default]
+    Fail,
+}
 
 pub struct GuiContext<C> {
     outgoing_messages: MessageMap,
     pub(in crate::core) renderers: HashMap<TypeId, Rc<dyn IntoRenderer<C>>>,
+    // Renderers that can also run on layout threads
+    shared_renderers: SharedRenderers<C>,
+    layout_threads: LayoutThreads,
+    frame: u64,
+    missing_renderer_policy: MissingRendererPolicy,
+    registry: DeviceRegistry,
+    render_hooks: Option<RenderHooks<C>>,
+    input: InputState,
+    state: StateStore,
+    persistent_types: PersistRegistry,
+    debug_messages: bool,
+    message_conflicts: Vec<MessageConflict>,
+    topics: TopicStore,
+    tracing: bool,
+    trace: MessageTrace,
+    // The incoming messages for the frames still to be replayed
+    replay: VecDeque<MessageMap>,
 }
 
 impl<C> Default for GuiContext<C> {
@@ -17,71 +60,576 @@ impl<C> Default for GuiContext<C> {
         GuiContext {
             outgoing_messages: Default::default(),
             renderers: Default::default(),
+            shared_renderers: Default::default(),
+            layout_threads: Default::default(),
+            frame: 0,
+            missing_renderer_policy: Default::default(),
+            registry: Default::default(),
+            render_hooks: None,
+            input: Default::default(),
+            state: Default::default(),
+            persistent_types: Default::default(),
+            debug_messages: false,
+            message_conflicts: Vec::new(),
+            topics: Default::default(),
+            tracing: false,
+            trace: Default::default(),
+            replay: VecDeque::new(),
         }
     }
 }
 
 impl<C: 'static> GuiContext<C> {
+    // Registers a renderer for 'D'. Unlike 'register_device', this records the device's names, so
+    // another device type claiming the same TypeId can be caught.
+    pub fn register<D: Device>(&mut self, renderer_factory: Rc<dyn IntoRenderer<C>>) {
+        if let Err(error) = self.try_register::<D>(renderer_factory) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn try_register<D: Device>(
+        &mut self,
+        renderer_factory: Rc<dyn IntoRenderer<C>>,
+    ) -> Result<(), BuoyError> {
+        self.try_register_info(DeviceInfo::of::<D>(), renderer_factory)
+    }
+
     pub fn register_device(&mut self, type_id: TypeId, renderer_factory: Rc<dyn IntoRenderer<C>>) {
+        if let Err(error) = self.try_register_device(type_id, renderer_factory) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn try_register_device(
+        &mut self,
+        type_id: TypeId,
+        renderer_factory: Rc<dyn IntoRenderer<C>>,
+    ) -> Result<(), BuoyError> {
+        self.try_register_info(DeviceInfo::unnamed(type_id), renderer_factory)
+    }
+
+    fn try_register_info(
+        &mut self,
+        info: DeviceInfo,
+        renderer_factory: Rc<dyn IntoRenderer<C>>,
+    ) -> Result<(), BuoyError> {
         // TODO: assert!(renderer_factory.supports(type_id);
-        match self.renderers.entry(type_id) {
-            Entry::Occupied(_) => panic!("Cannot register the same type id twice"),
-            Entry::Vacant(entry) => {
-                entry.insert(renderer_factory);
-            }
+        self.check_unregistered(&info)?;
+        self.renderers.insert(info.type_id, renderer_factory);
+        self.registry.insert(info)
+    }
+
+    // Registers a renderer for 'D' that can run on the layout threads as well as the thread rendering
+    // the window. The compiler checks that it (along with its devices and layouts) can be sent there.
+    pub fn register_shared<D: Device>(&mut self, renderer_factory: Arc<dyn IntoSharedRenderer<C>>) {
+        if let Err(error) = self.try_register_shared::<D>(renderer_factory) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn try_register_shared<D: Device>(
+        &mut self,
+        renderer_factory: Arc<dyn IntoSharedRenderer<C>>,
+    ) -> Result<(), BuoyError> {
+        let info = DeviceInfo::of::<D>();
+        self.check_unregistered(&info)?;
+        self.shared_renderers.insert(info.type_id, renderer_factory);
+        self.registry.insert(info)
+    }
+
+    fn check_unregistered(&self, info: &DeviceInfo) -> Result<(), BuoyError> {
+        self.registry.check(info)?;
+        let type_id = info.type_id;
+        if self.renderers.contains_key(&type_id) || self.shared_renderers.contains_key(&type_id) {
+            return Err(BuoyError::DuplicateDevice(self.registry.describe(type_id)));
+        }
+        Ok(())
+    }
+
+    // The parts of this that layout threads can see
+    pub(in crate::core) fn shared(&self) -> SharedContext<'_, C> {
+        SharedContext {
+            renderers: &self.shared_renderers,
+            registry: &self.registry,
+            missing_renderer_policy: self.missing_renderer_policy,
+            state: &self.state,
+            topics: &self.topics,
+            frame: self.frame,
+        }
+    }
+
+    // Every device type that has been registered, by TypeId
+    pub fn registry(&self) -> &DeviceRegistry {
+        &self.registry
+    }
+
+    pub fn set_missing_renderer_policy(&mut self, policy: MissingRendererPolicy) {
+        self.missing_renderer_policy = policy;
+    }
+
+    pub fn missing_renderer_policy(&self) -> MissingRendererPolicy {
+        self.missing_renderer_policy
+    }
+
+    pub fn set_render_hooks(&mut self, hooks: Option<RenderHooks<C>>) {
+        self.render_hooks = hooks;
+    }
+
+    pub fn render_hooks(&self) -> Option<&RenderHooks<C>> {
+        self.render_hooks.as_ref()
+    }
+
+    // Records which device wrote each message, so that messages replacing others written to the same
+    // Id in the same frame can be reported by 'message_conflicts'. Off by default, since it slows
+    // every device down a little.
+    pub fn set_debug_messages(&mut self, debug: bool) {
+        self.debug_messages = debug;
+        self.outgoing_messages.set_debug(debug);
+    }
+
+    pub fn debug_messages(&self) -> bool {
+        self.debug_messages
+    }
+
+    // The conflicting writes in the last frame (including input sent to it), while debugging messages
+    pub fn message_conflicts(&self) -> &[MessageConflict] {
+        &self.message_conflicts
+    }
+
+    // Records every message written, mapped and read in each frame, along with the device that did it
+    // and the messages the frame started with, until it's turned off again. Keeping those means deep
+    // copying every message a frame starts with, every frame, so it's slow for anything but debugging.
+    pub fn set_message_tracing(&mut self, tracing: bool) {
+        self.tracing = tracing;
+        self.outgoing_messages.set_tracing(tracing);
+    }
+
+    pub fn message_tracing(&self) -> bool {
+        self.tracing
+    }
+
+    pub fn message_trace(&self) -> &MessageTrace {
+        &self.trace
+    }
+
+    // Takes the frames traced so far, leaving an empty trace to carry on recording into
+    pub fn take_message_trace(&mut self) -> MessageTrace {
+        std::mem::take(&mut self.trace)
+    }
+
+    // Starts each of the next frames with the messages the traced frames started with, in order, in
+    // place of whatever was written for them. Given the same devices, each frame then does what the
+    // traced one did. Input sent during the replay is held until it's over (or stopped), then goes to
+    // the frame after it. State, focus and topics aren't part of the trace, so they aren't replayed.
+    pub fn replay(&mut self, trace: &MessageTrace) {
+        let frames = trace.frames.iter().map(|frame| frame.incoming.copy());
+        self.replay.extend(frames);
+    }
+
+    // How many traced frames are still waiting to be replayed
+    pub fn replay_len(&self) -> usize {
+        self.replay.len()
+    }
+
+    pub fn stop_replay(&mut self) {
+        self.replay.clear();
+    }
+
+    // Starts the threads 'LayoutContext::parallel' may spread its jobs across, which are kept for every
+    // frame until this is called again or the context is dropped. With zero (the default), jobs are run
+    // on the thread that started them. Only renderers registered with 'register_shared' can run on
+    // layout threads; any other device laid out there fails the frame.
+    pub fn set_layout_threads(&mut self, threads: usize) {
+        if threads != self.layout_threads.len() {
+            // Stop the old threads before starting new ones
+            self.layout_threads = Default::default();
+            self.layout_threads = LayoutThreads::new(threads);
         }
     }
 
+    pub fn layout_threads(&self) -> usize {
+        self.layout_threads.len()
+    }
+
+    // The number of frames that have been rendered before this one.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
     pub fn render_window<'frm, D: Anchor<dyn Device + 'frm>>(
         &mut self,
         window_region: Region,
         root: D,
         canvas: &mut C,
-    ) {
-        // Create a frame context and thread context
-        let buffer = Arena::new();
-        let frame_context = FrameContext::new(std::mem::take(&mut self.outgoing_messages));
-        let mut thread_context = ThreadContext::new(&buffer);
-
-        // Create a renderer for the root and allocate it
-        let renderer = thread_context.renderer_for(self, root.get_type_id());
-        let device_index = ref_move(root, |root| renderer.alloc(root));
-
-        // Run layout on the device
-        let layout_ctx = LayoutContext {
-            gui_ctx: self,
-            frame_ctx: &frame_context,
-            thread_ctx: &thread_context,
-
-            max_size: window_region.size,
-            children: Vec::default(),
+    ) -> Result<(), BuoyError> {
+        let mut root = Some(root);
+        self.render_window_passes(window_region, 1, |_| root.take().unwrap(), canvas)
+    }
+
+    // Renders the window in a sequence of passes, each one on top of the last. Messages written during
+    // a pass can be read by the passes after it, so layout can react to where things ended up earlier
+    // in the same frame (rather than lagging a frame behind).
+    // If a pass fails, the passes before it will already have been drawn to the canvas.
+    pub fn render_window_passes<'frm, D, F>(
+        &mut self,
+        window_region: Region,
+        passes: usize,
+        mut root: F,
+        canvas: &mut C,
+    ) -> Result<(), BuoyError>
+    where
+        D: Anchor<dyn Device + 'frm>,
+        F: FnMut(usize) -> D,
+    {
+        // Input stays queued while replaying, since the replayed messages would replace it
+        let replay = self.replay.pop_front();
+        if replay.is_none() {
+            self.write_input_messages();
+        }
+        let mut conflicts = self.outgoing_messages.take_conflicts();
+        let mut records = self.outgoing_messages.take_trace();
+        if let Some(messages) = replay {
+            self.outgoing_messages = messages;
+            self.outgoing_messages.set_debug(self.debug_messages);
+            self.outgoing_messages.set_tracing(self.tracing);
+        }
+        let incoming = match self.tracing {
+            true => Some(self.outgoing_messages.copy()),
+            false => None,
         };
 
-        match renderer.layout(device_index, layout_ctx) {
-            RendererLayoutResult::None => (),
-            RendererLayoutResult::Complete(layout) => {
-                // Get the renderer for the layout
-                let renderer = thread_context.renderer_for(self, layout.type_id);
+        // Create a frame context
+        let mut output = RenderOutput::default();
+        let mut frame_context = FrameContext::new(std::mem::take(&mut self.outgoing_messages));
+
+        for pass in 0..passes {
+            let result = self.render_pass(window_region, root(pass), &frame_context, canvas);
+
+            match result {
+                Ok((messages, pass_output)) => {
+                    frame_context.push_pass_messages(messages);
+                    output.extend(pass_output);
+                }
+                Err(error) => {
+                    // Put the messages back, so the next frame starts from the same state this one did
+                    self.outgoing_messages = frame_context.take_incoming_messages();
+                    self.record_frame(conflicts, records, incoming);
+                    return Err(error);
+                }
+            }
+        }
+
+        // Output messages, and keep the regions the next frame's input will be checked against
+        self.outgoing_messages = frame_context.take_pass_messages();
+        conflicts.extend(self.outgoing_messages.take_conflicts());
+        records.extend(self.outgoing_messages.take_trace());
+        self.outgoing_messages.set_debug(self.debug_messages);
+        self.outgoing_messages.set_tracing(self.tracing);
+        self.record_frame(conflicts, records, incoming);
+        for (topic, value) in self.outgoing_messages.take_events() {
+            self.topics.publish(topic, value, self.frame + 1);
+        }
+        self.input.set_output(output);
+        self.state.collect(self.frame);
+        self.frame += 1;
+
+        Ok(())
+    }
+
+    fn record_frame(
+        &mut self,
+        conflicts: Vec<MessageConflict>,
+        records: Vec<TraceRecord>,
+        incoming: Option<MessageMap>,
+    ) {
+        self.message_conflicts = conflicts;
+        if let Some(incoming) = incoming {
+            let frame = self.frame;
+            self.trace.frames.push(FrameTrace {
+                frame,
+                records,
+                incoming,
+            });
+        }
+    }
 
-                // Render the device
+    fn render_pass<'frm, D: Anchor<dyn Device + 'frm>>(
+        &self,
+        window_region: Region,
+        root: D,
+        frame_context: &FrameContext,
+        canvas: &mut C,
+    ) -> Result<(MessageMap, RenderOutput), BuoyError> {
+        // Create a thread context, and one for each layout thread
+        let mut thread_context = ThreadContext::new(None);
+        thread_context.set_message_tracking(self.debug_messages, self.tracing);
+        let mut workers: Vec<ThreadContext<C>> = (0..self.layout_threads.len())
+            .map(|thread| ThreadContext::new(Some(thread)))
+            .collect();
+        for worker in &mut workers {
+            worker.set_message_tracking(self.debug_messages, self.tracing);
+        }
+        let local = LocalRenderers::new(self);
+        let shared = self.shared();
+        let output = RefCell::new(RenderOutput::default());
+        let layered = RefCell::new(LayeredNodes::default());
+        let ancestors = RefCell::new(Vec::new());
+
+        // Run layout on the device, with the layout threads taking any jobs it starts. If it's still
+        // deferred once nothing else can make progress, its dependencies will never be met so there's
+        // nothing to render.
+        let result = self.layout_threads.scope(&mut workers, |pool| {
+            // Create a renderer for the root and allocate it
+            let (renderer, device_index) = thread_context.alloc(shared, Some(&local), root);
+            let mut root = SubDevice::new(renderer, device_index);
+
+            let window_ctx = LayoutContext::new(
+                shared,
+                frame_context,
+                &thread_context,
+                Some(&local),
+                pool,
+                window_region.size,
+                Vec::new(),
+            );
+            root.layout_until_blocked(&window_ctx, window_region.size)
+        });
+
+        // Don't render anything if a device was missing its renderer
+        let error = thread_context
+            .take_error()
+            .or_else(|| workers.iter().find_map(ThreadContext::take_error));
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        match result {
+            RendererLayoutResult::None | RendererLayoutResult::Deferred => (),
+            RendererLayoutResult::Complete(layout) => {
+                // Render the device, then anything it put in higher layers
                 let render_ctx = RenderContext {
                     region: window_region,
+                    layer: Layer::BASE,
                     gui_ctx: self,
                     thread_ctx: &thread_context,
+                    workers: &workers,
+                    local: &local,
+                    output: &output,
+                    layered: &layered,
+                    ancestors: &ancestors,
                 };
-                renderer.render(layout.index, render_ctx, canvas);
+                render_ctx.render(layout, window_region, canvas);
+                render_ctx.render_layered(canvas);
             }
         }
 
-        // Output messages
-        let mut thread_outgoing_messages = thread_context.take_outgoing_messages();
-        std::mem::drop(thread_context);
-        std::mem::drop(frame_context);
+        // Nodes laid out on other threads may have written messages while rendering
+        for worker in &workers {
+            thread_context.extend_outgoing_messages(&mut worker.take_outgoing_messages());
+        }
+
+        Ok((thread_context.take_outgoing_messages(), output.into_inner()))
+    }
+
+    // The persistent state for an Id, created with its default value if there isn't any. It's kept
+    // between frames for as long as it's used at least once every 'state_retention' frames.
+    pub fn state<T: StateValue + Default>(&self, id: Id) -> State<T> {
+        self.state_or_insert_with(id, T::default)
+    }
+
+    pub fn state_or_insert_with<T: StateValue, F: FnOnce() -> T>(
+        &self,
+        id: Id,
+        init: F,
+    ) -> State<T> {
+        self.state.get_or_insert_with(id, self.frame, init)
+    }
 
-        self.outgoing_messages.extend(&mut thread_outgoing_messages);
+    pub fn state_retention(&self) -> u64 {
+        self.state.retention()
+    }
+
+    // How many frames state can go without being used before it's dropped (60 by default)
+    pub fn set_state_retention(&mut self, frames: u64) {
+        self.state.set_retention(frames);
+    }
+
+    // How many values are in the state store
+    pub fn state_len(&self) -> usize {
+        self.state.len()
+    }
+
+    // Copies everything in the state store, to be put back later with 'restore_state'
+    pub fn snapshot_state(&self) -> StateSnapshot {
+        self.state.snapshot()
+    }
+
+    pub fn restore_state(&mut self, snapshot: &StateSnapshot) {
+        self.state.restore(snapshot, self.frame);
+    }
+
+    // Lets messages and state of type 'T' be saved with 'save_session'. The name is written to the
+    // file in place of the type, so it should stay the same for as long as old files need to load.
+    pub fn register_persistent<T: Persist + StateValue>(&mut self, name: &'static str) {
+        if let Err(error) = self.try_register_persistent::<T>(name) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn try_register_persistent<T: Persist + StateValue>(
+        &mut self,
+        name: &'static str,
+    ) -> Result<(), BuoyError> {
+        self.persistent_types.register::<T>(name)
+    }
+
+    // Saves the messages waiting for the next frame, and everything in the state store, whose types
+    // were registered with 'register_persistent'. Anything else is left out.
+    pub fn save_session(&self) -> Vec<u8> {
+        self.persistent_types
+            .save(&self.outgoing_messages, &self.state)
+    }
+
+    pub fn save_session_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.save_session())
+    }
+
+    // Loads a session saved by 'save_session', adding its messages to the next frame and its values
+    // to the state store. Types that haven't been registered are skipped, and nothing is loaded if
+    // the data can't be read.
+    pub fn load_session(&mut self, data: &[u8]) -> Result<(), PersistError> {
+        self.persistent_types.load(
+            data,
+            &mut self.outgoing_messages,
+            &mut self.state,
+            self.frame,
+        )
+    }
+
+    pub fn load_session_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), PersistError> {
+        let data = fs::read(path)?;
+        self.load_session(&data)
+    }
+
+    // Creates an Outbox for writing messages to the next frame from outside of it (eg, from input).
+    // As with any Outbox, the message is only kept if 'inbox' has been called on it.
+    #[inline]
+    pub fn message<T: Message>(&self, id: Id) -> Outbox<T> {
+        Outbox::new(id)
     }
 
     pub fn write_message<T: Message>(&mut self, outbox: Outbox<T>, value: T) {
         self.outgoing_messages.write(outbox, value);
     }
+
+    // Publishes an event from outside of a frame, for the next frame to read
+    pub fn publish<T: Message>(&mut self, topic: &Topic<T>, value: T) {
+        self.topics
+            .publish(topic.key(), Box::new(value), self.frame);
+    }
+
+    // The events the next frame (or the current one, during a frame) reads from the topic, in the
+    // order they were published
+    pub fn events<T: Message>(&self, topic: &Topic<T>) -> Vec<T> {
+        self.topics.events(topic, self.frame)
+    }
+
+    // Every event on the topic from a sequence number on, for devices that keep track of the last
+    // event they saw (with 'state', say) so they don't miss any while they aren't being shown. If
+    // events have dropped out of the topic's history since then, the first one returned will be
+    // later than asked for.
+    pub fn events_since<T: Message>(&self, topic: &Topic<T>, sequence: u64) -> Vec<TopicEvent<T>> {
+        self.topics.events_since(topic, sequence)
+    }
+
+    pub fn event_history(&self) -> usize {
+        self.topics.history()
+    }
+
+    // How many of the most recent events each topic keeps (256 by default). A frame that publishes
+    // more than this to one topic only delivers the last of them.
+    pub fn set_event_history(&mut self, events: usize) {
+        self.topics.set_history(events);
+    }
+
+    // Pointer input from the window. It's checked against the regions registered with
+    // 'RenderContext::hit_region' in the last frame, and sent to the devices in the next one.
+    pub fn pointer_move(&mut self, position: Point) {
+        self.input.pointer_move(position);
+    }
+
+    pub fn pointer_leave(&mut self) {
+        self.input.pointer_leave();
+    }
+
+    pub fn pointer_down(&mut self, button: MouseButton) {
+        self.input.pointer_down(button);
+    }
+
+    pub fn pointer_up(&mut self, button: MouseButton) {
+        self.input.pointer_up(button);
+    }
+
+    pub fn wheel(&mut self, delta: Vector) {
+        self.input.wheel(delta);
+    }
+
+    // Focus changes are queued like pointer input, and happen at the start of the next frame
+    pub fn move_focus(&mut self, movement: FocusMove) {
+        self.input.move_focus(movement);
+    }
+
+    pub fn focus(&mut self, id: Id) {
+        self.input.focus(Some(id));
+    }
+
+    pub fn clear_focus(&mut self) {
+        self.input.focus(None);
+    }
+
+    // What was focused in the last frame
+    pub fn focused(&self) -> Option<Id> {
+        self.input.focused()
+    }
+
+    // Keyboard input from the window, sent to 'keyboard_id' of whatever is focused in the next frame
+    // (and to 'global_keyboard_id()'). Tab moves the focus instead, unless Ctrl, Alt or Meta is held.
+    pub fn key_down(&mut self, key: Key, modifiers: Modifiers) {
+        self.input.key_down(key, modifiers, false);
+    }
+
+    // The key is still held down, and the system's key repeat has kicked in
+    pub fn key_repeat(&mut self, key: Key, modifiers: Modifiers) {
+        self.input.key_down(key, modifiers, true);
+    }
+
+    pub fn key_up(&mut self, key: Key, modifiers: Modifiers) {
+        self.input.key_up(key, modifiers);
+    }
+
+    // Text typed by the user, after the keyboard layout (and dead keys, etc) have been applied
+    pub fn text_input(&mut self, text: &str) {
+        self.input.text_input(text.to_string());
+    }
+
+    // The input method started composing text, or changed what it's composing. It's only sent to the
+    // focused Id, and moving the focus ends it.
+    pub fn ime_preedit(&mut self, text: &str, cursor: Option<(usize, usize)>) {
+        let text = text.to_string();
+        self.input.ime_preedit(Preedit { text, cursor });
+    }
+
+    // The input method finished composing, and the text should be inserted
+    pub fn ime_commit(&mut self, text: &str) {
+        self.input.ime_commit(text.to_string());
+    }
+
+    pub fn ime_cancel(&mut self) {
+        self.input.ime_cancel();
+    }
+
+    fn write_input_messages(&mut self) {
+        self.input.write_messages(&mut self.outgoing_messages);
+    }
 }
@@ -1,15 +1,18 @@
+use crate::core::context::parallel::Pool;
 use crate::core::context::*;
 use crate::core::device::*;
+use crate::core::error::BuoyError;
 use crate::core::id::Id;
+use crate::core::state::{State, StateValue};
+use crate::core::topic::{Topic, TopicEvent};
 use crate::message::*;
 use crate::space::*;
-use crate::util::arena::Arena;
 use crate::util::drain_filter::DrainFilter;
-use crate::util::ref_move::{ref_move, Anchor};
+use crate::util::ref_move::{ref_move, Anchor, Ext};
 
 pub enum LayoutResult<T> {
     None,
-    // TODO: Deferred,
+    Deferred,
     Complete { min_size: Size, layout: T },
     CompleteNode(LayoutNode),
 }
@@ -18,18 +21,63 @@ pub struct LayoutNode {
     pub type_id: TypeId,
     pub index: LayoutIndex,
     pub min_size: Size,
+
+    // The layout thread this node was produced on, if it's different from its parent's
+    pub(in crate::core) thread: Option<usize>,
 }
 
 pub struct LayoutContext<'thrd, 'frm, C> {
-    pub(in crate::core) gui_ctx: &'frm GuiContext<C>,
+    pub(in crate::core) shared: SharedContext<'frm, C>,
     pub(in crate::core) frame_ctx: &'frm FrameContext,
     pub(in crate::core) thread_ctx: &'thrd ThreadContext<'frm, C>,
+    // The renderers that can only run on the thread rendering the window, if this is running there
+    pub(in crate::core) local: Option<&'thrd LocalRenderers<'frm, C>>,
+    // The layout threads, if there are any
+    pub(in crate::core) pool: Option<&'thrd Pool<'frm, C>>,
 
     pub(in crate::core) max_size: Size,
-    pub(in crate::core) children: Vec<(SocketName, SubDevice<'thrd, 'frm, C>)>,
+    pub(in crate::core) children: Vec<(SocketName, Child<'thrd, 'frm, C>)>,
+
+    // Messages this device is waiting on, and the device it handed back with 'defer'
+    pub(in crate::core) dependencies: Vec<Id>,
+    pub(in crate::core) deferred: Option<(&'thrd dyn RendererWrapper<'frm, C>, DeviceIndex)>,
+    pub(in crate::core) pending: bool,
+
+    // The devices started with 'device_tree' that deferred, in the order they were started, so that
+    // they pick up where they left off (with the children they took) when this device is resumed
+    pub(in crate::core) subtrees: Vec<Option<SubDevice<'thrd, 'frm, C>>>,
+    subtrees_started: usize,
 }
 
 impl<'thrd, 'frm, C: 'static> LayoutContext<'thrd, 'frm, C> {
+    pub(in crate::core) fn new(
+        shared: SharedContext<'frm, C>,
+        frame_ctx: &'frm FrameContext,
+        thread_ctx: &'thrd ThreadContext<'frm, C>,
+        local: Option<&'thrd LocalRenderers<'frm, C>>,
+        pool: Option<&'thrd Pool<'frm, C>>,
+        max_size: Size,
+        children: Vec<(SocketName, Child<'thrd, 'frm, C>)>,
+    ) -> Self {
+        LayoutContext {
+            shared,
+            frame_ctx,
+            thread_ctx,
+            local,
+            pool,
+
+            max_size,
+            children,
+
+            dependencies: Vec::new(),
+            deferred: None,
+            pending: false,
+
+            subtrees: Vec::new(),
+            subtrees_started: 0,
+        }
+    }
+
     #[inline]
     pub fn max_size(&self) -> Size {
         self.max_size
@@ -39,88 +87,225 @@ impl<'thrd, 'frm, C: 'static> LayoutContext<'thrd, 'frm, C> {
         self.children.iter().filter(|(k, _)| *k == socket).count()
     }
 
-    // TODO: It would be nice if I didn't have to expose this
+    // Whether a socket or subcontext on this context couldn't be completed because a device deferred.
+    // A renderer that sees this should usually return 'ctx.defer(device)', so that it gets run again
+    // once the devices it's waiting on are able to make progress.
     #[inline]
-    pub fn buffer(&self) -> &'frm Arena {
-        self.thread_ctx.buffer()
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    // Fails the frame (unless something else already has), for a device that can't be laid out the
+    // way it was asked to be. Layout carries on, but nothing is rendered.
+    pub fn report_error(&self, error: BuoyError) {
+        self.thread_ctx.report_error(error);
     }
 
+    // Lays out a device along with a tree of children for it. If the device defers, it's kept (along
+    // with the children it took from this context) until this device is resumed, and the call that
+    // started it then picks it back up instead of starting over with the device and tree it's given.
     pub fn device_tree<D: Anchor<dyn Device + 'frm>, T: LayoutTree<'frm, C>>(
         &mut self,
         max_size: Size,
         device: D,
         subtree: T,
     ) -> LayoutResult<()> {
-        // Look up the renderer for this type
-        let renderer = self
-            .thread_ctx
-            .renderer_for(self.gui_ctx, device.get_type_id());
-
-        // Allocate the device with its renderer
-        let index = ref_move(device, |d| renderer.alloc(d));
-        let mut sub_device = SubDevice {
-            renderer,
-            index,
-            children: Vec::new(),
-        };
+        let started = self.subtrees_started;
+        self.subtrees_started += 1;
 
-        // Visit the subtree
-        let visitor = LayoutTreeVisitor {
-            parent: &mut sub_device,
-            gui_ctx: self.gui_ctx,
-            thread_ctx: self.thread_ctx,
-            ctx_children: &mut self.children,
-        };
-        subtree.visit(visitor);
-
-        // Create a context for running the device
-        let ctx = LayoutContext {
-            gui_ctx: self.gui_ctx,
-            frame_ctx: self.frame_ctx,
-            thread_ctx: self.thread_ctx,
+        let mut sub_device = match self.subtrees.get_mut(started).and_then(Option::take) {
+            Some(sub_device) => {
+                ref_move(device, |device| std::mem::drop(device));
+                sub_device
+            }
+            None => {
+                // Allocate the device with the renderer for its type
+                let (renderer, index) = self.thread_ctx.alloc(self.shared, self.local, device);
+                let mut sub_device = SubDevice::new(renderer, index);
 
-            max_size,
-            children: sub_device.children,
+                // Visit the subtree
+                let visitor = LayoutTreeVisitor {
+                    parent: &mut sub_device,
+                    shared: self.shared,
+                    local: self.local,
+                    thread_ctx: self.thread_ctx,
+                    ctx_children: &mut self.children,
+                };
+                subtree.visit(visitor);
+                sub_device
+            }
         };
 
-        match sub_device.renderer.layout(sub_device.index, ctx) {
+        // Run the device, resuming it for as long as it's able to make progress
+        match sub_device.layout_until_blocked(self, max_size) {
             RendererLayoutResult::None => LayoutResult::None,
+            RendererLayoutResult::Deferred => {
+                self.dependencies.extend(sub_device.dependencies());
+                self.pending = true;
+                if self.subtrees.len() <= started {
+                    self.subtrees.resize_with(started + 1, || None);
+                }
+                self.subtrees[started] = Some(sub_device);
+                LayoutResult::Deferred
+            }
             RendererLayoutResult::Complete(layout_node) => LayoutResult::CompleteNode(layout_node),
         }
     }
 
+    // Runs the children in the given socket and fills it with their layouts, in order.
+    // If any of those children defer and can't be resumed, the socket is left empty and this context
+    // is marked as pending. Children that did complete are kept, so the next call after resuming
+    // won't run them again.
     pub fn socket<S: Socket>(&mut self, name: SocketName, max_size: Size, socket: &mut S) {
-        // Fill the socket
-        let mut iter = self
-            .children
-            .buoy_drain_filter(|(socket, _)| *socket == name);
-        while socket.remaining_capacity() != 0 {
-            let mut device = match iter.next() {
-                Some((_, device)) => device,
-                None => break,
-            };
+        let capacity = socket.remaining_capacity();
+        let mut children = std::mem::take(&mut self.children);
 
-            // Run the child
-            let ctx = LayoutContext {
-                gui_ctx: self.gui_ctx,
-                frame_ctx: self.frame_ctx,
-                thread_ctx: self.thread_ctx,
+        // Run the children, retrying any that deferred for as long as they're making progress
+        let mut first_pass = true;
+        loop {
+            let mut progress = false;
+            let mut filled = 0_usize;
+            for (_, child) in children.iter_mut().filter(|(socket, _)| *socket == name) {
+                if filled == capacity {
+                    break;
+                }
 
-                max_size,
-                children: std::mem::take(&mut device.children),
-            };
+                let device = match child {
+                    Child::Pending(device) => device,
+                    Child::Complete(node) => {
+                        filled += node.is_some() as usize;
+                        continue;
+                    }
+                };
 
-            match device.renderer.layout(device.index, ctx) {
-                RendererLayoutResult::None => (),
-                RendererLayoutResult::Complete(layout_node) => socket.push(layout_node),
-            };
+                if !first_pass && !device.is_ready(self.thread_ctx) {
+                    continue;
+                }
+
+                match device.layout(self, max_size) {
+                    RendererLayoutResult::None => *child = Child::Complete(None),
+                    RendererLayoutResult::Deferred => continue,
+                    RendererLayoutResult::Complete(layout_node) => {
+                        *child = Child::Complete(Some(layout_node));
+                        filled += 1;
+                    }
+                }
+                progress = true;
+            }
+
+            first_pass = false;
+            if !progress {
+                break;
+            }
         }
+
+        // Figure out which children belong in the socket, and whether any of them are still waiting
+        let mut filled = 0_usize;
+        let mut len = 0_usize;
+        let mut waiting = false;
+        for (_, child) in children.iter().filter(|(socket, _)| *socket == name) {
+            if filled == capacity {
+                break;
+            }
+
+            match child {
+                Child::Pending(device) => {
+                    self.dependencies.extend(device.dependencies());
+                    waiting = true;
+                }
+                Child::Complete(node) => filled += node.is_some() as usize,
+            }
+            len += 1;
+        }
+
+        // Only fill the socket once everything in it has completed
+        if waiting {
+            self.pending = true;
+        } else {
+            let mut remaining = len;
+            let iter = children.buoy_drain_filter(|(socket, _)| {
+                if remaining == 0 || *socket != name {
+                    return false;
+                }
+
+                remaining -= 1;
+                true
+            });
+
+            for (_, child) in iter {
+                if let Child::Complete(Some(layout_node)) = child {
+                    socket.push(layout_node);
+                }
+            }
+        }
+
+        self.children = children;
+    }
+
+    // Runs each job in its own subcontext, spreading them across the layout threads configured with
+    // 'GuiContext::set_layout_threads' (or running them here, if there aren't any). Jobs can't see
+    // messages written by each other until this returns, and both their results and their messages
+    // come back in job order regardless of which thread ran them.
+    pub fn parallel<I, F>(&mut self, max_size: Size, jobs: I) -> Vec<LayoutResult<()>>
+    where
+        I: IntoIterator<Item = F>,
+        F: for<'w> FnOnce(&mut LayoutContext<'w, 'frm, C>) -> LayoutResult<()> + Send + 'frm,
+    {
+        let jobs: Vec<F> = jobs.into_iter().collect();
+
+        let pool = match self.pool {
+            Some(pool) => pool,
+            None => {
+                return jobs
+                    .into_iter()
+                    .map(|job| {
+                        let mut ctx = LayoutContext::new(
+                            self.shared,
+                            self.frame_ctx,
+                            self.thread_ctx,
+                            self.local,
+                            None,
+                            max_size,
+                            Vec::new(),
+                        );
+                        job(&mut ctx)
+                    })
+                    .collect();
+            }
+        };
+
+        let writer = self.thread_ctx.writer();
+        let outputs = pool.run(
+            self.thread_ctx,
+            self.shared,
+            self.frame_ctx,
+            writer,
+            max_size,
+            jobs,
+        );
+        outputs
+            .into_iter()
+            .map(|(result, mut messages)| {
+                self.thread_ctx.extend_outgoing_messages(&mut messages);
+                result
+            })
+            .collect()
     }
 
     pub fn layout<T>(&self, min_size: Size, layout: T) -> LayoutResult<T> {
         LayoutResult::Complete { min_size, layout }
     }
 
+    // Hands the device back to its renderer, which will run layout on it again once the messages
+    // this context has polled for have been written and something else has made progress.
+    // Any children that haven't been placed in a socket yet are carried over to the next run.
+    pub fn defer<D: Device + 'frm, T>(&mut self, device: D) -> LayoutResult<T> {
+        let device = device.move_anchor::<dyn Device + 'frm>();
+        self.deferred = Some(self.thread_ctx.alloc(self.shared, self.local, device));
+
+        LayoutResult::Deferred
+    }
+
     #[inline]
     pub fn message<T: Message>(&mut self, id: Id) -> Outbox<T> {
         Outbox::new(id)
@@ -128,20 +313,92 @@ impl<'thrd, 'frm, C: 'static> LayoutContext<'thrd, 'frm, C> {
 
     #[inline]
     pub fn read_message<T: Message, I: Into<Inbox<T>>>(&self, inbox: I) -> Option<T> {
-        self.frame_ctx.read_message(inbox)
+        let inbox = inbox.into();
+        let id = inbox.id();
+        let value = self.frame_ctx.read_message(inbox);
+        self.thread_ctx.record_read::<T>(id, value.is_some());
+        value
+    }
+
+    // Reads a message written by an earlier pass in this frame, ignoring the last frame's messages
+    #[inline]
+    pub fn read_pass_message<T: Message, I: Into<Inbox<T>>>(&self, inbox: I) -> Option<T> {
+        let inbox = inbox.into();
+        let id = inbox.id();
+        let value = self.frame_ctx.read_pass_message(inbox);
+        self.thread_ctx.record_read::<T>(id, value.is_some());
+        value
+    }
+
+    #[inline]
+    pub fn focused(&self) -> Option<Id> {
+        self.frame_ctx.focused()
+    }
+
+    // State that persists across frames, unlike messages. See 'GuiContext::state'.
+    #[inline]
+    pub fn state<T: StateValue + Default>(&self, id: Id) -> State<T> {
+        self.shared.state_or_insert_with(id, T::default)
+    }
+
+    #[inline]
+    pub fn state_or_insert_with<T: StateValue, F: FnOnce() -> T>(
+        &self,
+        id: Id,
+        init: F,
+    ) -> State<T> {
+        self.shared.state_or_insert_with(id, init)
+    }
+
+    // Reads a message that was written earlier in this frame (including by earlier passes). If it
+    // hasn't been written yet, it's recorded as a dependency so that 'defer' will wait for it.
+    pub fn poll_message<T: Message, I: Into<Inbox<T>>>(&mut self, inbox: I) -> Option<T> {
+        let inbox = inbox.into();
+        let id = inbox.id();
+
+        let value = if self.thread_ctx.has_message(id) {
+            self.thread_ctx.read_message(inbox)
+        } else {
+            self.frame_ctx.read_pass_message(inbox)
+        };
+
+        if value.is_none() {
+            self.dependencies.push(id);
+        }
+
+        self.thread_ctx.record_read::<T>(id, value.is_some());
+        value
     }
 
     #[inline]
     pub fn write_message<T: Message>(&mut self, outbox: Outbox<T>, value: T) {
         self.thread_ctx.write_message(outbox, value)
     }
+
+    // Publishes an event for every device reading the topic in the next frame. See 'Topic'.
+    #[inline]
+    pub fn publish<T: Message>(&mut self, topic: &Topic<T>, value: T) {
+        self.thread_ctx.publish(topic, value)
+    }
+
+    // The events published to the topic in the last frame (or since then, from outside of a frame)
+    #[inline]
+    pub fn events<T: Message>(&self, topic: &Topic<T>) -> Vec<T> {
+        self.shared.events(topic)
+    }
+
+    #[inline]
+    pub fn events_since<T: Message>(&self, topic: &Topic<T>, sequence: u64) -> Vec<TopicEvent<T>> {
+        self.shared.events_since(topic, sequence)
+    }
 }
 
 pub struct LayoutTreeVisitor<'slf, 'thrd, 'frm, C> {
     parent: &'slf mut SubDevice<'thrd, 'frm, C>,
-    gui_ctx: &'frm GuiContext<C>,
+    shared: SharedContext<'frm, C>,
+    local: Option<&'thrd LocalRenderers<'frm, C>>,
     thread_ctx: &'thrd ThreadContext<'frm, C>,
-    ctx_children: &'slf mut Vec<(SocketName, SubDevice<'thrd, 'frm, C>)>,
+    ctx_children: &'slf mut Vec<(SocketName, Child<'thrd, 'frm, C>)>,
 }
 
 impl<'slf, 'thrd, 'frm: 'thrd, C: 'static> LayoutTreeVisitor<'slf, 'thrd, 'frm, C> {
@@ -166,24 +423,14 @@ impl<'slf, 'thrd, 'frm: 'thrd, C: 'static> LayoutTreeVisitor<'slf, 'thrd, 'frm,
     }
 
     pub fn device<D: Anchor<dyn Device + 'frm>>(&mut self, socket: SocketName, device: D) {
-        // Look up the renderer for this type
-        let renderer = self
-            .thread_ctx
-            .renderer_for(self.gui_ctx, device.get_type_id());
-
         // TODO: Determine if it's viable to just call into 'device_tree' with a no-op tree
         // Might not be as performant for debug builds.
 
-        // Allocate the device and add it to the parent
-        let index = ref_move(device, |d| renderer.alloc(d));
-        self.parent.children.push((
-            socket,
-            SubDevice {
-                renderer,
-                index,
-                children: Vec::new(),
-            },
-        ));
+        // Allocate the device with the renderer for its type, and add it to the parent
+        let (renderer, index) = self.thread_ctx.alloc(self.shared, self.local, device);
+        self.parent
+            .children
+            .push((socket, Child::Pending(SubDevice::new(renderer, index))));
     }
 
     pub fn device_tree<D: Anchor<dyn Device + 'frm>, T: LayoutTree<'frm, C>>(
@@ -192,30 +439,24 @@ impl<'slf, 'thrd, 'frm: 'thrd, C: 'static> LayoutTreeVisitor<'slf, 'thrd, 'frm,
         device: D,
         subtree: T,
     ) {
-        // Look up the renderer for this type
-        let renderer = self
-            .thread_ctx
-            .renderer_for(self.gui_ctx, device.get_type_id());
-
-        // Allocate the device with its renderer
-        let index = ref_move(device, |d| renderer.alloc(d));
-        let mut sub_device = SubDevice {
-            renderer,
-            index,
-            children: Vec::new(),
-        };
+        // Allocate the device with the renderer for its type
+        let (renderer, index) = self.thread_ctx.alloc(self.shared, self.local, device);
+        let mut sub_device = SubDevice::new(renderer, index);
 
         // Visit the subtree
         let visitor = LayoutTreeVisitor {
             parent: &mut sub_device,
-            gui_ctx: self.gui_ctx,
+            shared: self.shared,
+            local: self.local,
             thread_ctx: self.thread_ctx,
             ctx_children: self.ctx_children,
         };
         subtree.visit(visitor);
 
         // Add the device to the parent
-        self.parent.children.push((socket, sub_device));
+        self.parent
+            .children
+            .push((socket, Child::Pending(sub_device)));
     }
 }
 
@@ -236,8 +477,770 @@ impl<'frm, C> LayoutTree<'frm, C> for () {
     fn visit<'ctx, 'thrd>(self, _visitor: LayoutTreeVisitor<'ctx, 'thrd, 'frm, C>) {}
 }
 
+pub(in crate::core) enum Child<'thrd, 'frm, C> {
+    Pending(SubDevice<'thrd, 'frm, C>),
+    Complete(Option<LayoutNode>),
+}
+
+struct Deferral {
+    dependencies: Vec<Id>,
+    generation: usize,
+}
+
 pub(in crate::core) struct SubDevice<'thrd, 'frm, C> {
     renderer: &'thrd dyn RendererWrapper<'frm, C>,
     index: DeviceIndex,
-    children: Vec<(SocketName, SubDevice<'thrd, 'frm, C>)>,
+    children: Vec<(SocketName, Child<'thrd, 'frm, C>)>,
+    subtrees: Vec<Option<SubDevice<'thrd, 'frm, C>>>,
+    deferral: Option<Deferral>,
+}
+
+impl<'thrd, 'frm, C: 'static> SubDevice<'thrd, 'frm, C> {
+    pub(in crate::core) fn new(
+        renderer: &'thrd dyn RendererWrapper<'frm, C>,
+        index: DeviceIndex,
+    ) -> Self {
+        SubDevice {
+            renderer,
+            index,
+            children: Vec::new(),
+            subtrees: Vec::new(),
+            deferral: None,
+        }
+    }
+
+    fn dependencies(&self) -> impl Iterator<Item = Id> + '_ {
+        self.deferral
+            .iter()
+            .flat_map(|deferral| deferral.dependencies.iter().cloned())
+    }
+
+    // A deferred device is worth resuming once all of its dependencies have been written,
+    // and something has happened since it last started running.
+    fn is_ready(&self, thread_ctx: &ThreadContext<'frm, C>) -> bool {
+        match self.deferral {
+            None => true,
+            Some(ref deferral) => {
+                deferral.generation != thread_ctx.generation()
+                    && deferral
+                        .dependencies
+                        .iter()
+                        .all(|&id| thread_ctx.has_message(id))
+            }
+        }
+    }
+
+    pub(in crate::core) fn layout(
+        &mut self,
+        parent: &LayoutContext<'thrd, 'frm, C>,
+        max_size: Size,
+    ) -> RendererLayoutResult {
+        let thread_ctx = parent.thread_ctx;
+        let generation = thread_ctx.generation();
+        let mut ctx = LayoutContext::new(
+            parent.shared,
+            parent.frame_ctx,
+            thread_ctx,
+            parent.local,
+            parent.pool,
+            max_size,
+            std::mem::take(&mut self.children),
+        );
+        ctx.subtrees = std::mem::take(&mut self.subtrees);
+
+        let (renderer, index) = (self.renderer, self.index);
+        let result = thread_ctx.with_writer(renderer, || renderer.layout(index, &mut ctx));
+        match (result, ctx.deferred.take()) {
+            (RendererLayoutResult::Deferred, Some((renderer, index))) => {
+                // Pick up the device where the renderer left it
+                self.renderer = renderer;
+                self.index = index;
+                self.children = ctx.children;
+                self.subtrees = ctx.subtrees;
+                self.deferral = Some(Deferral {
+                    dependencies: ctx.dependencies,
+                    generation,
+                });
+                RendererLayoutResult::Deferred
+            }
+            (RendererLayoutResult::Deferred, None) => {
+                // There's no device to resume, so there's nothing to lay out
+                thread_ctx.report_error(BuoyError::MissingDefer(renderer.device_info()));
+                thread_ctx.advance_generation();
+                RendererLayoutResult::None
+            }
+            (result, _) => {
+                thread_ctx.advance_generation();
+                result
+            }
+        }
+    }
+
+    pub(in crate::core) fn layout_until_blocked(
+        &mut self,
+        parent: &LayoutContext<'thrd, 'frm, C>,
+        max_size: Size,
+    ) -> RendererLayoutResult {
+        loop {
+            match self.layout(parent, max_size) {
+                RendererLayoutResult::Deferred if self.is_ready(parent.thread_ctx) => continue,
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::util::ref_move::Ext;
+    use std::cell::Cell;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, ThreadId};
+
+    type Canvas = Vec<(&'static str, f32)>;
+
+    macro_rules! device {
+        ($t:ty, $id:expr, $name:expr) => {
+            impl Device for $t {
+                fn type_id() -> TypeId {
+                    TypeId::new($id)
+                }
+
+                fn package_name() -> &'static str {
+                    "buoy"
+                }
+
+                fn type_name() -> &'static str {
+                    $name
+                }
+            }
+        };
+    }
+
+    struct Root;
+    struct Stack;
+    struct Reader(Inbox<f32>);
+    struct Writer(Outbox<f32>, f32);
+
+    device!(Root, 1, "root");
+    device!(Stack, 2, "stack");
+    device!(Reader, 3, "reader");
+    device!(Writer, 4, "writer");
+
+    // Lays out four rows in parallel, each of which lays out its eight tiles in parallel
+    struct Tiles(Option<u64>);
+    struct TilesRenderer;
+
+    device!(Tiles, 6, "tiles");
+
+    struct RootRenderer;
+    struct StackRenderer;
+    struct ReaderRenderer;
+    struct WriterRenderer;
+
+    impl<'frm> Renderer<'frm, Canvas> for RootRenderer {
+        type Device = Root;
+        type Layout = LayoutNode;
+
+        fn layout<'thrd>(
+            &self,
+            _device: Root,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<LayoutNode> {
+            let outbox = ctx.message::<f32>("value".into());
+            let inbox = outbox.inbox();
+
+            // The reader comes first, so it has to wait for the writer
+            let stack = Stack.move_anchor::<dyn Device>();
+            let tree = move |mut visitor: LayoutTreeVisitor<Canvas>| {
+                visitor.device(SocketName::default(), Reader(inbox).move_anchor());
+                visitor.device(SocketName::default(), Writer(outbox, 3_f32).move_anchor());
+            };
+
+            match ctx.device_tree(ctx.max_size(), stack, tree) {
+                LayoutResult::CompleteNode(node) => ctx.layout(node.min_size, node),
+                _ => LayoutResult::None,
+            }
+        }
+
+        fn render<'ctx>(
+            &self,
+            layout: LayoutNode,
+            ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            ctx.render(layout, ctx.region(), canvas);
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for StackRenderer {
+        type Device = Stack;
+        type Layout = Vec<LayoutNode>;
+
+        fn layout<'thrd>(
+            &self,
+            device: Stack,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<Vec<LayoutNode>> {
+            let mut children = Vec::new();
+            ctx.socket(SocketName::default(), ctx.max_size(), &mut children);
+            if ctx.is_pending() {
+                return ctx.defer(device);
+            }
+
+            ctx.layout(Size::zero(), children)
+        }
+
+        fn render<'ctx>(
+            &self,
+            layout: Vec<LayoutNode>,
+            ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            for node in layout {
+                ctx.render(node, ctx.region(), canvas);
+            }
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for ReaderRenderer {
+        type Device = Reader;
+        type Layout = f32;
+
+        fn layout<'thrd>(
+            &self,
+            device: Reader,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<f32> {
+            match ctx.poll_message(device.0) {
+                Some(value) => ctx.layout(Size::new(value, value), value),
+                None => ctx.defer(device),
+            }
+        }
+
+        fn render<'ctx>(
+            &self,
+            layout: f32,
+            _ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            canvas.push(("reader", layout));
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for WriterRenderer {
+        type Device = Writer;
+        type Layout = f32;
+
+        fn layout<'thrd>(
+            &self,
+            device: Writer,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<f32> {
+            let Writer(outbox, value) = device;
+            ctx.write_message(outbox, value);
+            ctx.layout(Size::new(value, value), value)
+        }
+
+        fn render<'ctx>(
+            &self,
+            layout: f32,
+            _ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            canvas.push(("writer", layout));
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for TilesRenderer {
+        type Device = Tiles;
+        type Layout = Vec<LayoutNode>;
+
+        fn layout<'thrd>(
+            &self,
+            Tiles(row): Tiles,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<Vec<LayoutNode>> {
+            let jobs = (0..8_u64).map(|i| {
+                move |ctx: &mut LayoutContext<Canvas>| match row {
+                    Some(row) => {
+                        let i = row * 8 + i;
+                        let outbox = ctx.message::<f32>(i.into());
+                        outbox.inbox();
+                        let writer = Writer(outbox, i as f32).move_anchor();
+                        ctx.device_tree(ctx.max_size(), writer, ())
+                    }
+                    None if i < 4 => {
+                        ctx.device_tree(ctx.max_size(), Tiles(Some(i)).move_anchor(), ())
+                    }
+                    None => LayoutResult::None,
+                }
+            });
+
+            let mut tiles = Vec::new();
+            for result in ctx.parallel(ctx.max_size(), jobs) {
+                if let LayoutResult::CompleteNode(node) = result {
+                    tiles.push(node);
+                }
+            }
+
+            ctx.layout(Size::zero(), tiles)
+        }
+
+        fn render<'ctx>(
+            &self,
+            layout: Vec<LayoutNode>,
+            ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            for node in layout {
+                ctx.render(node, ctx.region(), canvas);
+            }
+        }
+    }
+
+    #[test]
+    fn defer_on_sibling_message() {
+        let mut gui = GuiContext::default();
+        gui.register_device(Root::type_id(), Rc::new(RootRenderer));
+        gui.register_device(Stack::type_id(), Rc::new(StackRenderer));
+        gui.register_device(Reader::type_id(), Rc::new(ReaderRenderer));
+        gui.register_device(Writer::type_id(), Rc::new(WriterRenderer));
+
+        let mut canvas = Canvas::new();
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        gui.render_window(region, Root.move_anchor(), &mut canvas)
+            .unwrap();
+
+        assert_eq!(canvas, vec![("reader", 3_f32), ("writer", 3_f32)]);
+    }
+
+    #[test]
+    fn defer_in_forwarded_subtree() {
+        // Forwards its children to a stack, through a subtree that defers
+        struct Forward;
+        struct ForwardRenderer;
+        device!(Forward, 9, "forward");
+
+        struct Leaf(&'static str);
+        struct LeafRenderer;
+        device!(Leaf, 10, "leaf");
+
+        // A stack holding a 'Forward' (with a leaf and a reader) and then the writer the reader waits on
+        struct Outer;
+        struct OuterRenderer;
+        device!(Outer, 11, "outer");
+
+        impl<'frm> Renderer<'frm, Canvas> for ForwardRenderer {
+            type Device = Forward;
+            type Layout = LayoutNode;
+
+            fn layout<'thrd>(
+                &self,
+                device: Forward,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<LayoutNode> {
+                let default = SocketName::default();
+                let tree = move |mut visitor: LayoutTreeVisitor<Canvas>| {
+                    visitor.socket(default, default, None);
+                };
+                match ctx.device_tree(ctx.max_size(), Stack.move_anchor(), tree) {
+                    LayoutResult::CompleteNode(node) => ctx.layout(node.min_size, node),
+                    LayoutResult::Deferred => ctx.defer(device),
+                    _ => LayoutResult::None,
+                }
+            }
+
+            fn render<'ctx>(
+                &self,
+                layout: LayoutNode,
+                ctx: RenderContext<'ctx, 'frm, Canvas>,
+                canvas: &mut Canvas,
+            ) {
+                ctx.render(layout, ctx.region(), canvas);
+            }
+        }
+
+        impl<'frm> Renderer<'frm, Canvas> for LeafRenderer {
+            type Device = Leaf;
+            type Layout = &'static str;
+
+            fn layout<'thrd>(
+                &self,
+                device: Leaf,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<&'static str> {
+                ctx.layout(Size::zero(), device.0)
+            }
+
+            fn render<'ctx>(
+                &self,
+                name: &'static str,
+                _ctx: RenderContext<'ctx, 'frm, Canvas>,
+                canvas: &mut Canvas,
+            ) {
+                canvas.push((name, 0_f32));
+            }
+        }
+
+        impl<'frm> Renderer<'frm, Canvas> for OuterRenderer {
+            type Device = Outer;
+            type Layout = LayoutNode;
+
+            fn layout<'thrd>(
+                &self,
+                _device: Outer,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<LayoutNode> {
+                let outbox = ctx.message::<f32>("value".into());
+                let inbox = outbox.inbox();
+                let default = SocketName::default();
+                let tree = move |mut visitor: LayoutTreeVisitor<Canvas>| {
+                    let forwarded = move |mut visitor: LayoutTreeVisitor<Canvas>| {
+                        visitor.device(default, Leaf("first").move_anchor());
+                        visitor.device(default, Reader(inbox).move_anchor());
+                    };
+                    visitor.device_tree(default, Forward.move_anchor(), forwarded);
+                    visitor.device(default, Writer(outbox, 3_f32).move_anchor());
+                };
+                match ctx.device_tree(ctx.max_size(), Stack.move_anchor(), tree) {
+                    LayoutResult::CompleteNode(node) => ctx.layout(node.min_size, node),
+                    _ => LayoutResult::None,
+                }
+            }
+
+            fn render<'ctx>(
+                &self,
+                layout: LayoutNode,
+                ctx: RenderContext<'ctx, 'frm, Canvas>,
+                canvas: &mut Canvas,
+            ) {
+                ctx.render(layout, ctx.region(), canvas);
+            }
+        }
+
+        let mut gui = GuiContext::default();
+        gui.register::<Outer>(Rc::new(OuterRenderer));
+        gui.register::<Forward>(Rc::new(ForwardRenderer));
+        gui.register::<Leaf>(Rc::new(LeafRenderer));
+        gui.register::<Stack>(Rc::new(StackRenderer));
+        gui.register::<Reader>(Rc::new(ReaderRenderer));
+        gui.register::<Writer>(Rc::new(WriterRenderer));
+
+        // The forwarded children are still there when the stack they were forwarded to is resumed
+        let mut canvas = Canvas::new();
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        gui.render_window(region, Outer.move_anchor(), &mut canvas)
+            .unwrap();
+        let expected = vec![("first", 0_f32), ("reader", 3_f32), ("writer", 3_f32)];
+        assert_eq!(canvas, expected);
+    }
+
+    #[test]
+    fn read_message_from_previous_pass() {
+        // Writes a message on the first pass, and reads it back on the second
+        struct Pass(usize);
+        struct PassRenderer;
+        device!(Pass, 5, "pass");
+
+        impl<'frm> Renderer<'frm, Canvas> for PassRenderer {
+            type Device = Pass;
+            type Layout = Option<LayoutNode>;
+
+            fn layout<'thrd>(
+                &self,
+                device: Pass,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<Option<LayoutNode>> {
+                let outbox = ctx.message::<f32>("position".into());
+                let inbox = outbox.inbox();
+
+                // The first pass writes the message, the second reads it back
+                if device.0 == 0 {
+                    let writer = Writer(outbox, 5_f32).move_anchor();
+                    match ctx.device_tree(ctx.max_size(), writer, ()) {
+                        LayoutResult::CompleteNode(node) => ctx.layout(node.min_size, Some(node)),
+                        _ => LayoutResult::None,
+                    }
+                } else {
+                    let reader = Reader(inbox).move_anchor();
+                    assert_eq!(ctx.read_message(outbox.inbox()), Some(5_f32));
+                    match ctx.device_tree(ctx.max_size(), reader, ()) {
+                        LayoutResult::CompleteNode(node) => ctx.layout(node.min_size, Some(node)),
+                        _ => LayoutResult::None,
+                    }
+                }
+            }
+
+            fn render<'ctx>(
+                &self,
+                layout: Option<LayoutNode>,
+                ctx: RenderContext<'ctx, 'frm, Canvas>,
+                canvas: &mut Canvas,
+            ) {
+                if let Some(node) = layout {
+                    ctx.render(node, ctx.region(), canvas);
+                }
+            }
+        }
+
+        let mut gui = GuiContext::default();
+        gui.register_device(Pass::type_id(), Rc::new(PassRenderer));
+        gui.register_device(Reader::type_id(), Rc::new(ReaderRenderer));
+        gui.register_device(Writer::type_id(), Rc::new(WriterRenderer));
+
+        let mut canvas = Canvas::new();
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        gui.render_window_passes(region, 2, |pass| Pass(pass).move_anchor(), &mut canvas)
+            .unwrap();
+
+        assert_eq!(canvas, vec![("writer", 5_f32), ("reader", 5_f32)]);
+    }
+
+    #[test]
+    fn parallel_layout_is_ordered() {
+        let mut gui = GuiContext::default();
+        gui.register_shared::<Tiles>(Arc::new(TilesRenderer));
+        gui.register_shared::<Writer>(Arc::new(WriterRenderer));
+
+        // Without layout threads the jobs run in place, and with them jobs started from jobs are spread
+        // across the threads too
+        let expected: Canvas = (0..32).map(|i| ("writer", i as f32)).collect();
+        for threads in [0, 1, 4] {
+            gui.set_layout_threads(threads);
+            let mut canvas = Canvas::new();
+            let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+            gui.render_window(region, Tiles(None).move_anchor(), &mut canvas)
+                .unwrap();
+            assert_eq!(canvas, expected);
+        }
+    }
+
+    #[test]
+    fn parallel_layout_reads_messages_that_arent_sync() {
+        // Each job lays out a writer with the value of a message that can only be sent between threads
+        struct Cells;
+        struct CellsRenderer;
+        device!(Cells, 12, "cells");
+
+        impl<'frm> Renderer<'frm, Canvas> for CellsRenderer {
+            type Device = Cells;
+            type Layout = Vec<LayoutNode>;
+
+            fn layout<'thrd>(
+                &self,
+                _device: Cells,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<Vec<LayoutNode>> {
+                let jobs = (0..4_u64).map(|i| {
+                    move |ctx: &mut LayoutContext<Canvas>| {
+                        let cell = ctx.message::<Cell<f32>>("cell".into());
+                        let value = ctx.read_message(&cell).map_or(0_f32, |cell| cell.get());
+                        let outbox = ctx.message::<f32>(i.into());
+                        outbox.inbox();
+                        let writer = Writer(outbox, value + i as f32).move_anchor();
+                        ctx.device_tree(ctx.max_size(), writer, ())
+                    }
+                });
+
+                let mut nodes = Vec::new();
+                for result in ctx.parallel(ctx.max_size(), jobs) {
+                    if let LayoutResult::CompleteNode(node) = result {
+                        nodes.push(node);
+                    }
+                }
+                ctx.layout(Size::zero(), nodes)
+            }
+
+            fn render<'ctx>(
+                &self,
+                layout: Vec<LayoutNode>,
+                ctx: RenderContext<'ctx, 'frm, Canvas>,
+                canvas: &mut Canvas,
+            ) {
+                for node in layout {
+                    ctx.render(node, ctx.region(), canvas);
+                }
+            }
+        }
+
+        let mut gui = GuiContext::default();
+        gui.register_shared::<Cells>(Arc::new(CellsRenderer));
+        gui.register_shared::<Writer>(Arc::new(WriterRenderer));
+        gui.set_layout_threads(4);
+
+        let outbox = gui.message::<Cell<f32>>("cell".into());
+        outbox.inbox();
+        gui.write_message(outbox, Cell::new(10_f32));
+        let mut canvas = Canvas::new();
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        gui.render_window(region, Cells.move_anchor(), &mut canvas)
+            .unwrap();
+        let expected: Canvas = (10..14).map(|i| ("writer", i as f32)).collect();
+        assert_eq!(canvas, expected);
+    }
+
+    #[test]
+    fn layout_threads_outlive_frames() {
+        // Each job notes the thread it ran on
+        struct Threads(Arc<Mutex<HashSet<ThreadId>>>);
+        struct ThreadsRenderer;
+        device!(Threads, 13, "threads");
+
+        impl<'frm> Renderer<'frm, Canvas> for ThreadsRenderer {
+            type Device = Threads;
+            type Layout = ();
+
+            fn layout<'thrd>(
+                &self,
+                device: Threads,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<()> {
+                let jobs = (0..16).map(|_| {
+                    let threads = device.0.clone();
+                    move |ctx: &mut LayoutContext<Canvas>| {
+                        threads.lock().unwrap().insert(thread::current().id());
+                        ctx.layout(Size::zero(), ())
+                    }
+                });
+                ctx.parallel(ctx.max_size(), jobs);
+                ctx.layout(Size::zero(), ())
+            }
+
+            fn render<'ctx>(&self, _: (), _: RenderContext<'ctx, 'frm, Canvas>, _: &mut Canvas) {}
+        }
+
+        let mut gui = GuiContext::default();
+        gui.register_shared::<Threads>(Arc::new(ThreadsRenderer));
+        gui.set_layout_threads(2);
+
+        let threads = Arc::new(Mutex::new(HashSet::new()));
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        for _ in 0..8 {
+            let root = Threads(threads.clone()).move_anchor();
+            gui.render_window(region, root, &mut Canvas::new()).unwrap();
+        }
+        assert!(threads.lock().unwrap().len() <= 2);
+    }
+
+    #[test]
+    fn local_renderer_on_layout_thread() {
+        let mut gui = GuiContext::default();
+        gui.register_shared::<Tiles>(Arc::new(TilesRenderer));
+        gui.register::<Writer>(Rc::new(WriterRenderer));
+
+        // Fine on the thread rendering the window, but not on a layout thread
+        let mut canvas = Canvas::new();
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        gui.render_window(region, Tiles(None).move_anchor(), &mut canvas)
+            .unwrap();
+        gui.set_layout_threads(2);
+        let error = gui.render_window(region, Tiles(None).move_anchor(), &mut Canvas::new());
+        assert_eq!(
+            error,
+            Err(BuoyError::LocalRenderer(DeviceInfo::of::<Writer>()))
+        );
+    }
+
+    #[test]
+    fn missing_renderer_policy() {
+        let mut gui = GuiContext::default();
+        gui.register_device(Root::type_id(), Rc::new(RootRenderer));
+        gui.register_device(Stack::type_id(), Rc::new(StackRenderer));
+        gui.register_device(Writer::type_id(), Rc::new(WriterRenderer));
+        assert_eq!(
+            gui.try_register_device(Root::type_id(), Rc::new(RootRenderer)),
+            Err(BuoyError::DuplicateDevice(DeviceInfo::unnamed(
+                Root::type_id()
+            )))
+        );
+
+        // Nothing is drawn when the frame fails
+        let mut canvas = Canvas::new();
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        let result = gui.render_window(region, Root.move_anchor(), &mut canvas);
+        let missing = BuoyError::MissingRenderer(DeviceInfo::of::<Reader>());
+        assert_eq!(result, Err(missing));
+        assert!(canvas.is_empty());
+
+        gui.set_missing_renderer_policy(MissingRendererPolicy::Skip);
+        gui.render_window(region, Root.move_anchor(), &mut canvas)
+            .unwrap();
+        assert_eq!(canvas, vec![("writer", 3_f32)]);
+    }
+
+    #[test]
+    fn deferred_without_defer() {
+        // Says it deferred, but never hands the device back
+        struct Lost;
+        struct LostRenderer;
+        device!(Lost, 8, "lost");
+
+        impl<'frm> Renderer<'frm, Canvas> for LostRenderer {
+            type Device = Lost;
+            type Layout = ();
+
+            fn layout<'thrd>(
+                &self,
+                _device: Lost,
+                _ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<()> {
+                LayoutResult::Deferred
+            }
+
+            fn render<'ctx>(&self, _: (), _: RenderContext<'ctx, 'frm, Canvas>, _: &mut Canvas) {}
+        }
+
+        let mut gui = GuiContext::default();
+        gui.register::<Lost>(Rc::new(LostRenderer));
+        let mut canvas = Canvas::new();
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        let result = gui.render_window(region, Lost.move_anchor(), &mut canvas);
+        assert_eq!(
+            result,
+            Err(BuoyError::MissingDefer(DeviceInfo::of::<Lost>()))
+        );
+    }
+
+    #[test]
+    fn type_id_collision() {
+        // Claims the same TypeId as 'Stack'
+        struct Impostor;
+        device!(Impostor, 2, "impostor");
+
+        let mut gui = GuiContext::<Canvas>::default();
+        gui.register::<Stack>(Rc::new(StackRenderer));
+        let error = gui.try_register::<Impostor>(Rc::new(StackRenderer));
+        assert_eq!(
+            error,
+            Err(BuoyError::TypeIdCollision {
+                existing: DeviceInfo::of::<Stack>(),
+                new: DeviceInfo::of::<Impostor>(),
+            })
+        );
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "buoy::impostor uses the same TypeId as buoy::stack (00000000-0000-0000-0000-000000000002)"
+        );
+
+        assert_eq!(
+            gui.registry().get(Stack::type_id()),
+            Some(&DeviceInfo::of::<Stack>())
+        );
+        assert_eq!(gui.registry().len(), 1);
+
+        // Laying one out anyway fails the frame, rather than handing it to the wrong renderer
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        let result = gui.render_window(region, Impostor.move_anchor(), &mut Canvas::new());
+        assert_eq!(
+            result,
+            Err(BuoyError::TypeIdCollision {
+                existing: DeviceInfo::of::<Stack>(),
+                new: DeviceInfo::of::<Impostor>(),
+            })
+        );
+    }
 }
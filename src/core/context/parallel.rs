@@ -0,0 +1,316 @@
+use crate::core::context::shared::SharedContext;
+use crate::core::context::{FrameContext, LayoutContext, LayoutResult, ThreadContext};
+use crate::core::device::DeviceInfo;
+use crate::core::message::MessageMap;
+use crate::space::Size;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+
+// A queued job, run on whichever layout thread picks it up
+type Task<'frm, C> =
+    Box<dyn for<'w> FnOnce(&'w Pool<'frm, C>, &'w ThreadContext<'frm, C>) + Send + 'frm>;
+
+// What a job produced, or what it panicked with
+type Output = thread::Result<(LayoutResult<()>, MessageMap)>;
+
+// The jobs started by one call to 'Pool::run', which are handed back as they finish
+struct Batch {
+    outputs: Mutex<(Vec<Option<Output>>, usize)>,
+}
+
+impl Batch {
+    fn new(len: usize) -> Self {
+        Batch {
+            outputs: Mutex::new(((0..len).map(|_| None).collect(), len)),
+        }
+    }
+
+    fn finish(&self, index: usize, output: Output) {
+        let mut outputs = self.outputs.lock().unwrap();
+        outputs.0[index] = Some(output);
+        outputs.1 -= 1;
+    }
+
+    fn is_done(&self) -> bool {
+        self.outputs.lock().unwrap().1 == 0
+    }
+
+    fn take(&self) -> Vec<Output> {
+        let outputs = std::mem::take(&mut self.outputs.lock().unwrap().0);
+        outputs.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+#[derive(Default)]
+struct Sleep {
+    // Bumped whenever a job is queued or finishes, so idle threads know to look again
+    epoch: u64,
+    stopped: bool,
+}
+
+// The layout threads for a frame, which stay up until layout is over. Each thread has its own queue of
+// the jobs started on it, taking the newest of those first and stealing the oldest from the other
+// threads (or the ones started outside of the pool) once it runs out.
+pub(in crate::core) struct Pool<'frm, C> {
+    injector: Mutex<VecDeque<Task<'frm, C>>>,
+    queues: Vec<Mutex<VecDeque<Task<'frm, C>>>>,
+    sleep: Mutex<Sleep>,
+    wake: Condvar,
+}
+
+// What a layout thread runs for a pass, borrowing from the thread rendering the window
+type Session = Box<dyn FnOnce() + Send + 'static>;
+
+// The threads set with 'GuiContext::set_layout_threads', which stay up between frames (starting them
+// for every pass would cost more than most layouts gain from them). They're only lent to a 'Pool' while
+// a pass is being laid out.
+#[derive(Default)]
+pub(in crate::core) struct LayoutThreads {
+    threads: Vec<(mpsc::Sender<Session>, thread::JoinHandle<()>)>,
+}
+
+impl LayoutThreads {
+    pub fn new(threads: usize) -> Self {
+        let threads = (0..threads)
+            .map(|i| {
+                let (sender, sessions) = mpsc::channel::<Session>();
+                let handle = thread::Builder::new()
+                    .name(format!("buoy layout {}", i))
+                    .spawn(move || sessions.into_iter().for_each(|session| session()))
+                    .expect("Couldn't start a layout thread");
+                (sender, handle)
+            })
+            .collect();
+        LayoutThreads { threads }
+    }
+
+    pub fn len(&self) -> usize {
+        self.threads.len()
+    }
+
+    // Runs the jobs from 'LayoutContext::parallel' on the layout threads (one for each of the contexts)
+    // until 'f' returns. Without any, there's no pool and jobs are run by whatever starts them.
+    pub fn scope<'frm, C: 'static, R, F>(&self, workers: &mut [ThreadContext<'frm, C>], f: F) -> R
+    where
+        F: for<'p> FnOnce(Option<&'p Pool<'frm, C>>) -> R,
+    {
+        if workers.is_empty() {
+            return f(None);
+        }
+        assert!(workers.len() <= self.threads.len());
+
+        let pool = Pool {
+            injector: Mutex::new(VecDeque::new()),
+            queues: workers
+                .iter()
+                .map(|_| Mutex::new(VecDeque::new()))
+                .collect(),
+            sleep: Mutex::new(Sleep::default()),
+            wake: Condvar::new(),
+        };
+        let sessions = Sessions::default();
+
+        let result = {
+            // The sessions have to be over before anything they borrow goes away, even if 'f' panics
+            let _end = End(&pool, &sessions);
+            for ((sender, _), worker) in self.threads.iter().zip(workers.iter_mut()) {
+                let (pool, sessions) = (&pool, &sessions);
+                let session = move || {
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| pool.work(worker)));
+                    sessions.finish(result.err());
+                };
+                let session: Box<dyn FnOnce() + Send + '_> = Box::new(session);
+
+                // Safe because 'End' waits for the session to finish before this returns or unwinds
+                let session: Session = unsafe { std::mem::transmute(session) };
+                sessions.start();
+                sender.send(session).expect("A layout thread stopped");
+            }
+
+            f(Some(&pool))
+        };
+
+        if let Some(panic) = sessions.running.lock().unwrap().1.take() {
+            panic::resume_unwind(panic);
+        }
+        result
+    }
+}
+
+impl Drop for LayoutThreads {
+    fn drop(&mut self) {
+        // Dropping the sender ends the thread's loop
+        for (sender, handle) in self.threads.drain(..) {
+            drop(sender);
+            let _ = handle.join();
+        }
+    }
+}
+
+// The sessions still running, and the first panic from one that's finished
+#[derive(Default)]
+struct Sessions {
+    running: Mutex<(usize, Option<Box<dyn Any + Send>>)>,
+    finished: Condvar,
+}
+
+impl Sessions {
+    fn start(&self) {
+        self.running.lock().unwrap().0 += 1;
+    }
+
+    fn finish(&self, panic: Option<Box<dyn Any + Send>>) {
+        let mut sessions = self.running.lock().unwrap();
+        sessions.0 -= 1;
+        if sessions.1.is_none() {
+            sessions.1 = panic;
+        }
+        self.finished.notify_all();
+    }
+}
+
+// Stops the pool, and waits for the layout threads to leave it
+struct End<'a, 'frm, C>(&'a Pool<'frm, C>, &'a Sessions);
+
+impl<'a, 'frm, C> Drop for End<'a, 'frm, C> {
+    fn drop(&mut self) {
+        {
+            let mut sleep = self.0.sleep.lock().unwrap();
+            sleep.stopped = true;
+            self.0.wake.notify_all();
+        }
+
+        let mut sessions = self.1.running.lock().unwrap();
+        while sessions.0 > 0 {
+            sessions = self.1.finished.wait(sessions).unwrap();
+        }
+    }
+}
+
+impl<'frm, C: 'static> Pool<'frm, C> {
+    // Queues the jobs, and waits for all of them to finish. A layout thread runs queued jobs (its own
+    // first) while it waits, so jobs can start jobs of their own. Each job gets its own set of
+    // outgoing messages, so nothing about the results depends on which thread happened to run what.
+    // Messages the jobs write themselves are attributed to 'writer', the device that started them.
+    pub fn run<F>(
+        &self,
+        thread_ctx: &ThreadContext<'frm, C>,
+        shared: SharedContext<'frm, C>,
+        frame_ctx: &'frm FrameContext,
+        writer: Option<DeviceInfo>,
+        max_size: Size,
+        jobs: Vec<F>,
+    ) -> Vec<(LayoutResult<()>, MessageMap)>
+    where
+        F: for<'w> FnOnce(&mut LayoutContext<'w, 'frm, C>) -> LayoutResult<()> + Send + 'frm,
+    {
+        let batch = Arc::new(Batch::new(jobs.len()));
+        let tasks = jobs.into_iter().enumerate().map(|(index, job)| {
+            let batch = batch.clone();
+            Box::new(
+                move |pool: &Pool<'frm, C>, thread_ctx: &ThreadContext<'frm, C>| {
+                    let output = panic::catch_unwind(AssertUnwindSafe(|| {
+                        let outer_messages = thread_ctx.take_outgoing_messages();
+                        let outer_writer = thread_ctx.writer();
+                        thread_ctx.set_writer(writer);
+
+                        let mut ctx = LayoutContext::new(
+                            shared,
+                            frame_ctx,
+                            thread_ctx,
+                            None,
+                            Some(pool),
+                            max_size,
+                            Vec::new(),
+                        );
+                        let mut result = job(&mut ctx);
+                        if let LayoutResult::CompleteNode(ref mut node) = result {
+                            node.thread = thread_ctx.thread();
+                        }
+
+                        thread_ctx.set_writer(outer_writer);
+                        (result, thread_ctx.replace_outgoing_messages(outer_messages))
+                    }));
+                    batch.finish(index, output);
+                    pool.notify();
+                },
+            ) as Task<'frm, C>
+        });
+
+        match thread_ctx.thread() {
+            Some(thread) => self.queues[thread].lock().unwrap().extend(tasks),
+            None => self.injector.lock().unwrap().extend(tasks),
+        }
+        self.notify();
+
+        loop {
+            let epoch = self.epoch();
+            if batch.is_done() {
+                break;
+            }
+
+            // The thread rendering the window has nowhere to run jobs, so it just waits
+            match thread_ctx.thread().and_then(|thread| self.next(thread)) {
+                Some(task) => task(self, thread_ctx),
+                None => self.wait(epoch),
+            }
+        }
+
+        batch
+            .take()
+            .into_iter()
+            .map(|output| output.unwrap_or_else(|panic| panic::resume_unwind(panic)))
+            .collect()
+    }
+
+    // Runs on each layout thread until the pool is stopped
+    fn work(&self, thread_ctx: &ThreadContext<'frm, C>) {
+        let thread = thread_ctx
+            .thread()
+            .expect("Layout threads have a thread index");
+        loop {
+            let epoch = self.epoch();
+            match self.next(thread) {
+                Some(task) => task(self, thread_ctx),
+                None if self.sleep.lock().unwrap().stopped => break,
+                None => self.wait(epoch),
+            }
+        }
+    }
+
+    // The next job for a thread to run: the newest one started on it, or else the oldest one started
+    // outside of the pool, or else the oldest one started on another thread
+    fn next(&self, thread: usize) -> Option<Task<'frm, C>> {
+        if let Some(task) = self.queues[thread].lock().unwrap().pop_back() {
+            return Some(task);
+        }
+        if let Some(task) = self.injector.lock().unwrap().pop_front() {
+            return Some(task);
+        }
+
+        let len = self.queues.len();
+        (1..len)
+            .map(|offset| (thread + offset) % len)
+            .find_map(|other| self.queues[other].lock().unwrap().pop_front())
+    }
+
+    fn epoch(&self) -> u64 {
+        self.sleep.lock().unwrap().epoch
+    }
+
+    fn notify(&self) {
+        self.sleep.lock().unwrap().epoch += 1;
+        self.wake.notify_all();
+    }
+
+    // Blocks until something has been queued or finished since 'epoch' was read, or the pool stops
+    fn wait(&self, epoch: u64) {
+        let mut sleep = self.sleep.lock().unwrap();
+        while sleep.epoch == epoch && !sleep.stopped {
+            sleep = self.wake.wait(sleep).unwrap();
+        }
+    }
+}
@@ -1,29 +1,312 @@
-use crate::core::context::{GuiContext, ThreadContext};
+use crate::core::context::{GuiContext, LocalRenderers, ThreadContext};
+use crate::core::device::{DeviceInfo, LayoutIndex, RendererWrapper};
+use crate::core::id::Id;
+use crate::core::input::{FocusTarget, HitRegion};
+use crate::core::state::{State, StateValue};
+use crate::core::topic::{Topic, TopicEvent};
+use crate::message::{Message, Outbox};
 use crate::space::Region;
 use crate::LayoutNode;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// Run around every device as it renders (set with 'GuiContext::set_render_hooks'), so a canvas can
+// tell which device drew what. Calls are nested the same way the devices are. A node put in a higher
+// layer is drawn after its ancestors have finished, so they're begun again around it (and ended after
+// it), outermost first.
+pub struct RenderHooks<C> {
+    pub begin: fn(&mut C, &DeviceInfo),
+    pub end: fn(&mut C, &DeviceInfo),
+}
+
+// Layers are drawn in order, lowest first, so everything on a layer appears above (and takes pointer
+// input before) everything on the layers below it. Devices are on 'Layer::BASE' unless a parent puts
+// them somewhere else with 'RenderContext::render_in_layer'.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Layer(pub i32);
+
+impl Layer {
+    pub const BASE: Layer = Layer(0);
+    pub const POPUP: Layer = Layer(100);
+    pub const TOOLTIP: Layer = Layer(200);
+    pub const DRAG: Layer = Layer(300);
+}
+
+// A node put in a higher layer, waiting for the layers below it to be drawn
+pub(in crate::core) struct LayeredNode<'slf, 'frm, C> {
+    layer: Layer,
+    // How many nodes were put in higher layers before this one during the pass
+    order: usize,
+    node: LayoutNode,
+    region: Region,
+    thread_ctx: &'slf ThreadContext<'frm, C>,
+    ancestors: Vec<DeviceInfo>,
+}
+
+// Ordered so the heap gives back the lowest layer first, and the earliest node within it
+impl<'slf, 'frm, C> Ord for LayeredNode<'slf, 'frm, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.layer, other.order).cmp(&(self.layer, self.order))
+    }
+}
+
+impl<'slf, 'frm, C> PartialOrd for LayeredNode<'slf, 'frm, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'slf, 'frm, C> PartialEq for LayeredNode<'slf, 'frm, C> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.layer, self.order) == (other.layer, other.order)
+    }
+}
+
+impl<'slf, 'frm, C> Eq for LayeredNode<'slf, 'frm, C> {}
+
+// The nodes put in higher layers during a pass
+pub(in crate::core) struct LayeredNodes<'slf, 'frm, C> {
+    heap: BinaryHeap<LayeredNode<'slf, 'frm, C>>,
+    added: usize,
+}
+
+impl<'slf, 'frm, C> Default for LayeredNodes<'slf, 'frm, C> {
+    fn default() -> Self {
+        LayeredNodes {
+            heap: BinaryHeap::new(),
+            added: 0,
+        }
+    }
+}
+
+// The interactive regions registered while rendering, which the next frame's input is checked against
+#[derive(Default)]
+pub(in crate::core) struct RenderOutput {
+    pub hits: Vec<HitRegion>,
+    pub focusable: Vec<FocusTarget>,
+}
+
+impl RenderOutput {
+    pub fn extend(&mut self, other: RenderOutput) {
+        self.hits.extend(other.hits);
+        self.focusable.extend(other.focusable);
+    }
+}
 
 pub struct RenderContext<'slf, 'frm, C> {
     pub(in crate::core) region: Region,
+    pub(in crate::core) layer: Layer,
     pub(in crate::core) gui_ctx: &'frm GuiContext<C>,
     pub(in crate::core) thread_ctx: &'slf ThreadContext<'frm, C>,
+    pub(in crate::core) workers: &'slf [ThreadContext<'frm, C>],
+    pub(in crate::core) local: &'slf LocalRenderers<'frm, C>,
+    pub(in crate::core) output: &'slf RefCell<RenderOutput>,
+    pub(in crate::core) layered: &'slf RefCell<LayeredNodes<'slf, 'frm, C>>,
+    // The devices being rendered, outermost first, while there are render hooks to tell about them
+    pub(in crate::core) ancestors: &'slf RefCell<Vec<DeviceInfo>>,
 }
 
 impl<'slf, 'frm, C: 'static> RenderContext<'slf, 'frm, C> {
     pub fn render(&self, node: LayoutNode, region: Region, canvas: &mut C) {
-        // Get the renderer for this node
-        let renderer = self.thread_ctx.renderer_for(self.gui_ctx, node.type_id);
+        let thread_ctx = self.thread_for(&node);
+        self.render_on(thread_ctx, self.layer, node, region, canvas);
+    }
+
+    // Renders the node (and its children) on the given layer, once everything on the layers below it
+    // has been drawn. Whatever state the canvas is in at that point (clips, transforms, etc) applies,
+    // rather than the state it's in now, which is how popups escape the viewports they're opened from.
+    // Layers at or below the current one have already been started, so the node is drawn straight away.
+    pub fn render_in_layer(&self, layer: Layer, node: LayoutNode, region: Region, canvas: &mut C) {
+        if layer <= self.layer {
+            return self.render(node, region, canvas);
+        }
+
+        let thread_ctx = self.thread_for(&node);
+        let mut layered = self.layered.borrow_mut();
+        let order = layered.added;
+        layered.added += 1;
+        layered.heap.push(LayeredNode {
+            layer,
+            order,
+            node,
+            region,
+            thread_ctx,
+            ancestors: self.ancestors.borrow().clone(),
+        });
+    }
+
+    // Nodes laid out on another thread have their layouts stored with that thread
+    fn thread_for(&self, node: &LayoutNode) -> &'slf ThreadContext<'frm, C> {
+        match node.thread {
+            Some(thread) => &self.workers[thread],
+            None => self.thread_ctx,
+        }
+    }
+
+    fn render_on(
+        &self,
+        thread_ctx: &'slf ThreadContext<'frm, C>,
+        layer: Layer,
+        node: LayoutNode,
+        region: Region,
+        canvas: &mut C,
+    ) {
+        // Get the renderer for this node. Only the thread rendering the window has local renderers, and
+        // nodes are only ever made by renderers that were found.
+        let local = thread_ctx.thread().map_or(Some(self.local), |_| None);
+        let renderer = match thread_ctx.renderer_for(self.gui_ctx.shared(), local, node.type_id) {
+            Some(renderer) => renderer,
+            None => return,
+        };
 
         // Create a render context
         let ctx = RenderContext {
             region,
+            layer,
             gui_ctx: self.gui_ctx,
-            thread_ctx: self.thread_ctx,
+            thread_ctx,
+            workers: self.workers,
+            local: self.local,
+            output: self.output,
+            layered: self.layered,
+            ancestors: self.ancestors,
         };
 
-        renderer.render(node.index, ctx, canvas);
+        ctx.render_with(renderer, node.index, canvas);
+    }
+
+    // Draws everything put in a higher layer, lowest layer first (and in the order they were added
+    // within a layer), including anything those put in even higher layers
+    pub(in crate::core) fn render_layered(&self, canvas: &mut C) {
+        loop {
+            let next = self.layered.borrow_mut().heap.pop();
+            let next = match next {
+                Some(next) => next,
+                None => break,
+            };
+
+            let hooks = self.gui_ctx.render_hooks();
+            if let Some(hooks) = hooks {
+                for device in &next.ancestors {
+                    (hooks.begin)(canvas, device);
+                }
+            }
+
+            let outer = self.ancestors.replace(next.ancestors);
+            self.render_on(next.thread_ctx, next.layer, next.node, next.region, canvas);
+            let ancestors = self.ancestors.replace(outer);
+
+            if let Some(hooks) = hooks {
+                for device in ancestors.iter().rev() {
+                    (hooks.end)(canvas, device);
+                }
+            }
+        }
+    }
+
+    pub(in crate::core) fn render_with(
+        self,
+        renderer: &dyn RendererWrapper<'frm, C>,
+        layout: LayoutIndex,
+        canvas: &mut C,
+    ) {
+        let thread_ctx = self.thread_ctx;
+        let hooks = match self.gui_ctx.render_hooks() {
+            Some(hooks) => hooks,
+            None => {
+                return thread_ctx.with_writer(renderer, || renderer.render(layout, self, canvas));
+            }
+        };
+
+        let device = renderer.device_info();
+        let ancestors = self.ancestors;
+        (hooks.begin)(canvas, &device);
+        ancestors.borrow_mut().push(device);
+        thread_ctx.with_writer(renderer, || renderer.render(layout, self, canvas));
+        ancestors.borrow_mut().pop();
+        (hooks.end)(canvas, &device);
     }
 
     pub fn region(&self) -> Region {
         self.region
     }
+
+    pub fn layer(&self) -> Layer {
+        self.layer
+    }
+
+    // Makes the region interactive, so pointer events over it are sent to 'pointer_id(id)' next frame.
+    // Only the topmost region under the pointer gets them: the one on the highest layer, or the one
+    // registered last if they're on the same layer.
+    pub fn hit_region(&self, id: Id, region: Region) {
+        let layer = self.layer;
+        let hit = HitRegion { id, region, layer };
+        self.output.borrow_mut().hits.push(hit);
+    }
+
+    // Lets the region take keyboard focus, which is sent to 'focused_id()'. Tab moves through the
+    // focusable regions in the order they were rendered, and the arrow keys by where they are.
+    pub fn focusable(&self, id: Id, region: Region) {
+        self.push_focusable(id, region, None);
+    }
+
+    // Like 'focusable', but Tab visits the region in the given order, before any regions without one
+    pub fn focusable_in_order(&self, id: Id, region: Region, order: i32) {
+        self.push_focusable(id, region, Some(order));
+    }
+
+    fn push_focusable(&self, id: Id, region: Region, order: Option<i32>) {
+        let target = FocusTarget {
+            id,
+            region,
+            layer: self.layer,
+            order,
+        };
+        self.output.borrow_mut().focusable.push(target);
+    }
+
+    // State that persists across frames. See 'GuiContext::state'.
+    #[inline]
+    pub fn state<T: StateValue + Default>(&self, id: Id) -> State<T> {
+        self.gui_ctx.state(id)
+    }
+
+    #[inline]
+    pub fn state_or_insert_with<T: StateValue, F: FnOnce() -> T>(
+        &self,
+        id: Id,
+        init: F,
+    ) -> State<T> {
+        self.gui_ctx.state_or_insert_with(id, init)
+    }
+
+    #[inline]
+    pub fn message<T: Message>(&self, id: Id) -> Outbox<T> {
+        Outbox::new(id)
+    }
+
+    // Messages written while rendering are only seen by the next frame (or pass), since layout for
+    // this one has already finished. Useful for things that depend on the final region.
+    #[inline]
+    pub fn write_message<T: Message>(&self, outbox: Outbox<T>, value: T) {
+        self.thread_ctx.write_message(outbox, value)
+    }
+
+    // Unlike messages, events published while rendering are only seen by the next frame, not by later
+    // passes
+    #[inline]
+    pub fn publish<T: Message>(&self, topic: &Topic<T>, value: T) {
+        self.thread_ctx.publish(topic, value)
+    }
+
+    #[inline]
+    pub fn events<T: Message>(&self, topic: &Topic<T>) -> Vec<T> {
+        self.gui_ctx.events(topic)
+    }
+
+    #[inline]
+    pub fn events_since<T: Message>(&self, topic: &Topic<T>, sequence: u64) -> Vec<TopicEvent<T>> {
+        self.gui_ctx.events_since(topic, sequence)
+    }
 }
@@ -0,0 +1,48 @@
+use crate::core::context::MissingRendererPolicy;
+use crate::core::device::{DeviceRegistry, IntoSharedRenderer, TypeId};
+use crate::core::id::Id;
+use crate::core::state::{State, StateStore, StateValue};
+use crate::core::topic::{Topic, TopicEvent, TopicStore};
+use crate::message::Message;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub(in crate::core) type SharedRenderers<C> = HashMap<TypeId, Arc<dyn IntoSharedRenderer<C>>>;
+
+// The parts of the GuiContext that every layout thread can see during a frame. Renderers registered
+// with 'GuiContext::register' aren't among them, since they can only run on the thread rendering the
+// window.
+pub(in crate::core) struct SharedContext<'frm, C> {
+    pub renderers: &'frm SharedRenderers<C>,
+    pub registry: &'frm DeviceRegistry,
+    pub missing_renderer_policy: MissingRendererPolicy,
+    pub state: &'frm StateStore,
+    pub topics: &'frm TopicStore,
+    pub frame: u64,
+}
+
+impl<'frm, C> Clone for SharedContext<'frm, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'frm, C> Copy for SharedContext<'frm, C> {}
+
+impl<'frm, C> SharedContext<'frm, C> {
+    pub fn state_or_insert_with<T: StateValue, F: FnOnce() -> T>(
+        &self,
+        id: Id,
+        init: F,
+    ) -> State<T> {
+        self.state.get_or_insert_with(id, self.frame, init)
+    }
+
+    pub fn events<T: Message>(&self, topic: &Topic<T>) -> Vec<T> {
+        self.topics.events(topic, self.frame)
+    }
+
+    pub fn events_since<T: Message>(&self, topic: &Topic<T>, sequence: u64) -> Vec<TopicEvent<T>> {
+        self.topics.events_since(topic, sequence)
+    }
+}
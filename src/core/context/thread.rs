@@ -1,59 +1,264 @@
-use crate::core::context::GuiContext;
-use crate::core::device::{RendererWrapper, TypeId};
-use crate::core::message::{Message, MessageMap, Outbox};
-use crate::util::arena::Arena;
-use std::cell::RefCell;
+use crate::core::context::shared::SharedContext;
+use crate::core::context::{GuiContext, MissingRendererPolicy};
+use crate::core::device::{
+    Device, DeviceIndex, DeviceInfo, MissingDevice, MissingRendererWrapper, RendererWrapper, TypeId,
+};
+use crate::core::error::BuoyError;
+use crate::core::id::Id;
+use crate::core::message::{Inbox, Message, MessageMap, Outbox};
+use crate::core::topic::Topic;
+use crate::core::trace::TraceKind;
+use crate::util::ref_move::{ref_move, Anchor, Ext};
+use std::cell::{Cell, RefCell};
 use std::collections::hash_map::{Entry, HashMap};
 
+// Everything here is 'Send', so that a layout thread's context can be handed back to the thread
+// rendering the window, to render what was laid out on it.
 pub struct ThreadContext<'frm, C> {
     // TODO: Eventually replace these with UnsafeCell
-    renderers: RefCell<HashMap<TypeId, Box<dyn RendererWrapper<'frm, C> + 'frm>>>,
+    renderers: RefCell<HashMap<TypeId, Box<dyn RendererWrapper<'frm, C> + Send + 'frm>>>,
     outgoing_messages: RefCell<MessageMap>,
-    buffer: &'frm Arena,
+
+    // Stands in for the renderer of any device that couldn't be allocated
+    missing: MissingRendererWrapper<'frm, C>,
+
+    // The layout thread this context belongs to, or 'None' for the thread rendering the window
+    thread: Option<usize>,
+
+    // Bumped whenever something happens that could unblock a deferred device
+    generation: Cell<usize>,
+
+    // The first error that should fail the frame
+    error: RefCell<Option<BuoyError>>,
+
+    // While debugging or tracing messages, the device whose layout or render is running, to name in
+    // conflicts and traces
+    track_writers: bool,
+    writer: Cell<Option<DeviceInfo>>,
 }
 
 impl<'frm, C: 'static> ThreadContext<'frm, C> {
-    pub fn new(buffer: &'frm Arena) -> Self {
+    pub fn new(thread: Option<usize>) -> Self {
         ThreadContext {
             renderers: Default::default(),
             outgoing_messages: Default::default(),
-            buffer,
+            missing: Default::default(),
+            thread,
+            generation: Cell::new(0),
+            error: RefCell::new(None),
+            track_writers: false,
+            writer: Cell::new(None),
+        }
+    }
+
+    pub(in crate::core) fn set_message_tracking(&mut self, debug: bool, tracing: bool) {
+        self.track_writers = debug || tracing;
+        self.outgoing_messages.get_mut().set_debug(debug);
+        self.outgoing_messages.get_mut().set_tracing(tracing);
+    }
+
+    // Runs the function with messages written on this thread attributed to the renderer's device
+    pub(in crate::core) fn with_writer<R, F: FnOnce() -> R>(
+        &self,
+        renderer: &dyn RendererWrapper<'frm, C>,
+        f: F,
+    ) -> R {
+        if !self.track_writers {
+            return f();
         }
+
+        let outer = self.writer.replace(Some(renderer.device_info()));
+        let result = f();
+        self.writer.set(outer);
+        result
+    }
+
+    pub(in crate::core) fn writer(&self) -> Option<DeviceInfo> {
+        self.writer.get()
+    }
+
+    // Attributes messages to a device that's running on another thread (like a parallel job)
+    pub(in crate::core) fn set_writer(&self, writer: Option<DeviceInfo>) {
+        self.writer.set(writer);
     }
 
-    pub fn buffer(&self) -> &'frm Arena {
-        self.buffer
+    pub(in crate::core) fn thread(&self) -> Option<usize> {
+        self.thread
     }
 
-    pub fn renderer_for<'thrd>(
+    // The renderer for a TypeId, if one is registered. Renderers registered with 'GuiContext::register'
+    // are only found on the thread rendering the window, which passes them in as 'local'.
+    pub(in crate::core) fn renderer_for<'thrd>(
         &'thrd self,
-        gui: &'frm GuiContext<C>,
+        shared: SharedContext<'frm, C>,
+        local: Option<&'thrd LocalRenderers<'frm, C>>,
         type_id: TypeId,
-    ) -> &'thrd dyn RendererWrapper<'frm, C> {
-        let mut renderers = self.renderers.borrow_mut();
-        let value = match renderers.entry(type_id) {
-            Entry::Occupied(entry) => &**entry.into_mut(),
-            Entry::Vacant(entry) => {
-                let renderer_factory = gui
-                    .renderers
-                    .get(&type_id)
-                    .ok_or_else(|| format!("No renderer registered for {}", type_id))
-                    .unwrap();
-                &**entry.insert(renderer_factory.into_renderer())
-            }
+    ) -> Option<&'thrd dyn RendererWrapper<'frm, C>> {
+        if let Some(renderer) = local.and_then(|local| local.renderer_for(type_id)) {
+            return Some(renderer);
+        }
+
+        let renderer: *const (dyn RendererWrapper<'frm, C> + 'frm) = {
+            let mut renderers = self.renderers.borrow_mut();
+            let renderer = match renderers.entry(type_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let factory = shared.renderers.get(&type_id)?;
+                    entry.insert(factory.into_shared_renderer())
+                }
+            };
+            &**renderer
         };
 
-        // Safe because ABox's can be moved around without invalidating references to their contents
+        // Safe because Box's can be moved around without invalidating references to their contents
         // (so reallocating the HashMap won't invalidate the returned reference)
         // and nothing will be removed from the HashMap until this ThreadContext is destroyed.
-        unsafe { &*(value as *const dyn RendererWrapper<'frm, C>) }
+        Some(unsafe { &*renderer })
+    }
+
+    // Allocates the device with the renderer registered for its type. Devices without one are
+    // dropped, or swapped out for a 'MissingDevice', depending on the missing renderer policy.
+    pub(in crate::core) fn alloc<'thrd, 'dev: 'frm, D: Anchor<dyn Device + 'dev>>(
+        &'thrd self,
+        shared: SharedContext<'frm, C>,
+        local: Option<&'thrd LocalRenderers<'frm, C>>,
+        device: D,
+    ) -> (&'thrd dyn RendererWrapper<'frm, C>, DeviceIndex) {
+        if let Some(renderer) = self.renderer_for(shared, local, device.get_type_id()) {
+            match ref_move(device, |d| renderer.alloc(d)) {
+                Ok(index) => return (renderer, index),
+                // The renderer is for another device type with the same TypeId, and dropped it
+                Err(error) => {
+                    self.report_error(error);
+                    return (&self.missing, DeviceIndex(0));
+                }
+            }
+        }
+
+        let info = DeviceInfo::of_device(&*device);
+        let error = match shared.missing_renderer_policy {
+            // It has a renderer, just not one that can run here
+            _ if shared.registry.get(info.type_id).is_some() => {
+                Some(BuoyError::LocalRenderer(info))
+            }
+            MissingRendererPolicy::Skip => None,
+            MissingRendererPolicy::Placeholder if info.type_id != MissingDevice::type_id() => {
+                let placeholder = MissingDevice {
+                    type_id: info.type_id,
+                    package_name: info.package_name,
+                    type_name: info.type_name,
+                };
+                std::mem::drop(device);
+                return self.alloc(shared, local, placeholder.move_anchor());
+            }
+            MissingRendererPolicy::Placeholder | MissingRendererPolicy::Fail => {
+                Some(BuoyError::MissingRenderer(info))
+            }
+        };
+
+        if let Some(error) = error {
+            self.report_error(error);
+        }
+        std::mem::drop(device);
+        (&self.missing, DeviceIndex(0))
+    }
+
+    // Fails the frame, unless something else already has
+    pub(in crate::core) fn report_error(&self, error: BuoyError) {
+        self.error.borrow_mut().get_or_insert(error);
+    }
+
+    pub fn take_error(&self) -> Option<BuoyError> {
+        self.error.borrow_mut().take()
     }
 
     pub fn write_message<T: Message>(&self, outbox: Outbox<T>, value: T) {
-        self.outgoing_messages.borrow_mut().write(outbox, value);
+        let writer = self.writer.get();
+        self.outgoing_messages
+            .borrow_mut()
+            .write_from(outbox, value, writer);
+        self.advance_generation();
+    }
+
+    pub fn publish<T: Message>(&self, topic: &Topic<T>, value: T) {
+        let value = Box::new(value);
+        self.outgoing_messages
+            .borrow_mut()
+            .publish(topic.key(), value);
+    }
+
+    pub fn read_message<T: Message>(&self, inbox: Inbox<T>) -> Option<T> {
+        self.outgoing_messages.borrow().read(inbox)
+    }
+
+    // Adds a read by the current device to the trace, if messages are being traced
+    pub(in crate::core) fn record_read<T: Message>(&self, id: Id, hit: bool) {
+        let kind = match hit {
+            true => TraceKind::ReadHit,
+            false => TraceKind::ReadMiss,
+        };
+        let device = self.writer.get();
+        self.outgoing_messages
+            .borrow_mut()
+            .record::<T>(kind, id, device);
+    }
+
+    pub(in crate::core) fn has_message(&self, id: Id) -> bool {
+        self.outgoing_messages.borrow().contains(id)
+    }
+
+    pub(in crate::core) fn generation(&self) -> usize {
+        self.generation.get()
+    }
+
+    pub(in crate::core) fn advance_generation(&self) {
+        self.generation.set(self.generation.get().wrapping_add(1));
     }
 
-    pub fn take_outgoing_messages(&mut self) -> MessageMap {
-        std::mem::take(&mut self.outgoing_messages.borrow_mut())
+    pub fn take_outgoing_messages(&self) -> MessageMap {
+        self.outgoing_messages.borrow_mut().take()
+    }
+
+    // Swaps in another set of outgoing messages, returning the ones written since the last swap
+    pub(in crate::core) fn replace_outgoing_messages(&self, messages: MessageMap) -> MessageMap {
+        std::mem::replace(&mut *self.outgoing_messages.borrow_mut(), messages)
+    }
+
+    pub(in crate::core) fn extend_outgoing_messages(&self, messages: &mut MessageMap) {
+        self.outgoing_messages.borrow_mut().extend(messages);
+        self.advance_generation();
+    }
+}
+
+// The renderers registered with 'GuiContext::register', which aren't 'Send' or 'Sync', so they only run
+// on the thread rendering the window
+pub(in crate::core) struct LocalRenderers<'frm, C> {
+    gui_ctx: &'frm GuiContext<C>,
+    renderers: RefCell<HashMap<TypeId, Box<dyn RendererWrapper<'frm, C> + 'frm>>>,
+}
+
+impl<'frm, C: 'static> LocalRenderers<'frm, C> {
+    pub fn new(gui_ctx: &'frm GuiContext<C>) -> Self {
+        LocalRenderers {
+            gui_ctx,
+            renderers: Default::default(),
+        }
+    }
+
+    fn renderer_for(&self, type_id: TypeId) -> Option<&dyn RendererWrapper<'frm, C>> {
+        let renderer: *const (dyn RendererWrapper<'frm, C> + 'frm) = {
+            let mut renderers = self.renderers.borrow_mut();
+            let renderer = match renderers.entry(type_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let factory = self.gui_ctx.renderers.get(&type_id)?;
+                    entry.insert(factory.into_renderer())
+                }
+            };
+            &**renderer
+        };
+
+        // Safe for the same reasons as in 'ThreadContext::renderer_for'
+        Some(unsafe { &*renderer })
     }
 }
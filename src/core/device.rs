@@ -1,17 +1,32 @@
 mod type_id;
 pub use type_id::TypeId;
 
+mod registry;
+pub use self::registry::{DeviceInfo, DeviceRegistry};
+
 mod socket;
 pub use socket::{Socket, SocketName};
 
 mod renderer;
 pub use self::renderer::{
-    DeviceIndex, IntoRenderer, LayoutIndex, Renderer, RendererLayoutResult, RendererWrapper,
+    DeviceIndex, IntoRenderer, IntoSharedRenderer, LayoutIndex, Renderer, RendererLayoutResult,
+    RendererWrapper,
 };
 
-// This trait is unsafe because a careless implementation
-// of 'get_type_id()' (ie, returning a different TypeId than what this was registered with)
-// will cause an improper downcast.
+mod cache;
+pub use self::cache::Cached;
+
+#[cfg(feature = "derive")]
+pub use buoy_derive::Device;
+
+mod missing;
+pub use self::missing::MissingDevice;
+pub(crate) use self::missing::MissingRendererWrapper;
+
+/// # Safety
+/// This trait is unsafe because a careless implementation
+/// of 'get_type_id()' (ie, returning a different TypeId than what this was registered with)
+/// will cause an improper downcast.
 pub unsafe trait DynDevice {
     fn get_type_id(&self) -> TypeId;
     fn get_package_name(&self) -> &'static str;
@@ -47,3 +62,29 @@ unsafe impl<T: Device> DynDevice for T {
         T::type_name()
     }
 }
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Device)]
+    #[buoy(uuid = "2e8a1d7c-5b3f-4c61-9d0e-7f4a8b2c6e15", package = "widgets")]
+    struct Button;
+
+    #[derive(Device)]
+    #[buoy(uuid = "9c5fd3b8-07e1-4f0e-8a44-2b7d6c1e90f3", name = "List")]
+    struct ListView<T>(T);
+
+    #[test]
+    fn derive() {
+        assert_eq!(
+            Button::type_id(),
+            TypeId::new(0x2e8a1d7c_5b3f_4c61_9d0e_7f4a8b2c6e15)
+        );
+        assert_eq!(Button::package_name(), "widgets");
+        assert_eq!(Button::type_name(), "Button");
+
+        assert_eq!(ListView::<u32>::package_name(), "buoy");
+        assert_eq!(ListView::<u32>::type_name(), "List");
+    }
+}
@@ -0,0 +1,225 @@
+use crate::core::context::{LayoutContext, LayoutResult, RenderContext};
+use crate::core::device::{Device, Renderer};
+use crate::space::Size;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+// A device along with the space it was given. The device itself is kept, rather than just its hash,
+// so that two devices that happen to hash the same can't be mixed up.
+#[derive(PartialEq, Eq, Hash)]
+struct Key<D> {
+    device: D,
+    width: u32,
+    height: u32,
+}
+
+struct Entry<L> {
+    min_size: Size,
+    layout: L,
+    last_used: u64,
+}
+
+struct Cache<D, L> {
+    entries: HashMap<Key<D>, Entry<L>>,
+    last_sweep: u64,
+}
+
+// Wraps a renderer so that its devices can skip layout when nothing they depend on has changed,
+// reusing the layout from the last time an identical device was given the same amount of space.
+// Devices are compared for equality, so anything that affects layout (including their Id, if they
+// have one) needs to be part of it. Devices with children are never cached, and since cached devices
+// aren't run at all, renderers that read or write messages during layout shouldn't be cached.
+pub struct Cached<R, D, L> {
+    renderer: R,
+    retain_frames: u64,
+    cache: Mutex<Cache<D, L>>,
+}
+
+impl<R, D, L> Cached<R, D, L> {
+    pub fn new(renderer: R) -> Self {
+        Cached {
+            renderer,
+            retain_frames: 1,
+            cache: Mutex::new(Cache {
+                entries: HashMap::new(),
+                last_sweep: 0,
+            }),
+        }
+    }
+
+    // Sets how many frames a layout may go unused before it's evicted (one by default).
+    pub fn retain_frames(mut self, frames: u64) -> Self {
+        self.retain_frames = frames;
+        self
+    }
+
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().entries.clear();
+    }
+}
+
+impl<'frm, C: 'static, R, D, L> Renderer<'frm, C> for Cached<R, D, L>
+where
+    R: Renderer<'frm, C, Device = D, Layout = L>,
+    D: Device + Hash + Eq + Clone + 'frm,
+    L: Clone + 'frm,
+{
+    type Device = D;
+    type Layout = L;
+
+    fn layout<'thrd>(
+        &self,
+        device: Self::Device,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<L> {
+        // Children aren't part of the key, so anything with children has to be run
+        if !ctx.children.is_empty() {
+            return self.renderer.layout(device, ctx);
+        }
+
+        let frame = ctx.shared.frame;
+        let key = Key {
+            device,
+            width: ctx.max_size().width.to_bits(),
+            height: ctx.max_size().height.to_bits(),
+        };
+
+        // Reuse the last layout if we have one
+        {
+            let mut cache = self.cache.lock().unwrap();
+            sweep(&mut cache, frame, self.retain_frames);
+
+            if let Some(entry) = cache.entries.get_mut(&key) {
+                entry.last_used = frame;
+                return ctx.layout(entry.min_size, entry.layout.clone());
+            }
+        }
+
+        let result = self.renderer.layout(key.device.clone(), ctx);
+        if let LayoutResult::Complete {
+            min_size,
+            ref layout,
+        } = result
+        {
+            let entry = Entry {
+                min_size,
+                layout: layout.clone(),
+                last_used: frame,
+            };
+            self.cache.lock().unwrap().entries.insert(key, entry);
+        }
+
+        result
+    }
+
+    fn render<'ctx>(&self, layout: L, ctx: RenderContext<'ctx, 'frm, C>, canvas: &mut C) {
+        self.renderer.render(layout, ctx, canvas);
+    }
+}
+
+// Evicts anything that's gone unused for too long, at most once per frame
+fn sweep<D, L>(cache: &mut Cache<D, L>, frame: u64, retain_frames: u64) {
+    if cache.last_sweep == frame {
+        return;
+    }
+
+    cache.last_sweep = frame;
+    cache
+        .entries
+        .retain(|_, entry| entry.last_used + retain_frames >= frame);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::util::ref_move::Ext;
+    use std::cell::Cell;
+    use std::hash::{Hash, Hasher};
+    use std::rc::Rc;
+
+    #[derive(Clone, PartialEq, Eq)]
+    struct Label(u32);
+
+    // Every label hashes the same, so only comparing them tells them apart
+    impl Hash for Label {
+        fn hash<H: Hasher>(&self, _state: &mut H) {}
+    }
+
+    impl Device for Label {
+        fn type_id() -> TypeId {
+            TypeId::new(1)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "label"
+        }
+    }
+
+    struct LabelRenderer {
+        layouts: Rc<Cell<usize>>,
+    }
+
+    impl<'frm> Renderer<'frm, Vec<u32>> for LabelRenderer {
+        type Device = Label;
+        type Layout = u32;
+
+        fn layout<'thrd>(
+            &self,
+            device: Label,
+            ctx: &mut LayoutContext<'thrd, 'frm, Vec<u32>>,
+        ) -> LayoutResult<u32> {
+            self.layouts.set(self.layouts.get() + 1);
+            ctx.layout(Size::zero(), device.0)
+        }
+
+        fn render<'ctx>(
+            &self,
+            layout: u32,
+            _ctx: RenderContext<'ctx, 'frm, Vec<u32>>,
+            canvas: &mut Vec<u32>,
+        ) {
+            canvas.push(layout);
+        }
+    }
+
+    #[test]
+    fn reuse_layout() {
+        let layouts = Rc::new(Cell::new(0));
+        let renderer = LabelRenderer {
+            layouts: layouts.clone(),
+        };
+
+        let mut gui = GuiContext::default();
+        gui.register_device(Label::type_id(), Rc::new(Cached::new(renderer)));
+
+        let mut canvas = Vec::new();
+        let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+        gui.render_window(region, Label(1).move_anchor(), &mut canvas)
+            .unwrap();
+        gui.render_window(region, Label(1).move_anchor(), &mut canvas)
+            .unwrap();
+        assert_eq!(layouts.get(), 1);
+
+        gui.render_window(region, Label(2).move_anchor(), &mut canvas)
+            .unwrap();
+        assert_eq!(layouts.get(), 2);
+        assert_eq!(canvas, vec![1, 1, 2]);
+    }
+}
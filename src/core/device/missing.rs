@@ -0,0 +1,63 @@
+use crate::core::context::{LayoutContext, RenderContext};
+use crate::core::device::*;
+use crate::core::error::BuoyError;
+use crate::util::ref_move::RefMove;
+use std::marker::PhantomData;
+
+// Stands in for a device that has no renderer, when using 'MissingRendererPolicy::Placeholder'.
+// 'GuiContext::new' registers 'PlaceholderRenderer' for it, which can be replaced with another renderer.
+pub struct MissingDevice {
+    pub type_id: TypeId,
+    pub package_name: &'static str,
+    pub type_name: &'static str,
+}
+
+impl Device for MissingDevice {
+    fn type_id() -> TypeId {
+        TypeId::new(0x4018ce29_15ba_4a8a_93f9_a14263d39733)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "MissingDevice"
+    }
+}
+
+// Used in place of the renderer for a device that couldn't be allocated, like one without a renderer
+// under 'MissingRendererPolicy::Skip'. The device is dropped, along with its children.
+pub(crate) struct MissingRendererWrapper<'frm, C> {
+    _phantom: PhantomData<fn(&'frm C)>,
+}
+
+impl<'frm, C> Default for MissingRendererWrapper<'frm, C> {
+    fn default() -> Self {
+        MissingRendererWrapper {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'frm, C> RendererWrapper<'frm, C> for MissingRendererWrapper<'frm, C> {
+    fn alloc(&self, device: RefMove<dyn Device + 'frm>) -> Result<DeviceIndex, BuoyError> {
+        std::mem::drop(device);
+        Ok(DeviceIndex(0))
+    }
+
+    fn layout<'thrd>(
+        &self,
+        _device: DeviceIndex,
+        _ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> RendererLayoutResult {
+        RendererLayoutResult::None
+    }
+
+    // Never called, since there's never a layout to render
+    fn render<'ctx>(&self, _: LayoutIndex, _: RenderContext<'ctx, 'frm, C>, _: &mut C) {}
+
+    fn device_info(&self) -> DeviceInfo {
+        DeviceInfo::of::<MissingDevice>()
+    }
+}
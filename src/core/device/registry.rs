@@ -0,0 +1,116 @@
+use crate::core::device::{Device, TypeId};
+use crate::core::error::BuoyError;
+use std::collections::{hash_map::Entry, HashMap};
+use std::fmt;
+
+// Identifies a device type in diagnostics
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub type_id: TypeId,
+    pub package_name: &'static str,
+    pub type_name: &'static str,
+}
+
+impl DeviceInfo {
+    pub fn of<D: Device>() -> Self {
+        DeviceInfo {
+            type_id: D::type_id(),
+            package_name: D::package_name(),
+            type_name: D::type_name(),
+        }
+    }
+
+    pub fn of_device(device: &dyn Device) -> Self {
+        DeviceInfo {
+            type_id: device.get_type_id(),
+            package_name: device.get_package_name(),
+            type_name: device.get_type_name(),
+        }
+    }
+
+    // For a TypeId whose device type isn't known
+    pub fn unnamed(type_id: TypeId) -> Self {
+        DeviceInfo {
+            type_id,
+            package_name: "",
+            type_name: "",
+        }
+    }
+
+    pub fn is_unnamed(&self) -> bool {
+        self.package_name.is_empty() && self.type_name.is_empty()
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_unnamed() {
+            write!(f, "{}", self.type_id)
+        } else {
+            write!(
+                f,
+                "{}::{} ({})",
+                self.package_name, self.type_name, self.type_id
+            )
+        }
+    }
+}
+
+// Maps each registered TypeId back to the device type that claimed it
+#[derive(Default)]
+pub struct DeviceRegistry {
+    devices: HashMap<TypeId, DeviceInfo>,
+}
+
+impl DeviceRegistry {
+    pub fn get(&self, type_id: TypeId) -> Option<&DeviceInfo> {
+        self.devices.get(&type_id)
+    }
+
+    // Falls back to an unnamed DeviceInfo if the TypeId was never registered
+    pub fn describe(&self, type_id: TypeId) -> DeviceInfo {
+        self.get(type_id)
+            .copied()
+            .unwrap_or_else(|| DeviceInfo::unnamed(type_id))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DeviceInfo> {
+        self.devices.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    // Registering the same device type again is fine, but another type claiming its TypeId is not
+    pub fn check(&self, info: &DeviceInfo) -> Result<(), BuoyError> {
+        match self.get(info.type_id) {
+            Some(existing) if !existing.is_unnamed() && !info.is_unnamed() && existing != info => {
+                Err(BuoyError::TypeIdCollision {
+                    existing: *existing,
+                    new: *info,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn insert(&mut self, info: DeviceInfo) -> Result<(), BuoyError> {
+        self.check(&info)?;
+        match self.devices.entry(info.type_id) {
+            // Names are filled in if the TypeId was first registered without them
+            Entry::Occupied(mut entry) if entry.get().is_unnamed() => {
+                entry.insert(info);
+            }
+            Entry::Occupied(_) => (),
+            Entry::Vacant(entry) => {
+                entry.insert(info);
+            }
+        }
+        Ok(())
+    }
+}
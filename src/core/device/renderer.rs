@@ -1,14 +1,16 @@
 use crate::core::context::{LayoutContext, LayoutNode, LayoutResult, RenderContext};
-use crate::core::device::Device;
+use crate::core::device::{Device, DeviceInfo};
+use crate::core::error::BuoyError;
 use crate::util::ref_move::RefMove;
 use std::cell::RefCell;
 
 pub enum RendererLayoutResult {
     None,
-    // TODO: Deferred
+    Deferred,
     Complete(LayoutNode),
 }
 
+#[derive(Clone, Copy)]
 pub struct DeviceIndex(pub usize);
 pub struct LayoutIndex(pub usize);
 
@@ -17,32 +19,36 @@ pub trait Renderer<'frm, C>: Sized {
     type Layout: 'frm;
 
     // Begins the layout process for a device previously allocated in this renderer with 'alloc'.
-    // The renderer may open sockets or subcontexts, and calling ctx.defer() will block until all
-    // dependencies are met, then this function will be run again.
-    // TODO: Implement ctx.defer()
-    // TODO: Maybe allow this to specify which things its deferring on/how?
+    // The renderer may open sockets or subcontexts, and returning ctx.defer(device) will block until all
+    // dependencies (messages read with ctx.poll_message(), and any children that deferred) are met,
+    // then this function will be run again.
     fn layout<'thrd>(
         &self,
         device: Self::Device,
-        ctx: LayoutContext<'thrd, 'frm, C>,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
     ) -> LayoutResult<Self::Layout>;
 
     fn render<'ctx>(&self, layout: Self::Layout, ctx: RenderContext<'ctx, 'frm, C>, canvas: &mut C);
 }
 
 pub trait RendererWrapper<'frm, C> {
-    fn alloc(&self, device: RefMove<dyn Device + 'frm>) -> DeviceIndex;
+    // Fails (dropping the device) if the device isn't the type this renders
+    fn alloc(&self, device: RefMove<dyn Device + 'frm>) -> Result<DeviceIndex, BuoyError>;
 
     fn layout<'thrd>(
         &self,
         device: DeviceIndex,
-        ctx: LayoutContext<'thrd, 'frm, C>,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
     ) -> RendererLayoutResult;
 
     fn render<'ctx>(&self, layout: LayoutIndex, ctx: RenderContext<'ctx, 'frm, C>, canvas: &mut C);
+
+    // The device type whose layouts this renders
+    fn device_info(&self) -> DeviceInfo;
 }
 
 pub trait IntoRenderer<C: 'static> {
+    #[allow(clippy::wrong_self_convention)]
     fn into_renderer<'frm>(&'frm self) -> Box<dyn RendererWrapper<'frm, C> + 'frm>;
 }
 
@@ -59,6 +65,29 @@ where
     }
 }
 
+// A renderer that layout threads can run too (see 'GuiContext::register_shared'). Any renderer that can
+// be shared between threads, and whose devices and layouts can be sent between them, is one.
+pub trait IntoSharedRenderer<C: 'static>: Send + Sync {
+    #[allow(clippy::wrong_self_convention)]
+    fn into_shared_renderer<'frm>(&'frm self) -> Box<dyn RendererWrapper<'frm, C> + Send + 'frm>;
+}
+
+impl<C: 'static, T> IntoSharedRenderer<C> for T
+where
+    T: Send + Sync,
+    for<'frm> T: Renderer<'frm, C>,
+    for<'frm> <T as Renderer<'frm, C>>::Device: Send,
+    for<'frm> <T as Renderer<'frm, C>>::Layout: Send,
+{
+    fn into_shared_renderer<'frm>(&'frm self) -> Box<dyn RendererWrapper<'frm, C> + Send + 'frm> {
+        Box::new(RendererWrapperImpl {
+            renderer: self,
+            devices: Default::default(),
+            layouts: Default::default(),
+        })
+    }
+}
+
 struct RendererWrapperImpl<'frm, C: 'static, T: Renderer<'frm, C>> {
     renderer: &'frm T,
     devices: RefCell<Vec<Option<T::Device>>>,
@@ -66,20 +95,30 @@ struct RendererWrapperImpl<'frm, C: 'static, T: Renderer<'frm, C>> {
 }
 
 impl<'frm, C, T: Renderer<'frm, C>> RendererWrapper<'frm, C> for RendererWrapperImpl<'frm, C, T> {
-    fn alloc(&self, device: RefMove<dyn Device + 'frm>) -> DeviceIndex {
-        assert_eq!(device.get_type_id(), T::Device::type_id());
+    fn alloc(&self, device: RefMove<dyn Device + 'frm>) -> Result<DeviceIndex, BuoyError> {
+        // Another device type using the same TypeId would be downcast into the wrong type
+        let (expected, actual) = (
+            DeviceInfo::of::<T::Device>(),
+            DeviceInfo::of_device(&*device),
+        );
+        if expected != actual {
+            return Err(BuoyError::TypeIdCollision {
+                existing: expected,
+                new: actual,
+            });
+        }
         let device = unsafe { RefMove::downcast_unchecked::<T::Device>(device).take() };
 
         let mut devices = self.devices.borrow_mut();
         devices.push(Some(device));
 
-        DeviceIndex(devices.len() - 1)
+        Ok(DeviceIndex(devices.len() - 1))
     }
 
     fn layout<'ctx, 'thrd>(
         &self,
         device: DeviceIndex,
-        ctx: LayoutContext<'thrd, 'frm, C>,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
     ) -> RendererLayoutResult {
         let dev = self
             .devices
@@ -91,6 +130,7 @@ impl<'frm, C, T: Renderer<'frm, C>> RendererWrapper<'frm, C> for RendererWrapper
 
         let (min_size, layout) = match self.renderer.layout(dev, ctx) {
             LayoutResult::None => return RendererLayoutResult::None,
+            LayoutResult::Deferred => return RendererLayoutResult::Deferred,
             LayoutResult::Complete { min_size, layout } => (min_size, layout),
             LayoutResult::CompleteNode(node) => return RendererLayoutResult::Complete(node),
         };
@@ -103,6 +143,7 @@ impl<'frm, C, T: Renderer<'frm, C>> RendererWrapper<'frm, C> for RendererWrapper
             min_size,
             type_id: T::Device::type_id(),
             index: layout_index,
+            thread: None,
         })
     }
 
@@ -116,4 +157,8 @@ impl<'frm, C, T: Renderer<'frm, C>> RendererWrapper<'frm, C> for RendererWrapper
             .unwrap();
         self.renderer.render(layout, ctx, canvas);
     }
+
+    fn device_info(&self) -> DeviceInfo {
+        DeviceInfo::of::<T::Device>()
+    }
 }
@@ -1,25 +1,86 @@
-use std::fmt;
-
-// Generated as a v4 uuid. Usually precomputed.
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
-pub struct TypeId {
-    id: u128,
-}
-
-impl TypeId {
-    pub const fn new(id: u128) -> Self {
-        TypeId { id }
-    }
-}
-
-impl fmt::Display for TypeId {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.id)
-    }
-}
-
-impl fmt::Debug for TypeId {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        <Self as fmt::Display>::fmt(self, f)
-    }
-}
+use crate::core::error::BuoyError;
+use std::fmt;
+use std::str::FromStr;
+
+// Generated as a v4 uuid. Usually precomputed.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TypeId {
+    id: u128,
+}
+
+impl TypeId {
+    pub const fn new(id: u128) -> Self {
+        TypeId { id }
+    }
+
+    pub const fn as_u128(self) -> u128 {
+        self.id
+    }
+
+    // Parses a uuid in the hyphenated form it's displayed in (xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx)
+    pub fn parse(uuid: &str) -> Result<Self, BuoyError> {
+        let invalid = || BuoyError::InvalidTypeId(uuid.to_string());
+
+        let groups: Vec<&str> = uuid.split('-').collect();
+        let lengths: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+        if lengths != [8, 4, 4, 4, 12] {
+            return Err(invalid());
+        }
+
+        let mut id = 0_u128;
+        for c in groups.concat().chars() {
+            id = (id << 4) | c.to_digit(16).ok_or_else(invalid)? as u128;
+        }
+
+        Ok(TypeId::new(id))
+    }
+}
+
+impl FromStr for TypeId {
+    type Err = BuoyError;
+
+    fn from_str(s: &str) -> Result<Self, BuoyError> {
+        TypeId::parse(s)
+    }
+}
+
+impl fmt::Display for TypeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = self.id;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            id >> 96,
+            (id >> 80) & 0xffff,
+            (id >> 64) & 0xffff,
+            (id >> 48) & 0xffff,
+            id & 0xffff_ffff_ffff
+        )
+    }
+}
+
+impl fmt::Debug for TypeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <Self as fmt::Display>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypeId;
+
+    #[test]
+    fn display_and_parse() {
+        let type_id = TypeId::new(0x4018ce29_15ba_4a8a_93f9_a14263d39733);
+        assert_eq!(type_id.to_string(), "4018ce29-15ba-4a8a-93f9-a14263d39733");
+        assert_eq!(
+            TypeId::new(1).to_string(),
+            "00000000-0000-0000-0000-000000000001"
+        );
+
+        assert_eq!(TypeId::parse(&type_id.to_string()), Ok(type_id));
+        assert_eq!("4018CE29-15BA-4A8A-93F9-A14263D39733".parse(), Ok(type_id));
+        assert!(TypeId::parse("4018ce2915ba4a8a93f9a14263d39733").is_err());
+        assert!(TypeId::parse("4018ce29-15ba-4a8a-93f9-a14263d3973x").is_err());
+    }
+}
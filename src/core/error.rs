@@ -0,0 +1,74 @@
+use crate::core::device::{DeviceInfo, SocketName};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuoyError {
+    // A renderer was registered for a TypeId that already had one
+    DuplicateDevice(DeviceInfo),
+    // A device was used that has no renderer registered for its TypeId
+    MissingRenderer(DeviceInfo),
+    // Two different device types claim the same TypeId
+    TypeIdCollision {
+        existing: DeviceInfo,
+        new: DeviceInfo,
+    },
+    // A string couldn't be parsed as a TypeId
+    InvalidTypeId(String),
+    // A type was registered for saving under a name (or with a type) that's already registered
+    DuplicatePersistentType(&'static str),
+    // A renderer returned 'LayoutResult::Deferred' without handing its device back with 'defer'
+    MissingDefer(DeviceInfo),
+    // A device was laid out on a layout thread, but its renderer was registered with 'register' rather
+    // than 'register_shared', so it can only run on the thread rendering the window
+    LocalRenderer(DeviceInfo),
+    // A device was given more than one socket with the same name
+    DuplicateSocket {
+        device: DeviceInfo,
+        socket: SocketName,
+    },
+}
+
+impl fmt::Display for BuoyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuoyError::DuplicateDevice(device) => {
+                write!(f, "A renderer is already registered for {}", device)
+            }
+            BuoyError::MissingRenderer(device) => {
+                write!(f, "No renderer registered for {}", device)
+            }
+            BuoyError::TypeIdCollision { existing, new } => write!(
+                f,
+                "{}::{} uses the same TypeId as {}",
+                new.package_name, new.type_name, existing
+            ),
+            BuoyError::InvalidTypeId(uuid) => {
+                write!(f, "'{}' is not a hyphenated uuid", uuid)
+            }
+            BuoyError::DuplicatePersistentType(name) => {
+                write!(
+                    f,
+                    "'{}' (or its type) is already registered for saving",
+                    name
+                )
+            }
+            BuoyError::MissingDefer(device) => write!(
+                f,
+                "The renderer for {} deferred without calling 'LayoutContext::defer'",
+                device
+            ),
+            BuoyError::LocalRenderer(device) => write!(
+                f,
+                "The renderer for {} can't run on a layout thread, since it wasn't registered with \
+                 'register_shared'",
+                device
+            ),
+            BuoyError::DuplicateSocket { device, socket } => {
+                write!(f, "{} has more than one {:?}", device, socket)
+            }
+        }
+    }
+}
+
+impl Error for BuoyError {}
@@ -13,6 +13,15 @@ impl Default for Id {
 }
 
 impl Id {
+    // The hash itself, for saving Ids to a file
+    pub(in crate::core) fn to_bits(self) -> u64 {
+        self.0
+    }
+
+    pub(in crate::core) fn from_bits(bits: u64) -> Self {
+        Id(bits)
+    }
+
     pub fn append<T: Into<Id>>(self, id: T) -> Self {
         let mut hasher = FnvHasher::with_key(self.0);
         id.into().hash(&mut hasher);
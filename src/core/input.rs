@@ -0,0 +1,690 @@
+use crate::core::context::{Layer, RenderOutput};
+use crate::core::id::Id;
+use crate::core::message::{Message, MessageMap, Outbox};
+use crate::space::{Point, Region, Vector};
+use std::collections::HashMap;
+
+mod focus;
+mod keyboard;
+pub(in crate::core) use self::focus::FocusTarget;
+pub use self::focus::{focused_id, scroll_into_view_id, FocusMove};
+pub use self::keyboard::{
+    global_keyboard_id, keyboard_id, Key, KeyEvent, KeyboardInput, Modifiers, Preedit,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PointerEvent {
+    Enter,
+    Leave,
+    Move(Point),
+    Press(MouseButton),
+    // Sent to whatever the button was pressed over, wherever the pointer is when it's released
+    Release(MouseButton),
+    // Follows a release, if the pointer is still over what the button was pressed over
+    Click(MouseButton),
+    Wheel(Vector),
+}
+
+// What the pointer did to an interactive region since the last frame. Sent to 'pointer_id(id)' for
+// every Id that was hovered, held or had any events, so reading None means nothing happened.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PointerInput {
+    // Where the pointer is, if it's over the window
+    pub position: Option<Point>,
+    pub hovered: bool,
+    // Buttons pressed over this Id that haven't been released yet
+    pub held: Vec<MouseButton>,
+    // Everything that happened since the last frame, in order
+    pub events: Vec<PointerEvent>,
+}
+
+impl PointerInput {
+    pub fn pressed(&self, button: MouseButton) -> bool {
+        self.events.contains(&PointerEvent::Press(button))
+    }
+
+    pub fn released(&self, button: MouseButton) -> bool {
+        self.events.contains(&PointerEvent::Release(button))
+    }
+
+    pub fn clicked(&self, button: MouseButton) -> bool {
+        self.events.contains(&PointerEvent::Click(button))
+    }
+
+    pub fn wheel(&self) -> Vector {
+        self.events
+            .iter()
+            .fold(Vector::zero(), |total, event| match event {
+                PointerEvent::Wheel(delta) => total + *delta,
+                _ => total,
+            })
+    }
+}
+
+// Where the pointer input for an interactive region is sent
+pub fn pointer_id(id: Id) -> Id {
+    id.append("pointer")
+}
+
+// An interactive region registered while rendering. Later ones on the same layer were rendered on top
+// of earlier ones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(in crate::core) struct HitRegion {
+    pub id: Id,
+    pub region: Region,
+    pub layer: Layer,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum RawEvent {
+    Move(Point),
+    Leave,
+    Down(MouseButton),
+    Up(MouseButton),
+    Wheel(Vector),
+    MoveFocus(FocusMove),
+    Focus(Option<Id>),
+    KeyDown(Key, Modifiers, bool),
+    KeyUp(Key, Modifiers),
+    Text(String),
+    Preedit(Preedit),
+    Commit(String),
+    Cancel,
+}
+
+// The pointer, focus and keyboard state between frames. Events from the window are queued up, then
+// resolved against the regions from the frame the user was looking at when the next frame starts.
+#[derive(Default)]
+pub(in crate::core) struct InputState {
+    position: Option<Point>,
+    queue: Vec<RawEvent>,
+    // Sorted from bottom to top
+    regions: Vec<HitRegion>,
+    hovered: Option<Id>,
+    // Which button was pressed over which Id, in the order they were pressed
+    captures: Vec<(Id, MouseButton)>,
+    focusable: Vec<FocusTarget>,
+    focused: Option<Id>,
+    modifiers: Modifiers,
+    // The input method's composition for the focused Id, if one is in progress
+    preedit: Option<Preedit>,
+}
+
+impl InputState {
+    pub fn pointer_move(&mut self, position: Point) {
+        self.queue.push(RawEvent::Move(position));
+    }
+
+    pub fn pointer_leave(&mut self) {
+        self.queue.push(RawEvent::Leave);
+    }
+
+    pub fn pointer_down(&mut self, button: MouseButton) {
+        self.queue.push(RawEvent::Down(button));
+    }
+
+    pub fn pointer_up(&mut self, button: MouseButton) {
+        self.queue.push(RawEvent::Up(button));
+    }
+
+    pub fn wheel(&mut self, delta: Vector) {
+        self.queue.push(RawEvent::Wheel(delta));
+    }
+
+    pub fn move_focus(&mut self, movement: FocusMove) {
+        self.queue.push(RawEvent::MoveFocus(movement));
+    }
+
+    pub fn focus(&mut self, id: Option<Id>) {
+        self.queue.push(RawEvent::Focus(id));
+    }
+
+    pub fn focused(&self) -> Option<Id> {
+        self.focused
+    }
+
+    pub fn key_down(&mut self, key: Key, modifiers: Modifiers, repeat: bool) {
+        self.queue.push(RawEvent::KeyDown(key, modifiers, repeat));
+    }
+
+    pub fn key_up(&mut self, key: Key, modifiers: Modifiers) {
+        self.queue.push(RawEvent::KeyUp(key, modifiers));
+    }
+
+    pub fn text_input(&mut self, text: String) {
+        self.queue.push(RawEvent::Text(text));
+    }
+
+    pub fn ime_preedit(&mut self, preedit: Preedit) {
+        self.queue.push(RawEvent::Preedit(preedit));
+    }
+
+    pub fn ime_commit(&mut self, text: String) {
+        self.queue.push(RawEvent::Commit(text));
+    }
+
+    pub fn ime_cancel(&mut self) {
+        self.queue.push(RawEvent::Cancel);
+    }
+
+    // Takes the regions registered while rendering a frame, for the next frame's input to use
+    pub fn set_output(&mut self, output: RenderOutput) {
+        self.set_regions(output.hits);
+        self.focusable = output.focusable;
+    }
+
+    fn set_regions(&mut self, mut regions: Vec<HitRegion>) {
+        // The sort is stable, so regions on the same layer stay in the order they were registered
+        regions.sort_by_key(|hit| hit.layer);
+        self.regions = regions;
+    }
+
+    // The topmost region under the point, which occludes everything below it
+    fn hit(&self, position: Option<Point>) -> Option<Id> {
+        let position = position?;
+        self.regions
+            .iter()
+            .rev()
+            .find(|hit| hit.region.contains(position))
+            .map(|hit| hit.id)
+    }
+
+    // Moves the hover to whatever is under the pointer now, sending enter and leave events
+    fn update_hover(&mut self, inputs: &mut HashMap<Id, PointerInput>) {
+        let hovered = self.hit(self.position);
+        if hovered == self.hovered {
+            return;
+        }
+
+        if let Some(id) = self.hovered {
+            push_event(inputs, id, PointerEvent::Leave);
+        }
+        if let Some(id) = hovered {
+            push_event(inputs, id, PointerEvent::Enter);
+        }
+        self.hovered = hovered;
+    }
+
+    // Moves the focus, ending any composition the input method had going for the old focus (the
+    // window is expected to cancel it as well)
+    fn set_focus(&mut self, id: Option<Id>, keys: &mut HashMap<Id, KeyboardInput>) {
+        if id == self.focused {
+            return;
+        }
+        if let (Some(focused), Some(_)) = (self.focused, self.preedit.take()) {
+            push_key(keys, keyboard_id(focused), KeyEvent::CompositionEnd);
+        }
+        self.focused = id;
+    }
+
+    // Sends a key event to the focused Id (if there is one) and to everything listening for shortcuts
+    fn send_key(&self, keys: &mut HashMap<Id, KeyboardInput>, event: KeyEvent) {
+        if let Some(id) = self.focused {
+            push_key(keys, keyboard_id(id), event.clone());
+        }
+        push_key(keys, global_keyboard_id(), event);
+    }
+
+    // Resolves the queued events, writing the input for each Id that should receive any
+    pub fn write_messages(&mut self, messages: &mut MessageMap) {
+        let mut inputs = HashMap::new();
+        let mut keys = HashMap::new();
+        let mut scroll_into_view = None;
+
+        // Things may have moved under the pointer since the last frame, even if the pointer didn't
+        self.update_hover(&mut inputs);
+
+        for event in std::mem::take(&mut self.queue) {
+            match event {
+                RawEvent::Move(position) => {
+                    self.position = Some(position);
+                    self.update_hover(&mut inputs);
+
+                    let mut ids: Vec<Id> = self.hovered.into_iter().collect();
+                    for (id, _) in &self.captures {
+                        if !ids.contains(id) {
+                            ids.push(*id);
+                        }
+                    }
+                    for id in ids {
+                        push_event(&mut inputs, id, PointerEvent::Move(position));
+                    }
+                }
+                RawEvent::Leave => {
+                    self.position = None;
+                    self.update_hover(&mut inputs);
+                }
+                RawEvent::Down(button) => {
+                    if let Some(id) = self.hovered {
+                        push_event(&mut inputs, id, PointerEvent::Press(button));
+                        self.captures.push((id, button));
+
+                        // It's already in view, since it was just clicked
+                        if self.focusable.iter().any(|target| target.id == id) {
+                            self.set_focus(Some(id), &mut keys);
+                        }
+                    }
+                }
+                RawEvent::Up(button) => {
+                    let (released, held) = self.captures.iter().partition(|(_, b)| *b == button);
+                    self.captures = held;
+
+                    for (id, _) in released {
+                        push_event(&mut inputs, id, PointerEvent::Release(button));
+                        if self.hovered == Some(id) {
+                            push_event(&mut inputs, id, PointerEvent::Click(button));
+                        }
+                    }
+                }
+                RawEvent::Wheel(delta) => {
+                    if let Some(id) = self.hovered {
+                        push_event(&mut inputs, id, PointerEvent::Wheel(delta));
+                    }
+                }
+                RawEvent::MoveFocus(movement) => {
+                    if let Some(target) = focus::navigate(&self.focusable, self.focused, movement) {
+                        self.set_focus(Some(target.id), &mut keys);
+                        scroll_into_view = Some(target.region);
+                    }
+                }
+                RawEvent::Focus(id) => {
+                    self.set_focus(id, &mut keys);
+                    scroll_into_view = self
+                        .focusable
+                        .iter()
+                        .find(|target| Some(target.id) == id)
+                        .map(|target| target.region);
+                }
+                RawEvent::KeyDown(key, modifiers, repeat) => {
+                    self.modifiers = modifiers;
+
+                    // Tab (and Shift+Tab) moves the focus rather than going to what's focused
+                    let shortcut = modifiers.ctrl || modifiers.alt || modifiers.meta;
+                    if key == Key::Tab && !shortcut {
+                        let movement = match modifiers.shift {
+                            true => FocusMove::Previous,
+                            false => FocusMove::Next,
+                        };
+                        let target = focus::navigate(&self.focusable, self.focused, movement);
+                        if let Some(target) = target {
+                            self.set_focus(Some(target.id), &mut keys);
+                            scroll_into_view = Some(target.region);
+                        }
+                        continue;
+                    }
+
+                    let event = KeyEvent::Down {
+                        key,
+                        modifiers,
+                        repeat,
+                    };
+                    self.send_key(&mut keys, event);
+                }
+                RawEvent::KeyUp(key, modifiers) => {
+                    self.modifiers = modifiers;
+                    self.send_key(&mut keys, KeyEvent::Up { key, modifiers });
+                }
+                RawEvent::Text(text) => {
+                    if !text.is_empty() {
+                        self.send_key(&mut keys, KeyEvent::Text(text));
+                    }
+                }
+                RawEvent::Preedit(preedit) => {
+                    // Compositions only make sense for whatever they'll be inserted into
+                    if let Some(id) = self.focused {
+                        let event = KeyEvent::Composition(preedit.clone());
+                        push_key(&mut keys, keyboard_id(id), event);
+                        self.preedit = Some(preedit);
+                    }
+                }
+                RawEvent::Commit(text) => {
+                    if let (Some(id), Some(_)) = (self.focused, self.preedit.take()) {
+                        push_key(&mut keys, keyboard_id(id), KeyEvent::CompositionEnd);
+                    }
+                    if !text.is_empty() {
+                        self.send_key(&mut keys, KeyEvent::Text(text));
+                    }
+                }
+                RawEvent::Cancel => {
+                    if let (Some(id), Some(_)) = (self.focused, self.preedit.take()) {
+                        push_key(&mut keys, keyboard_id(id), KeyEvent::CompositionEnd);
+                    }
+                }
+            }
+        }
+
+        if let Some(id) = self.hovered {
+            inputs.entry(id).or_default().hovered = true;
+        }
+        for (id, button) in &self.captures {
+            inputs.entry(*id).or_default().held.push(*button);
+        }
+        for (id, mut input) in inputs {
+            input.position = self.position;
+            write(messages, pointer_id(id), input);
+        }
+
+        // The composition is resent every frame until it ends, so it can be drawn
+        if let (Some(id), Some(preedit)) = (self.focused, &self.preedit) {
+            keys.entry(keyboard_id(id)).or_default().preedit = Some(preedit.clone());
+        }
+        for (id, mut input) in keys {
+            input.modifiers = self.modifiers;
+            write(messages, id, input);
+        }
+
+        if let Some(id) = self.focused {
+            write(messages, focused_id(), id);
+        }
+        if let Some(region) = scroll_into_view {
+            write(messages, scroll_into_view_id(), region);
+        }
+    }
+}
+
+fn push_event(inputs: &mut HashMap<Id, PointerInput>, id: Id, event: PointerEvent) {
+    inputs.entry(id).or_default().events.push(event);
+}
+
+fn push_key(keys: &mut HashMap<Id, KeyboardInput>, id: Id, event: KeyEvent) {
+    keys.entry(id).or_default().events.push(event);
+}
+
+fn write<T: Message>(messages: &mut MessageMap, id: Id, value: T) {
+    // Outboxes need to be observed for their messages to be kept
+    let outbox = Outbox::new(id);
+    outbox.inbox();
+    messages.write(outbox, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::util::ref_move::Ext;
+    use std::rc::Rc;
+
+    type Canvas = Vec<(&'static str, Option<PointerInput>)>;
+
+    // Two overlapping buttons, reporting the input they got this frame
+    struct Buttons;
+    struct ButtonsRenderer;
+
+    const BUTTONS: [(&str, f32); 2] = [("back", 0_f32), ("front", 5_f32)];
+
+    impl Device for Buttons {
+        fn type_id() -> TypeId {
+            TypeId::new(0x34cf38aa_9655_42e5_a958_ea635ff28a59)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Buttons"
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for ButtonsRenderer {
+        type Device = Buttons;
+        type Layout = Canvas;
+
+        fn layout<'thrd>(
+            &self,
+            _device: Buttons,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<Canvas> {
+            let inputs = BUTTONS
+                .iter()
+                .map(|(name, _)| {
+                    let inbox = ctx.message(pointer_id(Id::from(*name))).inbox();
+                    (*name, ctx.read_message(inbox))
+                })
+                .collect();
+            ctx.layout(Size::zero(), inputs)
+        }
+
+        fn render<'ctx>(
+            &self,
+            inputs: Canvas,
+            ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            for (name, offset) in BUTTONS.iter() {
+                let pos = Point::new(*offset, *offset);
+                ctx.hit_region(Id::from(*name), Region::new(pos, Size::new(10_f32, 10_f32)));
+            }
+            canvas.extend(inputs);
+        }
+    }
+
+    fn frame(gui: &mut GuiContext<Canvas>) -> Canvas {
+        let mut canvas = Canvas::new();
+        let window = Region::new(Point::zero(), Size::new(20_f32, 20_f32));
+        gui.render_window(window, Buttons.move_anchor(), &mut canvas)
+            .unwrap();
+        canvas
+    }
+
+    #[test]
+    fn pointer() {
+        let mut gui = GuiContext::default();
+        gui.register::<Buttons>(Rc::new(ButtonsRenderer));
+
+        // Nothing is interactive until the first frame has been rendered
+        gui.pointer_move(Point::new(2_f32, 2_f32));
+        assert_eq!(frame(&mut gui), vec![("back", None), ("front", None)]);
+
+        let hovered = |position: Point, events: Vec<PointerEvent>| PointerInput {
+            position: Some(position),
+            hovered: true,
+            held: Vec::new(),
+            events,
+        };
+
+        // The pointer was already there, so the regions appearing under it counts as entering them
+        let pos = Point::new(2_f32, 2_f32);
+        assert_eq!(
+            frame(&mut gui),
+            vec![
+                ("back", Some(hovered(pos, vec![PointerEvent::Enter]))),
+                ("front", None)
+            ]
+        );
+
+        // The button keeps the press until it's released, even once the pointer leaves it
+        gui.pointer_down(MouseButton::Left);
+        assert_eq!(
+            frame(&mut gui),
+            vec![
+                (
+                    "back",
+                    Some(PointerInput {
+                        held: vec![MouseButton::Left],
+                        ..hovered(pos, vec![PointerEvent::Press(MouseButton::Left)])
+                    })
+                ),
+                ("front", None)
+            ]
+        );
+
+        // The front button covers the back one, so only it is hovered. Releasing away from the back
+        // button doesn't click it.
+        let pos = Point::new(7_f32, 7_f32);
+        gui.pointer_move(pos);
+        gui.pointer_up(MouseButton::Left);
+        let released = PointerInput {
+            hovered: false,
+            ..hovered(
+                pos,
+                vec![
+                    PointerEvent::Leave,
+                    PointerEvent::Move(pos),
+                    PointerEvent::Release(MouseButton::Left),
+                ],
+            )
+        };
+        assert_eq!(
+            frame(&mut gui),
+            vec![
+                ("back", Some(released)),
+                (
+                    "front",
+                    Some(hovered(
+                        pos,
+                        vec![PointerEvent::Enter, PointerEvent::Move(pos)]
+                    ))
+                ),
+            ]
+        );
+
+        // Clicks don't fall through to the button underneath
+        gui.pointer_down(MouseButton::Left);
+        gui.pointer_up(MouseButton::Left);
+        let inputs = frame(&mut gui);
+        assert_eq!(inputs[0].1, None);
+        let front = inputs[1].1.clone().unwrap();
+        assert!(front.pressed(MouseButton::Left) && front.clicked(MouseButton::Left));
+
+        gui.wheel(Vector::new(0_f32, 3_f32));
+        gui.wheel(Vector::new(0_f32, 2_f32));
+        let inputs = frame(&mut gui);
+        assert_eq!(inputs[0].1, None);
+        assert_eq!(
+            inputs[1].1.clone().unwrap().wheel(),
+            Vector::new(0_f32, 5_f32)
+        );
+    }
+
+    #[test]
+    fn layers() {
+        let region = Region::new(Point::zero(), Size::new(10_f32, 10_f32));
+        let hit = |id: &str, layer: Layer| HitRegion {
+            id: Id::from(id),
+            region,
+            layer,
+        };
+
+        // The popup was registered first, but it's on a higher layer so it's on top
+        let mut input = InputState::default();
+        input.set_regions(vec![
+            hit("popup", Layer::POPUP),
+            hit("back", Layer::BASE),
+            hit("front", Layer::BASE),
+        ]);
+        assert_eq!(
+            input.hit(Some(Point::new(5_f32, 5_f32))),
+            Some(Id::from("popup"))
+        );
+
+        input.set_regions(vec![hit("back", Layer::BASE), hit("front", Layer::BASE)]);
+        assert_eq!(
+            input.hit(Some(Point::new(5_f32, 5_f32))),
+            Some(Id::from("front"))
+        );
+        assert_eq!(input.hit(None), None);
+    }
+
+    #[test]
+    fn keyboard() {
+        let target = |id: &str, x: f32| FocusTarget {
+            id: Id::from(id),
+            region: Region::new(Point::new(x, 0_f32), Size::new(10_f32, 10_f32)),
+            layer: Layer::BASE,
+            order: None,
+        };
+        let mut input = InputState::default();
+        input.set_output(RenderOutput {
+            hits: Vec::new(),
+            focusable: vec![target("first", 0_f32), target("second", 20_f32)],
+        });
+
+        let frame = |input: &mut InputState, id: Id| {
+            let mut messages = MessageMap::default();
+            input.write_messages(&mut messages);
+            let read = |id: Id| messages.read(Outbox::<KeyboardInput>::new(id).inbox());
+            (read(keyboard_id(id)), read(global_keyboard_id()))
+        };
+        let first = Id::from("first");
+        let second = Id::from("second");
+
+        // Without focus, keys only go to the shortcuts
+        let a = Key::Character('a');
+        input.key_down(a, Modifiers::CTRL, false);
+        let (focused, global) = frame(&mut input, first);
+        assert_eq!(focused, None);
+        assert!(global.unwrap().pressed(a, Modifiers::CTRL));
+
+        // Tab moves the focus, and isn't sent anywhere
+        input.key_down(Key::Tab, Modifiers::NONE, false);
+        assert_eq!(frame(&mut input, first), (None, None));
+        assert_eq!(input.focused(), Some(first));
+
+        input.key_down(a, Modifiers::SHIFT, false);
+        input.key_down(a, Modifiers::SHIFT, true);
+        input.text_input("AA".to_string());
+        input.key_up(a, Modifiers::NONE);
+        let (focused, global) = frame(&mut input, first);
+        let focused = focused.unwrap();
+        assert_eq!(focused, global.unwrap());
+        assert_eq!(focused.text(), "AA");
+        assert_eq!(focused.modifiers, Modifiers::NONE);
+        assert_eq!(
+            focused.events[1],
+            KeyEvent::Down {
+                key: a,
+                modifiers: Modifiers::SHIFT,
+                repeat: true
+            }
+        );
+
+        // The composition is sent every frame until it's committed, and only to the focus
+        let preedit = Preedit {
+            text: "に".to_string(),
+            cursor: Some((3, 3)),
+        };
+        input.ime_preedit(preedit.clone());
+        let (focused, global) = frame(&mut input, first);
+        let focused = focused.unwrap();
+        assert_eq!(focused.events, vec![KeyEvent::Composition(preedit.clone())]);
+        assert_eq!(focused.preedit, Some(preedit.clone()));
+        assert_eq!(global, None);
+        assert_eq!(frame(&mut input, first).0.unwrap().preedit, Some(preedit));
+
+        input.ime_commit("日".to_string());
+        let (focused, global) = frame(&mut input, first);
+        let focused = focused.unwrap();
+        assert_eq!(focused.preedit, None);
+        assert_eq!(
+            focused.events,
+            vec![KeyEvent::CompositionEnd, KeyEvent::Text("日".to_string())]
+        );
+        assert_eq!(global.unwrap().text(), "日");
+        assert_eq!(frame(&mut input, first), (None, None));
+
+        // Moving the focus ends the composition
+        input.ime_preedit(Preedit::default());
+        input.key_down(Key::Tab, Modifiers::NONE, false);
+        input.text_input("b".to_string());
+        let (focused, _) = frame(&mut input, first);
+        assert_eq!(
+            focused.unwrap().events,
+            vec![
+                KeyEvent::Composition(Preedit::default()),
+                KeyEvent::CompositionEnd
+            ]
+        );
+        assert_eq!(input.focused(), Some(second));
+        input.text_input("c".to_string());
+        assert_eq!(frame(&mut input, second).0.unwrap().text(), "c");
+    }
+}
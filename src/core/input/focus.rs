@@ -0,0 +1,197 @@
+use crate::core::context::Layer;
+use crate::core::id::Id;
+use crate::space::{Point, Region};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FocusMove {
+    // Tab and Shift+Tab
+    Next,
+    Previous,
+    // The arrow keys, moving to the nearest focusable region in that direction
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// Where the focused Id (if there is one) is sent every frame. Also read by 'FrameContext::focused'.
+pub fn focused_id() -> Id {
+    Id::from("buoy::focused")
+}
+
+// Where the region of something that was just focused by the keyboard (or 'GuiContext::focus') is sent,
+// so scroll viewports can bring it into view. It's where the region was in the previous frame.
+pub fn scroll_into_view_id() -> Id {
+    Id::from("buoy::scroll_into_view")
+}
+
+// A focusable region registered while rendering
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(in crate::core) struct FocusTarget {
+    pub id: Id,
+    pub region: Region,
+    pub layer: Layer,
+    pub order: Option<i32>,
+}
+
+// The order Tab moves through the targets in: those with an explicit order first (lowest first), then
+// the rest in the order they were rendered. Only the highest layer with any targets takes part, so
+// focus stays inside a popup while it's open.
+fn focus_order(targets: &[FocusTarget]) -> Vec<FocusTarget> {
+    let top = targets.iter().map(|target| target.layer).max();
+
+    let mut order: Vec<FocusTarget> = Vec::new();
+    for target in targets {
+        if Some(target.layer) == top && !order.iter().any(|t| t.id == target.id) {
+            order.push(*target);
+        }
+    }
+
+    // The sort is stable, so targets without an explicit order stay in render order
+    order.sort_by_key(|target| target.order.unwrap_or(i32::MAX));
+    order
+}
+
+fn center(region: Region) -> Point {
+    Point::new(
+        region.pos.x + region.size.width / 2_f32,
+        region.pos.y + region.size.height / 2_f32,
+    )
+}
+
+// Finds where focus should move to from the focused Id, if anywhere
+pub(in crate::core) fn navigate(
+    targets: &[FocusTarget],
+    focused: Option<Id>,
+    movement: FocusMove,
+) -> Option<FocusTarget> {
+    let order = focus_order(targets);
+    let len = order.len();
+    let current = focused.and_then(|id| order.iter().position(|target| target.id == id));
+
+    let current = match (movement, current) {
+        (FocusMove::Next, Some(i)) => return order.get((i + 1) % len).copied(),
+        (FocusMove::Previous, Some(i)) => return order.get((i + len - 1) % len).copied(),
+        (FocusMove::Previous, None) => return order.last().copied(),
+        // Arrows start from the first target when nothing is focused, like Tab does
+        (_, None) => return order.first().copied(),
+        (_, Some(i)) => order[i],
+    };
+
+    // Prefer targets straight ahead, over ones that are closer but off to the side
+    let from = center(current.region);
+    let score = |target: &FocusTarget| {
+        let to = center(target.region);
+        let (along, across) = match movement {
+            FocusMove::Right => (to.x - from.x, to.y - from.y),
+            FocusMove::Left => (from.x - to.x, to.y - from.y),
+            FocusMove::Down => (to.y - from.y, to.x - from.x),
+            _ => (from.y - to.y, to.x - from.x),
+        };
+        if along > 0_f32 {
+            Some(along + across.abs() * 2_f32)
+        } else {
+            None
+        }
+    };
+
+    order
+        .iter()
+        .filter_map(|target| score(target).map(|score| (score, target)))
+        .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, target)| *target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::space::Size;
+
+    fn target(id: &str, x: f32, y: f32, order: Option<i32>) -> FocusTarget {
+        FocusTarget {
+            id: Id::from(id),
+            region: Region::new(Point::new(x, y), Size::new(10_f32, 10_f32)),
+            layer: Layer::BASE,
+            order,
+        }
+    }
+
+    fn moved(targets: &[FocusTarget], from: Option<&str>, movement: FocusMove) -> Option<Id> {
+        navigate(targets, from.map(Id::from), movement).map(|target| target.id)
+    }
+
+    #[test]
+    fn tab_order() {
+        // 'c' is explicitly first, and the rest follow in render order
+        let targets = [
+            target("a", 0_f32, 0_f32, None),
+            target("b", 20_f32, 0_f32, None),
+            target("c", 40_f32, 0_f32, Some(0)),
+        ];
+
+        assert_eq!(moved(&targets, None, FocusMove::Next), Some(Id::from("c")));
+        assert_eq!(
+            moved(&targets, Some("c"), FocusMove::Next),
+            Some(Id::from("a"))
+        );
+        assert_eq!(
+            moved(&targets, Some("b"), FocusMove::Next),
+            Some(Id::from("c"))
+        );
+        assert_eq!(
+            moved(&targets, Some("c"), FocusMove::Previous),
+            Some(Id::from("b"))
+        );
+        assert_eq!(
+            moved(&targets, None, FocusMove::Previous),
+            Some(Id::from("b"))
+        );
+        assert_eq!(moved(&[], None, FocusMove::Next), None);
+
+        // Only the popup's targets can be focused while it's open
+        let popup = FocusTarget {
+            layer: Layer::POPUP,
+            ..target("popup", 0_f32, 40_f32, None)
+        };
+        let with_popup = [targets[0], popup, targets[1]];
+        assert_eq!(
+            moved(&with_popup, Some("a"), FocusMove::Next),
+            Some(Id::from("popup"))
+        );
+        assert_eq!(
+            moved(&with_popup, Some("popup"), FocusMove::Next),
+            Some(Id::from("popup"))
+        );
+    }
+
+    #[test]
+    fn directions() {
+        // a b
+        // c   d
+        let targets = [
+            target("a", 0_f32, 0_f32, None),
+            target("b", 20_f32, 0_f32, None),
+            target("c", 0_f32, 20_f32, None),
+            target("d", 40_f32, 20_f32, None),
+        ];
+
+        assert_eq!(
+            moved(&targets, Some("a"), FocusMove::Right),
+            Some(Id::from("b"))
+        );
+        assert_eq!(
+            moved(&targets, Some("a"), FocusMove::Down),
+            Some(Id::from("c"))
+        );
+        assert_eq!(
+            moved(&targets, Some("c"), FocusMove::Right),
+            Some(Id::from("d"))
+        );
+        assert_eq!(
+            moved(&targets, Some("d"), FocusMove::Up),
+            Some(Id::from("b"))
+        );
+        assert_eq!(moved(&targets, Some("a"), FocusMove::Up), None);
+        assert_eq!(moved(&targets, None, FocusMove::Left), Some(Id::from("a")));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::core::id::Id;
+
+// A key, independent of the window system. Keys that type something are identified by the character
+// they'd type without any modifiers (so Shift+A is 'Character('a')' with 'shift' set).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Character(char),
+    Enter,
+    Tab,
+    Escape,
+    Backspace,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    F(u8),
+    Shift,
+    Control,
+    Alt,
+    Meta,
+    // Anything else, by the window system's own code for it
+    Other(u32),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers {
+        shift: false,
+        ctrl: false,
+        alt: false,
+        meta: false,
+    };
+
+    pub const SHIFT: Modifiers = Modifiers {
+        shift: true,
+        ..Modifiers::NONE
+    };
+
+    pub const CTRL: Modifiers = Modifiers {
+        ctrl: true,
+        ..Modifiers::NONE
+    };
+
+    pub fn is_empty(&self) -> bool {
+        *self == Modifiers::NONE
+    }
+}
+
+// Text an input method is in the middle of composing, to be shown where it'll be inserted. The cursor
+// (or selection) is a range of byte offsets into the text.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preedit {
+    pub text: String,
+    pub cursor: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyEvent {
+    Down {
+        key: Key,
+        modifiers: Modifiers,
+        // Sent again while the key is held down
+        repeat: bool,
+    },
+    Up {
+        key: Key,
+        modifiers: Modifiers,
+    },
+    // Text to insert, whether it was typed directly or committed by an input method
+    Text(String),
+    // The input method's composition changed, or started
+    Composition(Preedit),
+    // The composition was committed (followed by the text) or cancelled
+    CompositionEnd,
+}
+
+// What the keyboard did since the last frame. Sent to 'keyboard_id(id)' for the focused Id when it has
+// any events, or while an input method is composing text for it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyboardInput {
+    // Everything that happened since the last frame, in order
+    pub events: Vec<KeyEvent>,
+    // The modifiers held down as of the last event
+    pub modifiers: Modifiers,
+    // The text an input method is composing, if it's still in progress
+    pub preedit: Option<Preedit>,
+}
+
+impl KeyboardInput {
+    // Whether the key went down (including repeats) with exactly these modifiers
+    pub fn pressed(&self, key: Key, modifiers: Modifiers) -> bool {
+        self.events.iter().any(|event| match event {
+            KeyEvent::Down {
+                key: k,
+                modifiers: m,
+                ..
+            } => *k == key && *m == modifiers,
+            _ => false,
+        })
+    }
+
+    // All of the text that was input, joined together
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for event in &self.events {
+            if let KeyEvent::Text(t) = event {
+                text.push_str(t);
+            }
+        }
+        text
+    }
+}
+
+// Where the keyboard input for a focusable region is sent
+pub fn keyboard_id(id: Id) -> Id {
+    id.append("keyboard")
+}
+
+// Where every keyboard event is sent, whatever is focused (or if nothing is), for things like shortcuts.
+// Input method compositions only go to the focused Id.
+pub fn global_keyboard_id() -> Id {
+    Id::from("buoy::keyboard")
+}
@@ -1,60 +1,370 @@
 use std::any::Any;
 use std::cell::Cell;
-use std::collections::hash_map::HashMap;
+use std::collections::hash_map::{self, HashMap};
+use std::collections::HashSet;
+use std::fmt;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
+use crate::core::device::DeviceInfo;
 use crate::core::id::Id;
+use crate::core::topic::TopicKey;
+use crate::core::trace::{TraceKind, TraceRecord};
 
 pub trait Message: Clone + Send + Any {}
 
 impl<T: Clone + Send + Any> Message for T {}
 
+pub(in crate::core) type AnyMessage = Box<dyn Any + Send>;
+type Reducer = dyn Fn(AnyMessage, AnyMessage) -> AnyMessage + Send + Sync;
+
+#[derive(Clone)]
+enum Merge {
+    LastWins,
+    FirstWins,
+    Reduce(Arc<Reducer>),
+}
+
+// What happens when a message is written to an Id that already has one this frame (set with
+// 'Outbox::set_merge_policy'). The policy of the later write decides. Without one, the later write
+// replaces the earlier one, which is reported as a conflict when debugging messages.
+pub struct MergePolicy<T> {
+    merge: Merge,
+    _phantom: PhantomData<fn(T) -> T>,
+}
+
+impl<T: Message> MergePolicy<T> {
+    fn new(merge: Merge) -> Self {
+        MergePolicy {
+            merge,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn last_wins() -> Self {
+        MergePolicy::new(Merge::LastWins)
+    }
+
+    pub fn first_wins() -> Self {
+        MergePolicy::new(Merge::FirstWins)
+    }
+
+    // Combines the earlier message with the later one
+    pub fn reduce<F: Fn(T, T) -> T + Send + Sync + 'static>(reduce: F) -> Self {
+        let reduce = move |first: AnyMessage, second: AnyMessage| {
+            // Both are only ever a 'T', unless another type was written to the same Id
+            if !first.is::<T>() || !second.is::<T>() {
+                return second;
+            }
+            let first = *first.downcast::<T>().unwrap();
+            let second = *second.downcast::<T>().unwrap();
+            Box::new(reduce(first, second)) as AnyMessage
+        };
+        MergePolicy::new(Merge::Reduce(Arc::new(reduce)))
+    }
+}
+
+impl<T: Message> MergePolicy<Vec<T>> {
+    // Appends the later messages to the earlier ones, so writers can each write 'vec![value]' and the
+    // reader gets all of them, in the order they were written
+    pub fn collect() -> Self {
+        MergePolicy::reduce(|mut first: Vec<T>, second| {
+            first.extend(second);
+            first
+        })
+    }
+}
+
+impl<T> Clone for MergePolicy<T> {
+    fn clone(&self) -> Self {
+        MergePolicy {
+            merge: self.merge.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+// A message that replaced another one written to the same Id in the same frame, because neither
+// writer set a merge policy. Only recorded while debugging messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageConflict {
+    pub id: Id,
+    pub message_type: &'static str,
+    // The devices that wrote the message that was dropped, and the one that replaced it. 'None' for
+    // messages written from outside of a device, like input.
+    pub first: Option<DeviceInfo>,
+    pub second: Option<DeviceInfo>,
+}
+
+impl fmt::Display for MessageConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let writer = |writer: Option<DeviceInfo>| match writer {
+            Some(device) => device.to_string(),
+            None => "outside of a device".to_string(),
+        };
+        write!(
+            f,
+            "{} message for {:?} written by {} was replaced by one written by {}",
+            self.message_type,
+            self.id,
+            writer(self.first),
+            writer(self.second)
+        )
+    }
+}
+
+struct Entry {
+    value: AnyMessage,
+    clone: fn(&(dyn Any + Send)) -> AnyMessage,
+    merge: Option<Merge>,
+    message_type: &'static str,
+    writer: Option<DeviceInfo>,
+}
+
+impl Entry {
+    fn new<T: Message>(value: T, merge: Option<Merge>, writer: Option<DeviceInfo>) -> Self {
+        Entry {
+            value: Box::new(value),
+            clone: clone_message::<T>,
+            merge,
+            message_type: std::any::type_name::<T>(),
+            writer,
+        }
+    }
+}
+
+fn clone_message<T: Message>(value: &(dyn Any + Send)) -> AnyMessage {
+    Box::new(value.downcast_ref::<T>().unwrap().clone())
+}
+
 #[derive(Default)]
 pub struct MessageMap {
-    map: HashMap<Id, Box<dyn Any + Send>>,
+    map: HashMap<Id, Entry>,
+    // Only kept while debugging messages
+    conflicts: Option<Vec<MessageConflict>>,
+    // Only kept while tracing messages
+    trace: Option<Vec<TraceRecord>>,
+    // Events published to topics, in the order they were published
+    events: Vec<(TopicKey, AnyMessage)>,
 }
 
 impl MessageMap {
     pub fn read<T: Message>(&self, inbox: Inbox<T>) -> Option<T> {
         let value = match self.map.get(&inbox.id()) {
-            Some(value) => &**value,
+            Some(entry) => &*entry.value,
             None => return None,
         };
 
         value.downcast_ref::<T>().cloned()
     }
 
-    pub fn write<T: Message>(&mut self, mut outbox: Outbox<T>, value: T) {
+    pub fn write<T: Message>(&mut self, outbox: Outbox<T>, value: T) {
+        self.write_from(outbox, value, None);
+    }
+
+    // Writes a message on behalf of a device, so it can be named in conflicts and traces
+    pub(in crate::core) fn write_from<T: Message>(
+        &mut self,
+        mut outbox: Outbox<T>,
+        value: T,
+        writer: Option<DeviceInfo>,
+    ) {
+        let id = outbox.id();
         if let Some(mapping) = outbox.mapping.take() {
-            mapping(&value, MessageWriter { message_map: self });
+            self.record::<T>(TraceKind::Map, id, writer);
+            let message_map = &mut *self;
+            mapping(
+                &value,
+                MessageWriter {
+                    message_map,
+                    writer,
+                },
+            );
         }
 
         if outbox.observed.get() {
-            self.map.insert(outbox.id(), Box::new(value));
+            self.record::<T>(TraceKind::Write, id, writer);
+            self.merge(id, Entry::new(value, outbox.merge.take(), writer));
+        } else {
+            self.record::<T>(TraceKind::Dropped, id, writer);
+        }
+    }
+
+    // Adds to the trace, if messages are being traced
+    pub(in crate::core) fn record<T: Message>(
+        &mut self,
+        kind: TraceKind,
+        id: Id,
+        device: Option<DeviceInfo>,
+    ) {
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceRecord {
+                kind,
+                id,
+                message_type: std::any::type_name::<T>(),
+                device,
+            });
         }
     }
 
+    fn merge(&mut self, id: Id, mut entry: Entry) {
+        let mut existing = match self.map.entry(id) {
+            hash_map::Entry::Occupied(existing) => existing,
+            hash_map::Entry::Vacant(vacant) => {
+                vacant.insert(entry);
+                return;
+            }
+        };
+
+        match &entry.merge {
+            Some(Merge::LastWins) => (),
+            Some(Merge::FirstWins) => return,
+            Some(Merge::Reduce(reduce)) => {
+                let first = std::mem::replace(&mut existing.get_mut().value, Box::new(()));
+                entry.value = reduce(first, entry.value);
+            }
+            None => {
+                if let Some(conflicts) = &mut self.conflicts {
+                    conflicts.push(MessageConflict {
+                        id,
+                        message_type: entry.message_type,
+                        first: existing.get().writer,
+                        second: entry.writer,
+                    });
+                }
+            }
+        }
+        existing.insert(entry);
+    }
+
+    pub(in crate::core) fn contains(&self, id: Id) -> bool {
+        self.map.contains_key(&id)
+    }
+
+    pub(in crate::core) fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub(in crate::core) fn iter(&self) -> impl Iterator<Item = (Id, &(dyn Any + Send))> {
+        self.map.iter().map(|(id, entry)| (*id, &*entry.value))
+    }
+
+    // Writes a message without an Outbox, for messages that were already kept once (like ones loaded
+    // from a file)
+    pub(in crate::core) fn insert<T: Message>(&mut self, id: Id, value: T) {
+        self.map.insert(id, Entry::new(value, None, None));
+    }
+
+    // A copy of the messages, without any conflicts, trace or events
+    pub(in crate::core) fn copy(&self) -> MessageMap {
+        let copy = |entry: &Entry| Entry {
+            value: (entry.clone)(&*entry.value),
+            clone: entry.clone,
+            merge: entry.merge.clone(),
+            message_type: entry.message_type,
+            writer: entry.writer,
+        };
+        let map = self.map.iter().map(|(id, entry)| (*id, copy(entry)));
+        MessageMap {
+            map: map.collect(),
+            ..Default::default()
+        }
+    }
+
+    pub(in crate::core) fn publish(&mut self, topic: TopicKey, value: AnyMessage) {
+        self.events.push((topic, value));
+    }
+
+    pub(in crate::core) fn take_events(&mut self) -> Vec<(TopicKey, AnyMessage)> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Merges in messages written elsewhere in the same frame (like on another thread), as if they had
+    // been written here after everything that already has been
     pub fn extend(&mut self, other: &mut MessageMap) {
+        for (id, entry) in other.map.drain() {
+            self.merge(id, entry);
+        }
+        self.events.append(&mut other.events);
+        self.extend_records(other);
+    }
+
+    // Replaces messages with the ones from a later pass, without merging them. Topics the later pass
+    // published to only keep its events, since it will have published them again.
+    pub(in crate::core) fn overlay(&mut self, other: &mut MessageMap) {
         self.map.extend(other.map.drain());
+        let topics: HashSet<TopicKey> = other.events.iter().map(|(topic, _)| *topic).collect();
+        self.events.retain(|(topic, _)| !topics.contains(topic));
+        self.events.append(&mut other.events);
+        self.extend_records(other);
+    }
+
+    fn extend_records(&mut self, other: &mut MessageMap) {
+        if let Some(other) = &mut other.conflicts {
+            self.conflicts.get_or_insert_with(Vec::new).append(other);
+        }
+        if let Some(other) = &mut other.trace {
+            self.trace.get_or_insert_with(Vec::new).append(other);
+        }
+    }
+
+    // Starts or stops recording conflicts
+    pub(in crate::core) fn set_debug(&mut self, debug: bool) {
+        set_recording(&mut self.conflicts, debug);
+    }
+
+    // Starts or stops tracing
+    pub(in crate::core) fn set_tracing(&mut self, tracing: bool) {
+        set_recording(&mut self.trace, tracing);
+    }
+
+    pub(in crate::core) fn take_conflicts(&mut self) -> Vec<MessageConflict> {
+        self.conflicts
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub(in crate::core) fn take_trace(&mut self) -> Vec<TraceRecord> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // Takes every message, leaving this map empty but still recording whatever it was before
+    pub(in crate::core) fn take(&mut self) -> MessageMap {
+        let mut empty = MessageMap::default();
+        empty.set_debug(self.conflicts.is_some());
+        empty.set_tracing(self.trace.is_some());
+        std::mem::replace(self, empty)
     }
 
     pub fn clear(&mut self) {
         self.map.clear();
+        self.events.clear();
+    }
+}
+
+fn set_recording<T>(records: &mut Option<Vec<T>>, recording: bool) {
+    if !recording {
+        *records = None;
+    } else if records.is_none() {
+        *records = Some(Vec::new());
     }
 }
 
 pub struct MessageWriter<'a> {
     message_map: &'a mut MessageMap,
+    writer: Option<DeviceInfo>,
 }
 
 impl<'a> MessageWriter<'a> {
     pub fn write<T: Message>(&mut self, outbox: Outbox<T>, value: T) {
-        self.message_map.write(outbox, value);
+        self.message_map.write_from(outbox, value, self.writer);
     }
 
     pub fn reborrow<'b>(&'b mut self) -> MessageWriter<'b> {
         MessageWriter {
             message_map: self.message_map,
+            writer: self.writer,
         }
     }
 }
@@ -72,11 +382,14 @@ impl<T: Message> Inbox<T> {
     }
 }
 
+type Mapping<T> = Box<dyn FnOnce(&T, MessageWriter) + Send>;
+
 #[repr(C)]
 pub struct Outbox<T: Message> {
     id: Id,
     observed: Cell<bool>,
-    mapping: Option<Box<dyn FnOnce(&T, MessageWriter)>>,
+    mapping: Option<Mapping<T>>,
+    merge: Option<Merge>,
 }
 
 impl<T: Message> Outbox<T> {
@@ -85,6 +398,7 @@ impl<T: Message> Outbox<T> {
             id,
             observed: Cell::new(false),
             mapping: None,
+            merge: None,
         }
     }
 
@@ -100,7 +414,12 @@ impl<T: Message> Outbox<T> {
         }
     }
 
-    pub fn map<F: FnOnce(&T, MessageWriter) + 'static>(&mut self, mapping: F) {
+    // How this message is combined with others written to the same Id in the same frame
+    pub fn set_merge_policy(&mut self, policy: MergePolicy<T>) {
+        self.merge = Some(policy.merge);
+    }
+
+    pub fn map<F: FnOnce(&T, MessageWriter) + Send + 'static>(&mut self, mapping: F) {
         if let Some(existing_mapping) = self.mapping.take() {
             self.mapping = Some(Box::new(|v, mut writer| {
                 existing_mapping(v, writer.reborrow());
@@ -112,7 +431,7 @@ impl<T: Message> Outbox<T> {
     }
 
     // Constructs an Outbox of a different type that will write to this Outbox using the given mapping function.
-    pub fn map_from<I: Message, F: FnOnce(&I, MessageWriter) -> T + 'static>(
+    pub fn map_from<I: Message, F: FnOnce(&I, MessageWriter) -> T + Send + 'static>(
         self,
         mapping: F,
     ) -> Outbox<I> {
@@ -131,12 +450,177 @@ impl<T: Message> Outbox<T> {
                 let v = mapping(v, writer.reborrow());
                 writer.write(self, v);
             })),
+            merge: None,
         }
     }
 }
 
-impl<'a, T: Message> Into<Inbox<T>> for &'a Outbox<T> {
-    fn into(self) -> Inbox<T> {
-        self.inbox()
+impl<T: Message> From<&Outbox<T>> for Inbox<T> {
+    fn from(outbox: &Outbox<T>) -> Self {
+        outbox.inbox()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::util::ref_move::Ext;
+    use std::rc::Rc;
+
+    type Canvas = Vec<(&'static str, f32)>;
+
+    // Writes two messages to the same Id, one of them through a writer, after reading the one from the
+    // last frame
+    struct Fan(Option<MergePolicy<f32>>);
+    struct FanRenderer;
+
+    struct Writer(Outbox<f32>, f32);
+    struct WriterRenderer;
+
+    impl Device for Fan {
+        fn type_id() -> TypeId {
+            TypeId::new(0x1d4aa233_ea2e_43e6_a0e9_6639cb99165d)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Fan"
+        }
+    }
+
+    impl Device for Writer {
+        fn type_id() -> TypeId {
+            TypeId::new(0x58527e63_5a33_4881_8014_b6c7b9f1a41a)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Writer"
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for FanRenderer {
+        type Device = Fan;
+        type Layout = (f32, Option<LayoutNode>);
+
+        fn layout<'thrd>(
+            &self,
+            device: Fan,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<(f32, Option<LayoutNode>)> {
+            let mut first = ctx.message::<f32>("fan".into());
+            let mut second = ctx.message::<f32>("fan".into());
+            let last = ctx.read_message(first.inbox()).unwrap_or(0_f32);
+            second.inbox();
+            if let Some(policy) = device.0 {
+                first.set_merge_policy(policy.clone());
+                second.set_merge_policy(policy);
+            }
+
+            ctx.write_message(first, 1_f32);
+            let writer = Writer(second, 2_f32).move_anchor();
+            match ctx.device_tree(ctx.max_size(), writer, ()) {
+                LayoutResult::CompleteNode(node) => ctx.layout(Size::zero(), (last, Some(node))),
+                _ => ctx.layout(Size::zero(), (last, None)),
+            }
+        }
+
+        fn render<'ctx>(
+            &self,
+            (last, node): (f32, Option<LayoutNode>),
+            ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            canvas.push(("fan", last));
+            if let Some(node) = node {
+                ctx.render(node, ctx.region(), canvas);
+            }
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for WriterRenderer {
+        type Device = Writer;
+        type Layout = ();
+
+        fn layout<'thrd>(
+            &self,
+            Writer(outbox, value): Writer,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<()> {
+            ctx.write_message(outbox, value);
+            ctx.layout(Size::zero(), ())
+        }
+
+        fn render<'ctx>(&self, _: (), _: RenderContext<'ctx, 'frm, Canvas>, _: &mut Canvas) {}
+    }
+
+    #[test]
+    fn collect() {
+        // Messages written on other threads are merged in after the ones written here
+        let outbox = || {
+            let mut outbox = Outbox::<Vec<u32>>::new("clicks".into());
+            outbox.set_merge_policy(MergePolicy::collect());
+            outbox.inbox();
+            outbox
+        };
+        let (mut here, mut there) = (MessageMap::default(), MessageMap::default());
+        here.write(outbox(), vec![1]);
+        there.write(outbox(), vec![2]);
+        here.write(outbox(), vec![3]);
+        here.extend(&mut there);
+
+        let inbox = Outbox::<Vec<u32>>::new("clicks".into()).inbox();
+        assert_eq!(here.read(inbox), Some(vec![1, 3, 2]));
+    }
+
+    #[test]
+    fn merge_policies() {
+        let mut gui = GuiContext::default();
+        gui.register::<Fan>(Rc::new(FanRenderer));
+        gui.register::<Writer>(Rc::new(WriterRenderer));
+        gui.set_debug_messages(true);
+
+        // Returns what the last frame's writes were merged into
+        let frame = |gui: &mut GuiContext<Canvas>, policy| {
+            let mut canvas = Canvas::new();
+            let region = Region::new(Point::zero(), Size::new(100_f32, 100_f32));
+            gui.render_window(region, Fan(policy).move_anchor(), &mut canvas)
+                .unwrap();
+            canvas[0].1
+        };
+
+        // Without a policy the last write wins, and the one it replaced is reported
+        frame(&mut gui, None);
+        let conflict = MessageConflict {
+            id: "fan".into(),
+            message_type: "f32",
+            first: Some(DeviceInfo::of::<Fan>()),
+            second: Some(DeviceInfo::of::<Writer>()),
+        };
+        assert_eq!(gui.message_conflicts(), &[conflict]);
+        assert_eq!(
+            conflict.to_string(),
+            format!(
+                "f32 message for {:?} written by {} was replaced by one written by {}",
+                conflict.id,
+                DeviceInfo::of::<Fan>(),
+                DeviceInfo::of::<Writer>()
+            )
+        );
+
+        assert_eq!(frame(&mut gui, Some(MergePolicy::first_wins())), 2_f32);
+        assert!(gui.message_conflicts().is_empty());
+        let sum = MergePolicy::reduce(|a, b| a + b);
+        assert_eq!(frame(&mut gui, Some(sum)), 1_f32);
+        gui.set_debug_messages(false);
+        assert_eq!(frame(&mut gui, None), 3_f32);
+        assert!(gui.message_conflicts().is_empty());
     }
 }
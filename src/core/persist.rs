@@ -0,0 +1,537 @@
+use crate::core::error::BuoyError;
+use crate::core::id::Id;
+use crate::core::message::MessageMap;
+use crate::core::state::{StateStore, StateValue};
+use crate::space::{Point, Region, Size, Vector};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Mutex;
+
+// Saved files start with this, followed by the version of the format
+const MAGIC: &[u8; 8] = b"buoysave";
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum PersistError {
+    Io(io::Error),
+    // The data isn't a saved session, or is cut short
+    Malformed(&'static str),
+    // The data was saved with a different version of the format
+    Version(u32),
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PersistError::Io(error) => write!(f, "Couldn't read the saved session: {}", error),
+            PersistError::Malformed(what) => write!(f, "Malformed saved session: {}", what),
+            PersistError::Version(version) => write!(
+                f,
+                "Saved session is version {}, but only version {} can be loaded",
+                version, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for PersistError {}
+
+impl From<io::Error> for PersistError {
+    fn from(error: io::Error) -> Self {
+        PersistError::Io(error)
+    }
+}
+
+// Writes values in a compact binary form. Numbers are little-endian, and lengths are u32s.
+#[derive(Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder::default()
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn write_len(&mut self, len: usize) {
+        self.write_bytes(&(len as u32).to_le_bytes());
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Decoder { data }
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], PersistError> {
+        if len > self.data.len() {
+            return Err(PersistError::Malformed("unexpected end of data"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn read_len(&mut self) -> Result<usize, PersistError> {
+        u32::decode(self).map(|len| len as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+// Implemented by types that can be saved to a file and loaded back, so they can be registered with
+// 'GuiContext::register_persistent'
+pub trait Persist: Sized {
+    fn encode(&self, encoder: &mut Encoder);
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError>;
+}
+
+macro_rules! persist_number {
+    ($($ty:ty),*) => {
+        $(
+            impl Persist for $ty {
+                fn encode(&self, encoder: &mut Encoder) {
+                    encoder.write_bytes(&self.to_le_bytes());
+                }
+
+                fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+                    let bytes = decoder.read_bytes(std::mem::size_of::<$ty>())?;
+                    let mut array = [0; std::mem::size_of::<$ty>()];
+                    array.copy_from_slice(bytes);
+                    Ok(<$ty>::from_le_bytes(array))
+                }
+            }
+        )*
+    };
+}
+
+persist_number!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl Persist for usize {
+    fn encode(&self, encoder: &mut Encoder) {
+        (*self as u64).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        Ok(u64::decode(decoder)? as usize)
+    }
+}
+
+impl Persist for bool {
+    fn encode(&self, encoder: &mut Encoder) {
+        (*self as u8).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        match u8::decode(decoder)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(PersistError::Malformed("invalid bool")),
+        }
+    }
+}
+
+impl Persist for char {
+    fn encode(&self, encoder: &mut Encoder) {
+        (*self as u32).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        let c = std::char::from_u32(u32::decode(decoder)?);
+        c.ok_or(PersistError::Malformed("invalid char"))
+    }
+}
+
+impl Persist for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_len(self.len());
+        encoder.write_bytes(self.as_bytes());
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        let len = decoder.read_len()?;
+        let bytes = decoder.read_bytes(len)?;
+        let text = std::str::from_utf8(bytes);
+        text.map(str::to_string)
+            .map_err(|_| PersistError::Malformed("invalid UTF-8"))
+    }
+}
+
+impl<T: Persist> Persist for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_len(self.len());
+        for value in self {
+            value.encode(encoder);
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        let len = decoder.read_len()?;
+        // Each value takes at least a byte, so a bad length can't allocate more than the data
+        let mut values = Vec::with_capacity(len.min(decoder.data.len()));
+        for _ in 0..len {
+            values.push(T::decode(decoder)?);
+        }
+        Ok(values)
+    }
+}
+
+impl<T: Persist> Persist for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.is_some().encode(encoder);
+        if let Some(value) = self {
+            value.encode(encoder);
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        match bool::decode(decoder)? {
+            true => T::decode(decoder).map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<A: Persist, B: Persist> Persist for (A, B) {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.0.encode(encoder);
+        self.1.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        Ok((A::decode(decoder)?, B::decode(decoder)?))
+    }
+}
+
+impl Persist for Id {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.to_bits().encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        u64::decode(decoder).map(Id::from_bits)
+    }
+}
+
+impl Persist for Point {
+    fn encode(&self, encoder: &mut Encoder) {
+        (self.x, self.y).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        let (x, y) = Persist::decode(decoder)?;
+        Ok(Point::new(x, y))
+    }
+}
+
+impl Persist for Vector {
+    fn encode(&self, encoder: &mut Encoder) {
+        (self.x, self.y).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        let (x, y) = Persist::decode(decoder)?;
+        Ok(Vector::new(x, y))
+    }
+}
+
+impl Persist for Size {
+    fn encode(&self, encoder: &mut Encoder) {
+        (self.width, self.height).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        let (width, height) = Persist::decode(decoder)?;
+        Ok(Size::new(width, height))
+    }
+}
+
+impl Persist for Region {
+    fn encode(&self, encoder: &mut Encoder) {
+        (self.pos, self.size).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError> {
+        let (pos, size) = Persist::decode(decoder)?;
+        Ok(Region::new(pos, size))
+    }
+}
+
+// How to save and load one registered type
+struct Codec {
+    name: &'static str,
+    encode_message: fn(&(dyn Any + Send), &mut Encoder),
+    encode_state: fn(&(dyn Any + Send + Sync), &mut Encoder),
+    decode_message: fn(&mut Decoder, &mut MessageMap, Id) -> Result<(), PersistError>,
+    decode_state: fn(&mut Decoder, &mut StateStore, Id, u64) -> Result<(), PersistError>,
+}
+
+// A saved value whose type is registered, with the codec to read it
+type Record<'r, 'a> = (Id, &'r Codec, &'a [u8]);
+
+fn encode_message<T: Persist + 'static>(value: &(dyn Any + Send), encoder: &mut Encoder) {
+    value.downcast_ref::<T>().unwrap().encode(encoder);
+}
+
+fn encode_state<T: Persist + 'static>(value: &(dyn Any + Send + Sync), encoder: &mut Encoder) {
+    let value = value.downcast_ref::<Mutex<T>>().unwrap();
+    value.lock().unwrap().encode(encoder);
+}
+
+fn decode_message<T: Persist + StateValue>(
+    decoder: &mut Decoder,
+    messages: &mut MessageMap,
+    id: Id,
+) -> Result<(), PersistError> {
+    messages.insert(id, T::decode(decoder)?);
+    Ok(())
+}
+
+fn decode_state<T: Persist + StateValue>(
+    decoder: &mut Decoder,
+    state: &mut StateStore,
+    id: Id,
+    frame: u64,
+) -> Result<(), PersistError> {
+    state.insert(id, T::decode(decoder)?, frame);
+    Ok(())
+}
+
+// The types that can be saved, by name and by type. Names are what's written to the file, so they
+// need to stay the same between versions of the program for saved sessions to keep loading.
+#[derive(Default)]
+pub(in crate::core) struct PersistRegistry {
+    codecs: HashMap<TypeId, Codec>,
+    names: HashMap<&'static str, TypeId>,
+}
+
+impl PersistRegistry {
+    pub fn register<T: Persist + StateValue>(
+        &mut self,
+        name: &'static str,
+    ) -> Result<(), BuoyError> {
+        let type_id = TypeId::of::<T>();
+        if self.names.contains_key(name) || self.codecs.contains_key(&type_id) {
+            return Err(BuoyError::DuplicatePersistentType(name));
+        }
+
+        let codec = Codec {
+            name,
+            encode_message: encode_message::<T>,
+            encode_state: encode_state::<T>,
+            decode_message: decode_message::<T>,
+            decode_state: decode_state::<T>,
+        };
+        self.codecs.insert(type_id, codec);
+        self.names.insert(name, type_id);
+        Ok(())
+    }
+
+    // Writes every message and value in the state store whose type is registered. Everything else
+    // is left out.
+    pub fn save(&self, messages: &MessageMap, state: &StateStore) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.write_bytes(MAGIC);
+        FORMAT_VERSION.encode(&mut encoder);
+
+        let mut records = Vec::new();
+        for (id, value) in messages.iter() {
+            if let Some(codec) = self.codecs.get(&(*value).type_id()) {
+                let mut payload = Encoder::new();
+                (codec.encode_message)(value, &mut payload);
+                records.push((id, codec.name, payload.into_bytes()));
+            }
+        }
+        write_records(&mut encoder, records);
+
+        let mut records = Vec::new();
+        state.for_each(|id, type_id, value| {
+            if let Some(codec) = self.codecs.get(&type_id) {
+                let mut payload = Encoder::new();
+                (codec.encode_state)(value, &mut payload);
+                records.push((id, codec.name, payload.into_bytes()));
+            }
+        });
+        write_records(&mut encoder, records);
+
+        encoder.into_bytes()
+    }
+
+    // Reads messages and state saved by 'save', as of the given frame. Records of types that aren't
+    // registered (any more) are skipped. Nothing is changed unless all of the data can be read.
+    pub fn load(
+        &self,
+        data: &[u8],
+        messages: &mut MessageMap,
+        state: &mut StateStore,
+        frame: u64,
+    ) -> Result<(), PersistError> {
+        let mut decoder = Decoder::new(data);
+        if decoder.read_bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(PersistError::Malformed("not a saved session"));
+        }
+        let version = u32::decode(&mut decoder)?;
+        if version != FORMAT_VERSION {
+            return Err(PersistError::Version(version));
+        }
+
+        let mut loaded_messages = MessageMap::default();
+        for (id, codec, payload) in self.read_records(&mut decoder)? {
+            let mut payload = Decoder::new(payload);
+            (codec.decode_message)(&mut payload, &mut loaded_messages, id)?;
+        }
+
+        let mut loaded_state = StateStore::default();
+        for (id, codec, payload) in self.read_records(&mut decoder)? {
+            let mut payload = Decoder::new(payload);
+            (codec.decode_state)(&mut payload, &mut loaded_state, id, frame)?;
+        }
+
+        messages.extend(&mut loaded_messages);
+        state.merge(loaded_state);
+        Ok(())
+    }
+
+    fn read_records<'a>(
+        &self,
+        decoder: &mut Decoder<'a>,
+    ) -> Result<Vec<Record<'_, 'a>>, PersistError> {
+        let mut records = Vec::new();
+        let count = decoder.read_len()?;
+        for _ in 0..count {
+            let id = Id::decode(decoder)?;
+            let name = String::decode(decoder)?;
+            let len = decoder.read_len()?;
+            let payload = decoder.read_bytes(len)?;
+
+            let codec = self.names.get(&*name).map(|type_id| &self.codecs[type_id]);
+            if let Some(codec) = codec {
+                records.push((id, codec, payload));
+            }
+        }
+        Ok(records)
+    }
+}
+
+// Each record is the Id, the name of the type, then the length of the value so it can be skipped
+fn write_records(encoder: &mut Encoder, records: Vec<(Id, &'static str, Vec<u8>)>) {
+    encoder.write_len(records.len());
+    for (id, name, payload) in records {
+        id.encode(encoder);
+        name.to_string().encode(encoder);
+        encoder.write_len(payload.len());
+        encoder.write_bytes(&payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn round_trip<T: Persist>(value: &T) -> T {
+        let mut encoder = Encoder::new();
+        value.encode(&mut encoder);
+        let bytes = encoder.into_bytes();
+        let mut decoder = Decoder::new(&bytes);
+        let value = T::decode(&mut decoder).unwrap();
+        assert!(decoder.is_empty());
+        value
+    }
+
+    fn session() -> GuiContext<()> {
+        let mut gui = GuiContext::default();
+        gui.register_persistent::<Vector>("offset");
+        gui.register_persistent::<String>("text");
+        gui
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(round_trip(&-3_i16), -3_i16);
+        assert_eq!(round_trip(&'é'), 'é');
+        let value = vec![(Some("ab".to_string()), true), (None, false)];
+        assert_eq!(round_trip(&value), value);
+        let region = Region::new(Point::new(1_f32, 2_f32), Size::new(3_f32, 4_f32));
+        assert_eq!(round_trip(&region), region);
+
+        let mut decoder = Decoder::new(&[1, 0, 0, 0]);
+        assert!(String::decode(&mut decoder).is_err());
+    }
+
+    #[test]
+    fn sessions() {
+        let mut gui = session();
+        let outbox = gui.message::<Vector>(Id::from("scroll"));
+        outbox.inbox();
+        gui.write_message(outbox, Vector::new(0_f32, 30_f32));
+        gui.state_or_insert_with(Id::from("name"), || "buoy".to_string());
+        // Neither of these types are registered, so they aren't saved
+        gui.state::<u32>(Id::from("count")).set(3);
+        let outbox = gui.message::<u32>(Id::from("count"));
+        outbox.inbox();
+        gui.write_message(outbox, 3);
+        let saved = gui.save_session();
+
+        let mut loaded = session();
+        loaded.load_session(&saved).unwrap();
+        assert_eq!(loaded.state_len(), 1);
+        assert_eq!(loaded.state::<String>(Id::from("name")).get(), "buoy");
+        assert_eq!(loaded.save_session(), saved);
+
+        // Types that aren't registered when loading are skipped
+        let mut loaded = GuiContext::<()>::default();
+        loaded.register_persistent::<String>("text");
+        loaded.load_session(&saved).unwrap();
+        assert_eq!(loaded.state_len(), 1);
+        let mut other = GuiContext::<()>::default();
+        other.register_persistent::<String>("text");
+        other.state_or_insert_with(Id::from("name"), || "buoy".to_string());
+        assert_eq!(loaded.save_session(), other.save_session());
+
+        let mut gui = session();
+        assert!(gui.try_register_persistent::<Vector>("vector").is_err());
+        assert!(gui.try_register_persistent::<u32>("text").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        let mut gui = session();
+        let mut saved = gui.save_session();
+        saved[8] = 2;
+        assert!(matches!(
+            gui.load_session(&saved),
+            Err(PersistError::Version(2))
+        ));
+
+        assert!(matches!(
+            gui.load_session(b"not a session"),
+            Err(PersistError::Malformed(_))
+        ));
+
+        // Nothing is loaded from data that's cut short
+        let other = session();
+        other.state_or_insert_with(Id::from("name"), || "buoy".to_string());
+        let saved = other.save_session();
+        let result = gui.load_session(&saved[..saved.len() - 1]);
+        assert!(matches!(result, Err(PersistError::Malformed(_))));
+        assert_eq!(gui.state_len(), 0);
+    }
+}
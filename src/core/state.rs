@@ -0,0 +1,300 @@
+use crate::core::id::Id;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// Anything that can be kept in the state store
+pub trait StateValue: Clone + Send + Any {}
+
+impl<T: Clone + Send + Any> StateValue for T {}
+
+// A handle to a value in the state store. Handles to the same Id and type share the value, which stays
+// in the store for as long as some device asks for it at least once every few frames.
+pub struct State<T> {
+    value: Arc<Mutex<T>>,
+}
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        State {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T: StateValue> State<T> {
+    pub fn get(&self) -> T {
+        self.lock().clone()
+    }
+
+    pub fn set(&self, value: T) {
+        *self.lock() = value;
+    }
+
+    pub fn update<R, F: FnOnce(&mut T) -> R>(&self, update: F) -> R {
+        update(&mut self.lock())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, T> {
+        self.value.lock().unwrap()
+    }
+}
+
+type Key = (Id, TypeId);
+
+struct Entry {
+    // Always an 'Arc<Mutex<T>>' for the entry's type
+    value: Arc<dyn Any + Send + Sync>,
+    clone: fn(&(dyn Any + Send + Sync)) -> Entry,
+    last_used: u64,
+}
+
+impl Entry {
+    // A separate copy of the value, that won't change along with it
+    fn copy(&self) -> Entry {
+        let mut copy = (self.clone)(&*self.value);
+        copy.last_used = self.last_used;
+        copy
+    }
+}
+
+fn copy(entries: &HashMap<Key, Entry>) -> HashMap<Key, Entry> {
+    entries
+        .iter()
+        .map(|(key, entry)| (*key, entry.copy()))
+        .collect()
+}
+
+fn clone_entry<T: StateValue>(value: &(dyn Any + Send + Sync)) -> Entry {
+    let value = value.downcast_ref::<Mutex<T>>().unwrap();
+    let value = value.lock().unwrap().clone();
+    Entry {
+        value: Arc::new(Mutex::new(value)),
+        clone: clone_entry::<T>,
+        last_used: 0,
+    }
+}
+
+// State kept between frames, keyed by Id and type, for anything that doesn't fit in a message.
+// Entries are dropped once they haven't been used for a number of frames, so state belonging to
+// devices that have gone away doesn't pile up.
+pub(in crate::core) struct StateStore {
+    entries: Mutex<HashMap<Key, Entry>>,
+    // How many frames an entry can go unused before it's dropped
+    retention: u64,
+}
+
+impl Default for StateStore {
+    fn default() -> Self {
+        StateStore {
+            entries: Default::default(),
+            retention: 60,
+        }
+    }
+}
+
+impl StateStore {
+    pub fn get_or_insert_with<T: StateValue, F: FnOnce() -> T>(
+        &self,
+        id: Id,
+        frame: u64,
+        init: F,
+    ) -> State<T> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .entry((id, TypeId::of::<T>()))
+            .or_insert_with(|| Entry {
+                value: Arc::new(Mutex::new(init())),
+                clone: clone_entry::<T>,
+                last_used: frame,
+            });
+        entry.last_used = entry.last_used.max(frame);
+
+        let value = entry.value.clone().downcast::<Mutex<T>>().unwrap();
+        State { value }
+    }
+
+    // Adds (or replaces) a value, as if it had been used in the given frame
+    pub fn insert<T: StateValue>(&mut self, id: Id, value: T, frame: u64) {
+        let entry = Entry {
+            value: Arc::new(Mutex::new(value)),
+            clone: clone_entry::<T>,
+            last_used: frame,
+        };
+        let entries = self.entries.get_mut().unwrap();
+        entries.insert((id, TypeId::of::<T>()), entry);
+    }
+
+    // Moves everything from another store into this one, replacing values with the same Id and type
+    pub fn merge(&mut self, mut other: StateStore) {
+        let other = other.entries.get_mut().unwrap().drain();
+        self.entries.get_mut().unwrap().extend(other);
+    }
+
+    // Calls the function with every value in the store, along with its Id and type. The value is the
+    // 'Mutex<T>' that holds it.
+    pub fn for_each<F: FnMut(Id, TypeId, &(dyn Any + Send + Sync))>(&self, mut f: F) {
+        let entries = self.entries.lock().unwrap();
+        for ((id, type_id), entry) in entries.iter() {
+            f(*id, *type_id, &*entry.value);
+        }
+    }
+
+    pub fn retention(&self) -> u64 {
+        self.retention
+    }
+
+    pub fn set_retention(&mut self, frames: u64) {
+        self.retention = frames;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    // Drops everything that wasn't used in the last 'retention' frames, as of the given frame
+    pub fn collect(&mut self, frame: u64) {
+        let retention = self.retention;
+        let entries = self.entries.get_mut().unwrap();
+        entries.retain(|_, entry| frame.saturating_sub(entry.last_used) < retention);
+    }
+
+    pub fn snapshot(&self) -> StateSnapshot {
+        let entries = copy(&self.entries.lock().unwrap());
+        StateSnapshot { entries }
+    }
+
+    // Replaces everything in the store with the snapshot, as if it had just been used. Handles from
+    // before are left pointing at the old values.
+    pub fn restore(&mut self, snapshot: &StateSnapshot, frame: u64) {
+        let mut entries = copy(&snapshot.entries);
+        for entry in entries.values_mut() {
+            entry.last_used = frame;
+        }
+        *self.entries.get_mut().unwrap() = entries;
+    }
+}
+
+// A copy of everything in the state store at one point, which can be put back with
+// 'GuiContext::restore_state'
+pub struct StateSnapshot {
+    entries: HashMap<Key, Entry>,
+}
+
+impl StateSnapshot {
+    pub fn get<T: StateValue>(&self, id: Id) -> Option<T> {
+        let entry = self.entries.get(&(id, TypeId::of::<T>()))?;
+        let value = entry.value.downcast_ref::<Mutex<T>>()?;
+        let value = value.lock().unwrap().clone();
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Clone for StateSnapshot {
+    fn clone(&self) -> Self {
+        StateSnapshot {
+            entries: copy(&self.entries),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::util::ref_move::Ext;
+    use std::rc::Rc;
+
+    // Counts the frames it's been shown in, if it's shown
+    struct Counter(bool);
+    struct CounterRenderer;
+
+    impl Device for Counter {
+        fn type_id() -> TypeId {
+            TypeId::new(0x21623e7c_673a_4195_9041_f481f66551ce)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Counter"
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Vec<u32>> for CounterRenderer {
+        type Device = Counter;
+        type Layout = Option<State<u32>>;
+
+        fn layout<'thrd>(
+            &self,
+            device: Counter,
+            ctx: &mut LayoutContext<'thrd, 'frm, Vec<u32>>,
+        ) -> LayoutResult<Option<State<u32>>> {
+            let state = match device.0 {
+                true => Some(ctx.state::<u32>(Id::from("counter"))),
+                false => None,
+            };
+            if let Some(state) = &state {
+                state.update(|count| *count += 1);
+            }
+            ctx.layout(Size::zero(), state)
+        }
+
+        fn render<'ctx>(
+            &self,
+            state: Option<State<u32>>,
+            _ctx: RenderContext<'ctx, 'frm, Vec<u32>>,
+            canvas: &mut Vec<u32>,
+        ) {
+            canvas.extend(state.map(|state| state.get()));
+        }
+    }
+
+    fn frame(gui: &mut GuiContext<Vec<u32>>, show: bool) -> Option<u32> {
+        let mut canvas = Vec::new();
+        let window = Region::new(Point::zero(), Size::zero());
+        gui.render_window(window, Counter(show).move_anchor(), &mut canvas)
+            .unwrap();
+        canvas.pop()
+    }
+
+    #[test]
+    fn persistence() {
+        let mut gui = GuiContext::default();
+        gui.register::<Counter>(Rc::new(CounterRenderer));
+        gui.set_state_retention(2);
+
+        assert_eq!(frame(&mut gui, true), Some(1));
+        assert_eq!(frame(&mut gui, true), Some(2));
+        let snapshot = gui.snapshot_state();
+
+        // Skipping a frame isn't enough for it to be dropped, but skipping two is
+        assert_eq!(frame(&mut gui, false), None);
+        assert_eq!(frame(&mut gui, true), Some(3));
+        assert_eq!(gui.state_len(), 1);
+        frame(&mut gui, false);
+        frame(&mut gui, false);
+        assert_eq!(gui.state_len(), 0);
+        assert_eq!(frame(&mut gui, true), Some(1));
+
+        // Snapshots don't change along with the store
+        assert_eq!(snapshot.get::<u32>(Id::from("counter")), Some(2));
+        assert_eq!(snapshot.get::<i32>(Id::from("counter")), None);
+        gui.restore_state(&snapshot);
+        assert_eq!(frame(&mut gui, true), Some(3));
+        assert_eq!(snapshot.get::<u32>(Id::from("counter")), Some(2));
+
+        // State can be used from outside of a frame too
+        gui.state::<u32>(Id::from("counter")).set(10);
+        assert_eq!(frame(&mut gui, true), Some(11));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::core::id::Id;
+use crate::core::message::{AnyMessage, Message};
+use std::any::TypeId;
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+// A channel for broadcasting events of one type. Unlike messages, events aren't addressed to anyone:
+// any device can publish to a topic, and every device that reads the topic in the next frame sees all
+// of them, in the order they were published. Topics with the same Id but different types don't see
+// each other's events.
+pub struct Topic<T: Message> {
+    id: Id,
+    _phantom: PhantomData<fn(T) -> T>,
+}
+
+impl<T: Message> Topic<T> {
+    pub fn new<I: Into<Id>>(id: I) -> Self {
+        Topic {
+            id: id.into(),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub(in crate::core) fn key(&self) -> TopicKey {
+        (self.id, TypeId::of::<T>())
+    }
+}
+
+impl<T: Message> Clone for Topic<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Message> Copy for Topic<T> {}
+
+// Topics are told apart by their type as well as their Id
+pub(in crate::core) type TopicKey = (Id, TypeId);
+
+// An event along with where it falls in its topic's history
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopicEvent<T> {
+    // Counts up from zero in the order events were published, separately for each topic
+    pub sequence: u64,
+    // The first frame that could read the event
+    pub frame: u64,
+    pub value: T,
+}
+
+struct Stored {
+    sequence: u64,
+    frame: u64,
+    value: AnyMessage,
+}
+
+#[derive(Default)]
+struct Log {
+    events: VecDeque<Stored>,
+    next_sequence: u64,
+}
+
+// The recent events on every topic. Events published during a frame are held with the frame's messages
+// until it's over, then added here for the frames after it to read.
+pub(in crate::core) struct TopicStore {
+    // Events only have to be 'Send', so layout threads take turns reading them
+    logs: Mutex<HashMap<TopicKey, Log>>,
+    // How many events each topic keeps
+    history: usize,
+}
+
+impl Default for TopicStore {
+    fn default() -> Self {
+        TopicStore {
+            logs: Mutex::new(HashMap::new()),
+            history: 256,
+        }
+    }
+}
+
+impl TopicStore {
+    // Adds an event for the given frame (and the ones after it) to read, dropping the oldest event on
+    // the topic if it's full
+    pub fn publish(&mut self, topic: TopicKey, value: AnyMessage, frame: u64) {
+        let log = self.logs.get_mut().unwrap().entry(topic).or_default();
+        log.events.push_back(Stored {
+            sequence: log.next_sequence,
+            frame,
+            value,
+        });
+        log.next_sequence += 1;
+        while log.events.len() > self.history {
+            log.events.pop_front();
+        }
+    }
+
+    // The events first readable in the given frame, oldest first
+    pub fn events<T: Message>(&self, topic: &Topic<T>, frame: u64) -> Vec<T> {
+        let logs = self.logs.lock().unwrap();
+        stored(&logs, topic)
+            .filter(|stored| stored.frame == frame)
+            .filter_map(|stored| stored.value.downcast_ref::<T>().cloned())
+            .collect()
+    }
+
+    // Every event still in the topic's history from the given sequence number on, oldest first
+    pub fn events_since<T: Message>(&self, topic: &Topic<T>, sequence: u64) -> Vec<TopicEvent<T>> {
+        let logs = self.logs.lock().unwrap();
+        stored(&logs, topic)
+            .filter(|stored| stored.sequence >= sequence)
+            .filter_map(|stored| {
+                let value = stored.value.downcast_ref::<T>()?.clone();
+                Some(TopicEvent {
+                    sequence: stored.sequence,
+                    frame: stored.frame,
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    pub fn history(&self) -> usize {
+        self.history
+    }
+
+    pub fn set_history(&mut self, events: usize) {
+        self.history = events;
+        for log in self.logs.get_mut().unwrap().values_mut() {
+            while log.events.len() > events {
+                log.events.pop_front();
+            }
+        }
+    }
+}
+
+fn stored<'a, T: Message>(
+    logs: &'a HashMap<TopicKey, Log>,
+    topic: &Topic<T>,
+) -> impl Iterator<Item = &'a Stored> {
+    logs.get(&topic.key())
+        .into_iter()
+        .flat_map(|log| &log.events)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::util::ref_move::Ext;
+    use std::sync::Arc;
+
+    type Canvas = Vec<Vec<u32>>;
+
+    // Publishes its values (from parallel jobs, or one after another), and draws the events it read
+    // while laying out and while rendering
+    struct Feed(Vec<u32>, bool);
+    struct FeedRenderer;
+
+    impl Device for Feed {
+        fn type_id() -> TypeId {
+            TypeId::new(0xed235b97_4f8f_4baf_a0a6_f0bd8b99c8dd)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Feed"
+        }
+    }
+
+    fn topic() -> Topic<u32> {
+        Topic::new("feed")
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for FeedRenderer {
+        type Device = Feed;
+        type Layout = Vec<u32>;
+
+        fn layout<'thrd>(
+            &self,
+            Feed(values, parallel): Feed,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<Vec<u32>> {
+            let events = ctx.events(&topic());
+            if parallel {
+                let jobs = values.into_iter().map(|value| {
+                    move |ctx: &mut LayoutContext<Canvas>| {
+                        ctx.publish(&topic(), value);
+                        LayoutResult::None
+                    }
+                });
+                ctx.parallel(Size::zero(), jobs);
+            } else {
+                for value in values {
+                    ctx.publish(&topic(), value);
+                }
+            }
+            ctx.layout(Size::zero(), events)
+        }
+
+        fn render<'ctx>(
+            &self,
+            events: Vec<u32>,
+            ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            canvas.push(events);
+            canvas.push(ctx.events(&topic()));
+        }
+    }
+
+    fn frame(gui: &mut GuiContext<Canvas>, values: Vec<u32>, parallel: bool) -> Vec<u32> {
+        let mut canvas = Canvas::new();
+        let window = Region::new(Point::zero(), Size::zero());
+        gui.render_window(window, Feed(values, parallel).move_anchor(), &mut canvas)
+            .unwrap();
+        assert_eq!(canvas[0], canvas[1]);
+        canvas.remove(0)
+    }
+
+    #[test]
+    fn broadcast() {
+        let mut gui = GuiContext::default();
+        gui.register_shared::<Feed>(Arc::new(FeedRenderer));
+        gui.set_layout_threads(4);
+
+        // Events are read in the frame after they're published, followed by those published between
+        // the frames
+        assert_eq!(frame(&mut gui, vec![1, 2], false), vec![]);
+        gui.publish(&topic(), 0);
+        assert_eq!(gui.events(&topic()), vec![1, 2, 0]);
+        assert_eq!(frame(&mut gui, (3..11).collect(), true), vec![1, 2, 0]);
+        assert_eq!(frame(&mut gui, vec![], false), (3..11).collect::<Vec<_>>());
+        assert_eq!(frame(&mut gui, vec![], false), vec![]);
+
+        // Topics with the same Id but another type are separate, with their own sequence numbers and
+        // history
+        let other = Topic::<i32>::new("feed");
+        assert!(gui.events_since(&other, 0).is_empty());
+        gui.publish(&other, -1);
+        let event = TopicEvent {
+            sequence: 0,
+            frame: 4,
+            value: -1,
+        };
+        assert_eq!(gui.events_since(&other, 0), vec![event]);
+
+        // Only the most recent events are kept
+        gui.set_event_history(4);
+        let events = gui.events_since(&topic(), 0);
+        let sequences: Vec<u64> = events.iter().map(|event| event.sequence).collect();
+        assert_eq!(sequences, vec![7, 8, 9, 10]);
+        let event = TopicEvent {
+            sequence: 10,
+            frame: 2,
+            value: 10,
+        };
+        assert_eq!(gui.events_since(&topic(), 10), vec![event]);
+    }
+}
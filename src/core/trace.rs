@@ -0,0 +1,278 @@
+use crate::core::device::DeviceInfo;
+use crate::core::id::Id;
+use crate::core::message::MessageMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceKind {
+    // A message was written and kept for the next frame
+    Write,
+    // A message was written to an Outbox that nothing had called 'inbox' on, so it was dropped
+    Dropped,
+    // The mappings added to an Outbox with 'map' or 'map_from' ran, before its message was written
+    Map,
+    // A device read a message, and there was one
+    ReadHit,
+    // A device read a message, but there wasn't one
+    ReadMiss,
+}
+
+// Something that happened to a message during a frame. Writes and reads of the same Id can be matched
+// up to see why a device did or didn't react to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub kind: TraceKind,
+    pub id: Id,
+    pub message_type: &'static str,
+    // 'None' for messages written from outside of a device, like input
+    pub device: Option<DeviceInfo>,
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.device {
+            Some(device) => write!(f, "{}: ", device)?,
+            None => write!(f, "(outside of a device): ")?,
+        }
+        let action = match self.kind {
+            TraceKind::Write => "wrote",
+            TraceKind::Dropped => "dropped (unobserved)",
+            TraceKind::Map => "mapped",
+            TraceKind::ReadHit => "read",
+            TraceKind::ReadMiss => "missed",
+        };
+        write!(f, "{} {} {:?}", action, self.message_type, self.id)
+    }
+}
+
+// Everything recorded for one frame, in the order it happened. Messages written between frames (like
+// input) are part of the frame after them.
+pub struct FrameTrace {
+    pub frame: u64,
+    pub records: Vec<TraceRecord>,
+    // The messages the frame started with, to be fed back in when replaying
+    pub(in crate::core) incoming: MessageMap,
+}
+
+impl FrameTrace {
+    // How many messages the frame started with
+    pub fn incoming_len(&self) -> usize {
+        self.incoming.len()
+    }
+}
+
+// The frames recorded while 'GuiContext::set_message_tracing' was on, which can be played back with
+// 'GuiContext::replay'
+#[derive(Default)]
+pub struct MessageTrace {
+    pub(in crate::core) frames: Vec<FrameTrace>,
+}
+
+impl MessageTrace {
+    pub fn frames(&self) -> &[FrameTrace] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::util::ref_move::Ext;
+    use std::rc::Rc;
+
+    // Reads the count from the last frame, and writes it back one higher (mapping it to an Id that
+    // nothing reads)
+    struct Counter;
+    struct CounterRenderer;
+
+    impl Device for Counter {
+        fn type_id() -> TypeId {
+            TypeId::new(0xbf9d4a3b_1c0c_4da0_bf9b_265ab11d97ce)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Counter"
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Vec<u32>> for CounterRenderer {
+        type Device = Counter;
+        type Layout = u32;
+
+        fn layout<'thrd>(
+            &self,
+            _device: Counter,
+            ctx: &mut LayoutContext<'thrd, 'frm, Vec<u32>>,
+        ) -> LayoutResult<u32> {
+            let mut outbox = ctx.message::<u32>("count".into());
+            let count = ctx.read_message(outbox.inbox()).unwrap_or(0);
+            let echo = ctx.message::<u32>("echo".into());
+            outbox.map(move |value, mut writer| writer.write(echo, *value));
+            ctx.write_message(outbox, count + 1);
+            ctx.layout(Size::zero(), count)
+        }
+
+        fn render<'ctx>(
+            &self,
+            count: u32,
+            _ctx: RenderContext<'ctx, 'frm, Vec<u32>>,
+            canvas: &mut Vec<u32>,
+        ) {
+            canvas.push(count);
+        }
+    }
+
+    fn frame(gui: &mut GuiContext<Vec<u32>>) -> u32 {
+        let mut canvas = Vec::new();
+        let window = Region::new(Point::zero(), Size::zero());
+        gui.render_window(window, Counter.move_anchor(), &mut canvas)
+            .unwrap();
+        canvas[0]
+    }
+
+    fn record(kind: TraceKind, id: &str, device: Option<DeviceInfo>) -> TraceRecord {
+        TraceRecord {
+            kind,
+            id: id.into(),
+            message_type: "u32",
+            device,
+        }
+    }
+
+    #[test]
+    fn trace_and_replay() {
+        let mut gui = GuiContext::default();
+        gui.register::<Counter>(Rc::new(CounterRenderer));
+        gui.set_message_tracing(true);
+
+        assert_eq!(frame(&mut gui), 0);
+        let outbox = gui.message::<u32>("count".into());
+        outbox.inbox();
+        gui.write_message(outbox, 10);
+        assert_eq!(frame(&mut gui), 10);
+        gui.set_message_tracing(false);
+        assert_eq!(frame(&mut gui), 11);
+
+        let trace = gui.take_message_trace();
+        assert_eq!(trace.len(), 2);
+        let counter = Some(DeviceInfo::of::<Counter>());
+        assert_eq!(
+            trace.frames()[0].records,
+            vec![
+                record(TraceKind::ReadMiss, "count", counter),
+                record(TraceKind::Map, "count", counter),
+                record(TraceKind::Dropped, "echo", counter),
+                record(TraceKind::Write, "count", counter),
+            ]
+        );
+        let frame_1 = &trace.frames()[1];
+        assert_eq!(frame_1.frame, 1);
+        assert_eq!(frame_1.incoming_len(), 1);
+        assert_eq!(frame_1.records[0], record(TraceKind::Write, "count", None));
+        assert_eq!(
+            frame_1.records[1],
+            record(TraceKind::ReadHit, "count", counter)
+        );
+        assert_eq!(
+            frame_1.records[1].to_string(),
+            format!(
+                "{}: read u32 {:?}",
+                DeviceInfo::of::<Counter>(),
+                Id::from("count")
+            )
+        );
+
+        // Replayed frames start with the traced messages, whatever was written since
+        gui.replay(&trace);
+        assert_eq!(gui.replay_len(), 2);
+        let outbox = gui.message::<u32>("count".into());
+        outbox.inbox();
+        gui.write_message(outbox, 100);
+        assert_eq!(frame(&mut gui), 0);
+        assert_eq!(frame(&mut gui), 10);
+        assert_eq!(gui.replay_len(), 0);
+        assert_eq!(frame(&mut gui), 11);
+        assert!(gui.message_trace().is_empty());
+    }
+
+    // Draws how many key events it was sent
+    struct Keys;
+    struct KeysRenderer;
+
+    impl Device for Keys {
+        fn type_id() -> TypeId {
+            TypeId::new(0x3e8c57d1_92a4_4f0b_8d6e_b1f04c7a2e95)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Keys"
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Vec<u32>> for KeysRenderer {
+        type Device = Keys;
+        type Layout = u32;
+
+        fn layout<'thrd>(
+            &self,
+            _device: Keys,
+            ctx: &mut LayoutContext<'thrd, 'frm, Vec<u32>>,
+        ) -> LayoutResult<u32> {
+            let keyboard = ctx.message::<KeyboardInput>(global_keyboard_id());
+            let keyboard = ctx.read_message(&keyboard);
+            let count = keyboard.map_or(0, |keyboard| keyboard.events.len());
+            ctx.layout(Size::zero(), count as u32)
+        }
+
+        fn render<'ctx>(
+            &self,
+            count: u32,
+            _ctx: RenderContext<'ctx, 'frm, Vec<u32>>,
+            canvas: &mut Vec<u32>,
+        ) {
+            canvas.push(count);
+        }
+    }
+
+    fn keys_frame(gui: &mut GuiContext<Vec<u32>>) -> u32 {
+        let mut canvas = Vec::new();
+        let window = Region::new(Point::zero(), Size::zero());
+        gui.render_window(window, Keys.move_anchor(), &mut canvas)
+            .unwrap();
+        canvas[0]
+    }
+
+    #[test]
+    fn input_during_replay() {
+        let mut gui = GuiContext::default();
+        gui.register::<Keys>(Rc::new(KeysRenderer));
+        gui.set_message_tracing(true);
+        assert_eq!(keys_frame(&mut gui), 0);
+        gui.set_message_tracing(false);
+        let trace = gui.take_message_trace();
+
+        // The replayed frame gets what was traced, and the input goes to the one after it
+        gui.replay(&trace);
+        gui.text_input("a");
+        gui.text_input("b");
+        assert_eq!(keys_frame(&mut gui), 0);
+        assert_eq!(keys_frame(&mut gui), 2);
+        assert_eq!(keys_frame(&mut gui), 0);
+    }
+}
@@ -0,0 +1,229 @@
+// Basic layout devices. Their renderers only place their children, so they work with any canvas
+// (except for 'Scroll', which needs to be able to clip it). 'Label' and 'TextInput' draw, so they need
+// 'Draw', as does 'PlaceholderRenderer' (which 'GuiContext::new' registers).
+
+mod align;
+pub use self::align::{Align, AlignLayout, AlignRenderer};
+
+mod grid;
+pub use self::grid::{Grid, GridLayout, GridRegion, GridRenderer, Track};
+
+mod label;
+pub use self::label::{Label, LabelLayout, LabelRenderer};
+
+mod layered;
+pub use self::layered::{Layered, LayeredLayout, LayeredRenderer};
+
+mod overlay;
+pub use self::overlay::{Overlay, OverlayRenderer};
+
+mod padding;
+pub use self::padding::{Padding, PaddingLayout, PaddingRenderer};
+
+mod scroll;
+pub use self::scroll::{Scroll, ScrollAxis, ScrollLayout, ScrollRenderer};
+
+mod placeholder;
+pub use self::placeholder::PlaceholderRenderer;
+
+mod stack;
+pub use self::stack::{Direction, Stack, StackLayout, StackRenderer};
+
+mod text_input;
+pub use self::text_input::{
+    Clipboard, Movement, TextEditor, TextInput, TextInputLayout, TextInputRenderer,
+};
+
+use crate::prelude::*;
+use std::rc::Rc;
+
+// Registers the renderers for every device in this module that works with any canvas.
+// 'Scroll' needs a canvas that implements 'Clip', and 'Label' and 'TextInput' one that implements
+// 'Draw' (along with fonts or a clipboard), so they have to be registered separately.
+pub fn register<C: 'static>(gui: &mut GuiContext<C>) {
+    gui.register::<Align>(Rc::new(AlignRenderer));
+    gui.register::<Grid>(Rc::new(GridRenderer));
+    gui.register::<Layered>(Rc::new(LayeredRenderer));
+    gui.register::<Overlay>(Rc::new(OverlayRenderer));
+    gui.register::<Padding>(Rc::new(PaddingRenderer));
+    gui.register::<Stack>(Rc::new(StackRenderer));
+}
+
+// What the device tests share: a canvas recording the regions things are drawn in, and devices that
+// sit in a tree to be measured
+#[cfg(test)]
+mod fixtures {
+    use super::*;
+    use crate::canvas::Clip;
+    use crate::util::ref_move::Ext;
+
+    pub type Canvas = Vec<(&'static str, Region)>;
+
+    impl Clip for Canvas {
+        fn push_clip(&mut self, region: Region) {
+            self.push(("clip", region));
+        }
+
+        fn pop_clip(&mut self) {
+            self.push(("unclip", Region::new(Point::zero(), Size::zero())));
+        }
+    }
+
+    // Lays out the tree from its function, and draws its minimum size before anything else (followed
+    // by whatever the function adds to the canvas)
+    pub struct Root(pub fn(&mut LayoutContext<Canvas>, &mut Canvas) -> LayoutResult<()>);
+
+    // Draws its name in its region, which it can take keyboard focus in
+    pub struct Leaf(pub &'static str, pub Size);
+
+    // Draws the space it was given to lay out in, in place of its region
+    pub struct Probe(pub &'static str);
+
+    impl Device for Root {
+        fn type_id() -> TypeId {
+            TypeId::new(1)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Root"
+        }
+    }
+
+    impl Device for Leaf {
+        fn type_id() -> TypeId {
+            TypeId::new(2)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Leaf"
+        }
+    }
+
+    impl Device for Probe {
+        fn type_id() -> TypeId {
+            TypeId::new(3)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Probe"
+        }
+    }
+
+    struct RootRenderer;
+    struct LeafRenderer;
+    struct ProbeRenderer;
+
+    impl<'frm> Renderer<'frm, Canvas> for RootRenderer {
+        type Device = Root;
+        type Layout = (LayoutNode, Canvas);
+
+        fn layout<'thrd>(
+            &self,
+            device: Root,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<(LayoutNode, Canvas)> {
+            let mut extra = Canvas::new();
+            match (device.0)(ctx, &mut extra) {
+                LayoutResult::CompleteNode(node) => {
+                    extra.insert(0, ("min", Region::new(Point::zero(), node.min_size)));
+                    ctx.layout(node.min_size, (node, extra))
+                }
+                _ => LayoutResult::None,
+            }
+        }
+
+        fn render<'ctx>(
+            &self,
+            (node, extra): (LayoutNode, Canvas),
+            ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            canvas.extend(extra);
+            ctx.render(node, ctx.region(), canvas);
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for LeafRenderer {
+        type Device = Leaf;
+        type Layout = &'static str;
+
+        fn layout<'thrd>(
+            &self,
+            device: Leaf,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<&'static str> {
+            ctx.layout(device.1, device.0)
+        }
+
+        fn render<'ctx>(
+            &self,
+            layout: &'static str,
+            ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            ctx.focusable(Id::from(layout), ctx.region());
+            canvas.push((layout, ctx.region()));
+        }
+    }
+
+    impl<'frm> Renderer<'frm, Canvas> for ProbeRenderer {
+        type Device = Probe;
+        type Layout = (&'static str, Size);
+
+        fn layout<'thrd>(
+            &self,
+            device: Probe,
+            ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+        ) -> LayoutResult<(&'static str, Size)> {
+            ctx.layout(Size::zero(), (device.0, ctx.max_size()))
+        }
+
+        fn render<'ctx>(
+            &self,
+            (name, max_size): (&'static str, Size),
+            _ctx: RenderContext<'ctx, 'frm, Canvas>,
+            canvas: &mut Canvas,
+        ) {
+            canvas.push((name, Region::new(Point::zero(), max_size)));
+        }
+    }
+
+    // A context with every device in this module registered, along with the fixtures
+    pub fn gui() -> GuiContext<Canvas> {
+        let mut gui = GuiContext::default();
+        register(&mut gui);
+        gui.register::<Scroll>(Rc::new(ScrollRenderer));
+        gui.register::<Root>(Rc::new(RootRenderer));
+        gui.register::<Leaf>(Rc::new(LeafRenderer));
+        gui.register::<Probe>(Rc::new(ProbeRenderer));
+        gui
+    }
+
+    pub fn render(root: Root, window: Size) -> Canvas {
+        render_frame(&mut gui(), root, window)
+    }
+
+    pub fn render_frame(gui: &mut GuiContext<Canvas>, root: Root, window: Size) -> Canvas {
+        let mut canvas = Canvas::new();
+        let region = Region::new(Point::zero(), window);
+        gui.render_window(region, root.move_anchor(), &mut canvas)
+            .unwrap();
+        canvas
+    }
+
+    pub fn region(x: f32, y: f32, width: f32, height: f32) -> Region {
+        Region::new(Point::new(x, y), Size::new(width, height))
+    }
+}
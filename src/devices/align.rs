@@ -0,0 +1,104 @@
+use crate::prelude::*;
+
+// Places the child in its default socket at its minimum size, aligned within the region.
+#[derive(Default)]
+pub struct Align {
+    pub horizontal: HAlign,
+    pub vertical: VAlign,
+}
+
+impl Align {
+    pub fn new(horizontal: HAlign, vertical: VAlign) -> Self {
+        Align {
+            horizontal,
+            vertical,
+        }
+    }
+
+    pub fn center() -> Self {
+        Align::new(HAlign::Center, VAlign::Center)
+    }
+}
+
+impl Device for Align {
+    fn type_id() -> TypeId {
+        TypeId::new(0x508b1b89_d659_428a_987a_6f39d12249fd)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "Align"
+    }
+}
+
+pub struct AlignLayout {
+    horizontal: HAlign,
+    vertical: VAlign,
+    child: Option<LayoutNode>,
+}
+
+pub struct AlignRenderer;
+
+impl<'frm, C: 'static> Renderer<'frm, C> for AlignRenderer {
+    type Device = Align;
+    type Layout = AlignLayout;
+
+    fn layout<'thrd>(
+        &self,
+        device: Align,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<AlignLayout> {
+        let mut child = None;
+        ctx.socket(SocketName::default(), ctx.max_size(), &mut child);
+        if ctx.is_pending() {
+            return ctx.defer(device);
+        }
+
+        let min_size = child.as_ref().map_or(Size::zero(), |child| child.min_size);
+        let layout = AlignLayout {
+            horizontal: device.horizontal,
+            vertical: device.vertical,
+            child,
+        };
+        ctx.layout(min_size, layout)
+    }
+
+    fn render<'ctx>(&self, layout: AlignLayout, ctx: RenderContext<'ctx, 'frm, C>, canvas: &mut C) {
+        if let Some(child) = layout.child {
+            let size = child.min_size.min(ctx.region().size);
+            let region = layout.horizontal.align_horizontally(size, ctx.region());
+            let region = layout.vertical.align_vertically(size, region);
+            ctx.render(child, region, canvas);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::fixtures::*;
+    use crate::util::ref_move::Ext;
+
+    #[test]
+    fn center() {
+        let root = Root(|ctx, _| {
+            let tree = |mut visitor: LayoutTreeVisitor<Canvas>| {
+                let leaf = Leaf("centered", Size::new(4_f32, 6_f32)).move_anchor();
+                visitor.device(SocketName::default(), leaf);
+            };
+            ctx.device_tree(ctx.max_size(), Align::center().move_anchor(), tree)
+        });
+
+        let canvas = render(root, Size::new(20_f32, 50_f32));
+        assert_eq!(
+            canvas,
+            vec![
+                ("min", region(0_f32, 0_f32, 4_f32, 6_f32)),
+                ("centered", region(8_f32, 22_f32, 4_f32, 6_f32)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,615 @@
+use crate::prelude::*;
+
+// How a row or column is sized
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Track {
+    // Just big enough for the largest thing in it
+    Auto,
+    // Takes this proportion of the space left over after the auto tracks (like star-sizing in xaml)
+    Rem(f32),
+}
+
+// A named area of the grid, whose socket's children are placed over the cells it covers.
+// Columns and rows are numbered from 0, but -1 and 'len' may also be used to refer to the auto-sized
+// overflow tracks just outside either edge of the grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridRegion {
+    pub name: SocketName,
+    pub column: isize,
+    pub row: isize,
+    pub column_span: usize,
+    pub row_span: usize,
+}
+
+impl GridRegion {
+    pub fn new(name: SocketName, column: isize, row: isize) -> Self {
+        GridRegion {
+            name,
+            column,
+            row,
+            column_span: 1,
+            row_span: 1,
+        }
+    }
+
+    pub fn span(mut self, columns: usize, rows: usize) -> Self {
+        self.column_span = columns;
+        self.row_span = rows;
+        self
+    }
+}
+
+// Lays out its children in a grid of rows and columns, as described in 'notes/grid.md'.
+// If no rows or columns are given, there's a single auto-sized one.
+#[derive(Default)]
+pub struct Grid {
+    pub columns: Vec<Track>,
+    pub rows: Vec<Track>,
+    pub regions: Vec<GridRegion>,
+
+    // The children of the regions that were filled before the grid deferred, which have already been
+    // taken out of the context
+    filled: Vec<(SocketName, Vec<LayoutNode>)>,
+}
+
+impl Grid {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn column(mut self, track: Track) -> Self {
+        self.columns.push(track);
+        self
+    }
+
+    pub fn row(mut self, track: Track) -> Self {
+        self.rows.push(track);
+        self
+    }
+
+    pub fn region(mut self, region: GridRegion) -> Self {
+        self.regions.push(region);
+        self
+    }
+}
+
+impl Device for Grid {
+    fn type_id() -> TypeId {
+        TypeId::new(0xb9feeb7e_2e0a_4b8d_a730_c6284e7f58bf)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "Grid"
+    }
+}
+
+// The cells covered by a region, as a range of tracks (including the overflow tracks) on each axis
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    fn new(index: isize, span: usize, len: usize) -> Self {
+        // Track 0 is the leading overflow track, and 'len + 1' the trailing one
+        let start = (index + 1).max(0).min(len as isize + 1) as usize;
+        let end = (start + span.max(1)).min(len + 2);
+        Span { start, end }
+    }
+}
+
+// The sizes of the tracks along one axis, before the space left over for rem tracks is known
+struct Axis {
+    auto: Vec<f32>,
+    rem: Vec<f32>,
+    min_rem_unit: f32,
+}
+
+impl Axis {
+    fn new(tracks: &[Track]) -> Self {
+        let tracks: &[Track] = if tracks.is_empty() {
+            &[Track::Auto]
+        } else {
+            tracks
+        };
+
+        let mut rem = vec![0_f32; tracks.len() + 2];
+        for (i, track) in tracks.iter().enumerate() {
+            if let Track::Rem(weight) = *track {
+                rem[i + 1] = weight.max(0_f32);
+            }
+        }
+
+        Axis {
+            auto: vec![0_f32; tracks.len() + 2],
+            rem,
+            min_rem_unit: 0_f32,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.auto.len() - 2
+    }
+
+    // Grows the tracks in the span so they can fit something of the given size
+    fn fit(&mut self, span: &Span, size: f32) {
+        let range = span.start..span.end;
+        let auto: f32 = self.auto[range.clone()].iter().sum();
+        let rem: f32 = self.rem[range.clone()].iter().sum();
+        let deficit = size - auto;
+        if deficit <= 0_f32 {
+            return;
+        }
+
+        if rem > 0_f32 {
+            self.min_rem_unit = self.min_rem_unit.max(deficit / rem);
+        } else if let Some(last) = range.rev().find(|&i| self.rem[i] == 0_f32) {
+            self.auto[last] += deficit;
+        }
+    }
+
+    fn has_rem(&self, span: &Span) -> bool {
+        self.rem[span.start..span.end]
+            .iter()
+            .any(|&rem| rem > 0_f32)
+    }
+
+    // The space something in the span can take when the grid is 'length' long: all of it if the span
+    // only covers auto tracks, or else the auto tracks in it plus its share of what they leave over
+    fn span_length(&self, span: &Span, length: f32) -> f32 {
+        let range = span.start..span.end;
+        let rem: f32 = self.rem[range.clone()].iter().sum();
+        if rem == 0_f32 {
+            return length;
+        }
+
+        let auto: f32 = self.auto[range].iter().sum();
+        auto + rem * self.rem_unit(length)
+    }
+
+    fn rem_unit(&self, length: f32) -> f32 {
+        let total_rem = self.total_rem();
+        if total_rem > 0_f32 {
+            ((length - self.inner_auto()) / total_rem).max(self.min_rem_unit)
+        } else {
+            0_f32
+        }
+    }
+
+    fn inner_auto(&self) -> f32 {
+        self.auto[1..=self.len()].iter().sum()
+    }
+
+    fn total_rem(&self) -> f32 {
+        self.rem.iter().sum()
+    }
+
+    // The smallest the grid can be along this axis, not counting the overflow tracks
+    fn min_length(&self) -> f32 {
+        self.inner_auto() + self.min_rem_unit * self.total_rem()
+    }
+
+    // The position of each boundary between tracks, when the grid starts at 'start' and is 'length' long
+    fn boundaries(&self, start: f32, length: f32) -> Vec<f32> {
+        let rem_unit = self.rem_unit(length);
+        let mut boundaries = Vec::with_capacity(self.auto.len() + 1);
+        let mut pos = start - self.auto[0];
+        boundaries.push(pos);
+        for (auto, rem) in self.auto.iter().zip(&self.rem) {
+            pos += auto + rem * rem_unit;
+            boundaries.push(pos);
+        }
+
+        boundaries
+    }
+}
+
+// Grows the tracks to fit every cell's children
+fn fit(columns: &mut Axis, rows: &mut Axis, cells: &[(Span, Span, Vec<LayoutNode>)]) {
+    // Fit the regions covering a single track first, so spanning regions only grow them if they must
+    let sizes: Vec<Size> = cells
+        .iter()
+        .map(|(_, _, children)| {
+            let size = Size::zero();
+            children
+                .iter()
+                .fold(size, |size, child| size.max(child.min_size))
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..cells.len()).collect();
+    order.sort_by_key(|&i| cells[i].0.end - cells[i].0.start);
+    for &i in &order {
+        columns.fit(&cells[i].0, sizes[i].width);
+    }
+
+    order.sort_by_key(|&i| cells[i].1.end - cells[i].1.start);
+    for &i in &order {
+        rows.fit(&cells[i].1, sizes[i].height);
+    }
+}
+
+// Defers the grid, holding on to the children of every region that's been filled (or has nothing to
+// fill it with), since they can't be taken from the context again
+fn defer<'thrd, 'frm, C: 'static>(
+    mut device: Grid,
+    names: &[SocketName],
+    cells: Vec<(Span, Span, Vec<LayoutNode>)>,
+    ctx: &mut LayoutContext<'thrd, 'frm, C>,
+) -> LayoutResult<GridLayout> {
+    for (name, (_, _, children)) in names.iter().zip(cells) {
+        if ctx.socket_children_len(*name) == 0 {
+            device.filled.push((*name, children));
+        }
+    }
+
+    ctx.defer(device)
+}
+
+pub struct GridLayout {
+    columns: Axis,
+    rows: Axis,
+    cells: Vec<(Span, Span, Vec<LayoutNode>)>,
+}
+
+pub struct GridRenderer;
+
+impl<'frm, C: 'static> Renderer<'frm, C> for GridRenderer {
+    type Device = Grid;
+    type Layout = GridLayout;
+
+    fn layout<'thrd>(
+        &self,
+        mut device: Grid,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<GridLayout> {
+        let mut columns = Axis::new(&device.columns);
+        let mut rows = Axis::new(&device.rows);
+        let mut filled = std::mem::take(&mut device.filled);
+
+        // A socket can only be filled once, so a region reusing another's name is left out
+        let mut names = Vec::with_capacity(device.regions.len());
+        let mut cells = Vec::with_capacity(device.regions.len());
+        for region in &device.regions {
+            if names.contains(&region.name) {
+                ctx.report_error(BuoyError::DuplicateSocket {
+                    device: DeviceInfo::of::<Grid>(),
+                    socket: region.name,
+                });
+                continue;
+            }
+
+            names.push(region.name);
+            let column = Span::new(region.column, region.column_span, columns.len());
+            let row = Span::new(region.row, region.row_span, rows.len());
+            let children = match filled.iter().position(|(name, _)| *name == region.name) {
+                Some(i) => filled.swap_remove(i).1,
+                None => Vec::new(),
+            };
+            cells.push((column, row, children));
+        }
+
+        // Regions only covering auto tracks can take all of the space there is. The others are limited
+        // to their tracks' share of it, which depends on how big those auto tracks turn out to be.
+        let max_size = ctx.max_size();
+        let auto: Vec<bool> = cells
+            .iter()
+            .map(|(column, row, _)| !columns.has_rem(column) && !rows.has_rem(row))
+            .collect();
+        for (i, (_, _, children)) in cells.iter_mut().enumerate() {
+            if auto[i] {
+                ctx.socket(names[i], max_size, children);
+            }
+        }
+
+        if ctx.is_pending() {
+            return defer(device, &names, cells, ctx);
+        }
+
+        fit(&mut columns, &mut rows, &cells);
+        for (i, (column, row, children)) in cells.iter_mut().enumerate() {
+            if !auto[i] {
+                let size = Size::new(
+                    columns.span_length(column, max_size.width),
+                    rows.span_length(row, max_size.height),
+                );
+                ctx.socket(names[i], size, children);
+            }
+        }
+
+        if ctx.is_pending() {
+            return defer(device, &names, cells, ctx);
+        }
+
+        let mut columns = Axis::new(&device.columns);
+        let mut rows = Axis::new(&device.rows);
+        fit(&mut columns, &mut rows, &cells);
+
+        let min_size = Size::new(columns.min_length(), rows.min_length());
+        let layout = GridLayout {
+            columns,
+            rows,
+            cells,
+        };
+        ctx.layout(min_size, layout)
+    }
+    fn render<'ctx>(&self, layout: GridLayout, ctx: RenderContext<'ctx, 'frm, C>, canvas: &mut C) {
+        let region = ctx.region();
+        let columns = layout.columns.boundaries(region.pos.x, region.size.width);
+        let rows = layout.rows.boundaries(region.pos.y, region.size.height);
+
+        for (column, row, children) in layout.cells {
+            let pos = Point::new(columns[column.start], rows[row.start]);
+            let size = Size::new(
+                columns[column.end] - columns[column.start],
+                rows[row.end] - rows[row.start],
+            );
+
+            for child in children {
+                ctx.render(child, Region::new(pos, size), canvas);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::fixtures::*;
+    use crate::devices::Overlay;
+    use crate::util::ref_move::Ext;
+    use std::rc::Rc;
+
+    fn regions_tree(ctx: &mut LayoutContext<Canvas>, _: &mut Canvas) -> LayoutResult<()> {
+        let grid = Grid::new()
+            .column(Track::Auto)
+            .column(Track::Rem(1_f32))
+            .column(Track::Rem(3_f32))
+            .row(Track::Auto)
+            .row(Track::Rem(1_f32))
+            .region(GridRegion::new("a".into(), 0, 0))
+            .region(GridRegion::new("b".into(), 1, 0).span(2, 1))
+            .region(GridRegion::new("c".into(), 2, 1))
+            .region(GridRegion::new("side".into(), -1, 1));
+
+        let tree = |mut visitor: LayoutTreeVisitor<Canvas>| {
+            let cells = [
+                ("a", Size::new(10_f32, 5_f32)),
+                ("b", Size::new(12_f32, 4_f32)),
+                ("c", Size::new(3_f32, 3_f32)),
+                ("side", Size::new(4_f32, 2_f32)),
+            ];
+            for (name, size) in cells.iter() {
+                visitor.device((*name).into(), Leaf(name, *size).move_anchor());
+            }
+        };
+
+        ctx.device_tree(ctx.max_size(), grid.move_anchor(), tree)
+    }
+
+    fn cells_tree(ctx: &mut LayoutContext<Canvas>, _: &mut Canvas) -> LayoutResult<()> {
+        let grid = Grid::new()
+            .column(Track::Auto)
+            .column(Track::Rem(1_f32))
+            .column(Track::Rem(3_f32))
+            .row(Track::Auto)
+            .row(Track::Rem(1_f32))
+            .region(GridRegion::new("a".into(), 0, 0))
+            .region(GridRegion::new("cell".into(), 1, 1))
+            .region(GridRegion::new("span".into(), 1, 0).span(2, 1))
+            .region(GridRegion::new("side".into(), -1, 1));
+
+        let tree = |mut visitor: LayoutTreeVisitor<Canvas>| {
+            let a = Leaf("a", Size::new(10_f32, 5_f32)).move_anchor();
+            visitor.device("a".into(), a);
+            for name in ["cell", "span", "side"] {
+                visitor.device(name.into(), Probe(name).move_anchor());
+            }
+        };
+
+        ctx.device_tree(ctx.max_size(), grid.move_anchor(), tree)
+    }
+
+    #[test]
+    fn regions() {
+        let canvas = render(Root(regions_tree), Size::new(40_f32, 30_f32));
+        assert_eq!(
+            canvas,
+            vec![
+                ("min", region(0_f32, 0_f32, 22_f32, 8_f32)),
+                ("a", region(0_f32, 0_f32, 10_f32, 5_f32)),
+                ("b", region(10_f32, 0_f32, 30_f32, 5_f32)),
+                ("c", region(17.5_f32, 5_f32, 22.5_f32, 25_f32)),
+                ("side", region(-4_f32, 5_f32, 4_f32, 25_f32)),
+            ]
+        );
+    }
+
+    #[test]
+    fn cells() {
+        // Regions in rem tracks are laid out in their share of what's left after the auto tracks, and
+        // regions only in auto tracks can take all of the space
+        let canvas = render(Root(cells_tree), Size::new(40_f32, 30_f32));
+        assert_eq!(
+            canvas,
+            vec![
+                ("min", region(0_f32, 0_f32, 10_f32, 5_f32)),
+                ("a", region(0_f32, 0_f32, 10_f32, 5_f32)),
+                ("cell", region(0_f32, 0_f32, 7.5_f32, 25_f32)),
+                ("span", region(0_f32, 0_f32, 30_f32, 30_f32)),
+                ("side", region(0_f32, 0_f32, 40_f32, 25_f32)),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_region() {
+        struct Twice;
+
+        impl Device for Twice {
+            fn type_id() -> TypeId {
+                TypeId::new(4)
+            }
+
+            fn package_name() -> &'static str {
+                "buoy"
+            }
+
+            fn type_name() -> &'static str {
+                "Twice"
+            }
+        }
+
+        impl<'frm> Renderer<'frm, Canvas> for Twice {
+            type Device = Twice;
+            type Layout = ();
+
+            fn layout<'thrd>(
+                &self,
+                _device: Twice,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<()> {
+                let grid = Grid::new()
+                    .column(Track::Auto)
+                    .column(Track::Auto)
+                    .region(GridRegion::new("a".into(), 0, 0))
+                    .region(GridRegion::new("a".into(), 1, 0));
+                ctx.device_tree(ctx.max_size(), grid.move_anchor(), ());
+                ctx.layout(Size::zero(), ())
+            }
+
+            fn render<'ctx>(&self, _: (), _: RenderContext<'ctx, 'frm, Canvas>, _: &mut Canvas) {}
+        }
+
+        let mut gui = gui();
+        gui.register::<Twice>(Rc::new(Twice));
+        let region = Region::new(Point::zero(), Size::new(10_f32, 10_f32));
+        let result = gui.render_window(region, Twice.move_anchor(), &mut Canvas::new());
+        assert_eq!(
+            result,
+            Err(BuoyError::DuplicateSocket {
+                device: DeviceInfo::of::<Grid>(),
+                socket: "a".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn defer_in_rem_region() {
+        // Waits for a message, then draws it in its region
+        struct Reader(Inbox<f32>);
+        struct ReaderRenderer;
+
+        // Writes a message, and draws nothing
+        struct Writer(Outbox<f32>);
+        struct WriterRenderer;
+
+        impl Device for Reader {
+            fn type_id() -> TypeId {
+                TypeId::new(5)
+            }
+
+            fn package_name() -> &'static str {
+                "buoy"
+            }
+
+            fn type_name() -> &'static str {
+                "Reader"
+            }
+        }
+
+        impl Device for Writer {
+            fn type_id() -> TypeId {
+                TypeId::new(6)
+            }
+
+            fn package_name() -> &'static str {
+                "buoy"
+            }
+
+            fn type_name() -> &'static str {
+                "Writer"
+            }
+        }
+
+        impl<'frm> Renderer<'frm, Canvas> for ReaderRenderer {
+            type Device = Reader;
+            type Layout = ();
+
+            fn layout<'thrd>(
+                &self,
+                device: Reader,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<()> {
+                match ctx.poll_message(device.0) {
+                    Some(_) => ctx.layout(Size::zero(), ()),
+                    None => ctx.defer(device),
+                }
+            }
+
+            fn render<'ctx>(
+                &self,
+                _: (),
+                ctx: RenderContext<'ctx, 'frm, Canvas>,
+                canvas: &mut Canvas,
+            ) {
+                canvas.push(("reader", ctx.region()));
+            }
+        }
+
+        impl<'frm> Renderer<'frm, Canvas> for WriterRenderer {
+            type Device = Writer;
+            type Layout = ();
+
+            fn layout<'thrd>(
+                &self,
+                device: Writer,
+                ctx: &mut LayoutContext<'thrd, 'frm, Canvas>,
+            ) -> LayoutResult<()> {
+                ctx.write_message(device.0, 1_f32);
+                ctx.layout(Size::zero(), ())
+            }
+
+            fn render<'ctx>(&self, _: (), _: RenderContext<'ctx, 'frm, Canvas>, _: &mut Canvas) {}
+        }
+
+        // The grid has to defer after filling its auto region, because its rem region is waiting on
+        // a writer that comes after it
+        let root = Root(|ctx, _| {
+            let outbox = ctx.message::<f32>("value".into());
+            let inbox = outbox.inbox();
+            let grid = Grid::new()
+                .column(Track::Auto)
+                .column(Track::Rem(1_f32))
+                .region(GridRegion::new("a".into(), 0, 0))
+                .region(GridRegion::new("b".into(), 1, 0));
+            let tree = move |mut visitor: LayoutTreeVisitor<Canvas>| {
+                let cells = move |mut visitor: LayoutTreeVisitor<Canvas>| {
+                    let a = Leaf("a", Size::new(10_f32, 5_f32)).move_anchor();
+                    visitor.device("a".into(), a);
+                    visitor.device("b".into(), Reader(inbox).move_anchor());
+                };
+                visitor.device_tree(SocketName::default(), grid.move_anchor(), cells);
+                visitor.device(SocketName::default(), Writer(outbox).move_anchor());
+            };
+            ctx.device_tree(ctx.max_size(), Overlay.move_anchor(), tree)
+        });
+
+        let mut gui = gui();
+        gui.register::<Reader>(Rc::new(ReaderRenderer));
+        gui.register::<Writer>(Rc::new(WriterRenderer));
+        let canvas = render_frame(&mut gui, root, Size::new(40_f32, 30_f32));
+        assert_eq!(
+            canvas,
+            vec![
+                ("min", region(0_f32, 0_f32, 10_f32, 5_f32)),
+                ("a", region(0_f32, 0_f32, 10_f32, 5_f32)),
+                ("reader", region(10_f32, 0_f32, 30_f32, 5_f32)),
+            ]
+        );
+    }
+}
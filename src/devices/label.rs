@@ -0,0 +1,125 @@
+use crate::canvas::{Color, Draw};
+use crate::prelude::*;
+use crate::text::{FontCollection, TextLayout};
+use std::rc::Rc;
+
+// A paragraph (or several, separated by line breaks) of text, wrapped to the width it's given. Its
+// minimum size is the size of the wrapped text, so it grows taller as it gets narrower.
+pub struct Label {
+    pub text: String,
+    pub font_size: f32,
+    pub color: Color,
+}
+
+impl Label {
+    pub fn new(text: &str) -> Self {
+        Label {
+            text: text.to_string(),
+            font_size: 16_f32,
+            color: Color::BLACK,
+        }
+    }
+
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Device for Label {
+    fn type_id() -> TypeId {
+        TypeId::new(0x93b9f74f_f828_4aef_9f42_9027fd5fa577)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "Label"
+    }
+}
+
+pub struct LabelLayout {
+    text: TextLayout,
+    color: Color,
+}
+
+// Lays out labels with the given fonts, which are tried in order for each character
+pub struct LabelRenderer {
+    fonts: Rc<FontCollection>,
+}
+
+impl LabelRenderer {
+    pub fn new(fonts: Rc<FontCollection>) -> Self {
+        LabelRenderer { fonts }
+    }
+}
+
+impl<'frm, C: Draw + 'static> Renderer<'frm, C> for LabelRenderer {
+    type Device = Label;
+    type Layout = LabelLayout;
+
+    fn layout<'thrd>(
+        &self,
+        device: Label,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<LabelLayout> {
+        let max_width = ctx.max_size().width;
+        let text = TextLayout::new(&self.fonts, &device.text, device.font_size, max_width);
+        let min_size = text.size;
+        let layout = LabelLayout {
+            text,
+            color: device.color,
+        };
+        ctx.layout(min_size, layout)
+    }
+
+    fn render<'ctx>(&self, layout: LabelLayout, ctx: RenderContext<'ctx, 'frm, C>, canvas: &mut C) {
+        for run in layout.text.glyph_runs(ctx.region(), layout.color) {
+            canvas.draw_glyphs(run);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Command, DisplayList};
+    use crate::text::{test_font, Font};
+    use crate::util::ref_move::Ext;
+
+    #[test]
+    fn wrapping() {
+        let mut fonts = FontCollection::new();
+        let font = test_font(&[('a', 500), ('b', 500), (' ', 250)], &[]);
+        fonts.add(Font::from_bytes(font).unwrap());
+        let mut gui = GuiContext::default();
+        gui.register::<Label>(Rc::new(LabelRenderer::new(Rc::new(fonts))));
+
+        // Each word ends up on its own line, at the top left of the region
+        let mut canvas = DisplayList::new();
+        let window = Region::new(Point::new(5_f32, 5_f32), Size::new(25_f32, 100_f32));
+        let label = Label::new("ab ba ab").with_font_size(20_f32);
+        gui.render_window(window, label.move_anchor(), &mut canvas)
+            .unwrap();
+
+        let glyphs: Vec<(char, Point)> = canvas
+            .commands()
+            .iter()
+            .flat_map(|command| match command {
+                Command::Glyphs(run) => run.glyphs.clone(),
+                _ => Vec::new(),
+            })
+            .map(|glyph| (glyph.ch, glyph.origin))
+            .collect();
+        let lines: Vec<f32> = glyphs.iter().map(|(_, origin)| origin.y).collect();
+        assert_eq!(lines, vec![21_f32, 21_f32, 41_f32, 41_f32, 61_f32, 61_f32]);
+        assert_eq!(glyphs[3], ('a', Point::new(15_f32, 41_f32)));
+    }
+}
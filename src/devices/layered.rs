@@ -0,0 +1,127 @@
+use crate::prelude::*;
+
+// Draws the children in its default socket on top of each other like 'Overlay', but on another layer,
+// so they appear above (and block pointer input to) anything on the layers below. Useful for popups.
+pub struct Layered(pub Layer);
+
+impl Device for Layered {
+    fn type_id() -> TypeId {
+        TypeId::new(0xedf04f65_d6a9_4242_a52b_c660a3edc1bc)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "Layered"
+    }
+}
+
+pub struct LayeredLayout {
+    layer: Layer,
+    children: Vec<LayoutNode>,
+}
+
+pub struct LayeredRenderer;
+
+impl<'frm, C: 'static> Renderer<'frm, C> for LayeredRenderer {
+    type Device = Layered;
+    type Layout = LayeredLayout;
+
+    fn layout<'thrd>(
+        &self,
+        device: Layered,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<LayeredLayout> {
+        let mut children = Vec::new();
+        ctx.socket(SocketName::default(), ctx.max_size(), &mut children);
+        if ctx.is_pending() {
+            return ctx.defer(device);
+        }
+
+        let min_size = children
+            .iter()
+            .fold(Size::zero(), |size, child| size.max(child.min_size));
+        let layout = LayeredLayout {
+            layer: device.0,
+            children,
+        };
+        ctx.layout(min_size, layout)
+    }
+
+    fn render<'ctx>(
+        &self,
+        layout: LayeredLayout,
+        ctx: RenderContext<'ctx, 'frm, C>,
+        canvas: &mut C,
+    ) {
+        for child in layout.children {
+            ctx.render_in_layer(layout.layer, child, ctx.region(), canvas);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::fixtures::*;
+    use crate::devices::Overlay;
+    use crate::util::ref_move::Ext;
+
+    fn layers_tree(ctx: &mut LayoutContext<Canvas>, _: &mut Canvas) -> LayoutResult<()> {
+        let tree = |mut visitor: LayoutTreeVisitor<Canvas>| {
+            let tooltip = Layered(Layer::TOOLTIP).move_anchor();
+            visitor.device_tree(
+                SocketName::default(),
+                tooltip,
+                |mut visitor: LayoutTreeVisitor<Canvas>| {
+                    let leaf = Leaf("tooltip", Size::new(2_f32, 2_f32)).move_anchor();
+                    visitor.device(SocketName::default(), leaf);
+
+                    // Lower than the layer it's in, so it's drawn straight away
+                    let base = Layered(Layer::BASE).move_anchor();
+                    visitor.device_tree(
+                        SocketName::default(),
+                        base,
+                        |mut visitor: LayoutTreeVisitor<Canvas>| {
+                            let leaf = Leaf("in tooltip", Size::new(1_f32, 1_f32)).move_anchor();
+                            visitor.device(SocketName::default(), leaf);
+                        },
+                    );
+                },
+            );
+
+            let popup = Layered(Layer::POPUP).move_anchor();
+            visitor.device_tree(
+                SocketName::default(),
+                popup,
+                |mut visitor: LayoutTreeVisitor<Canvas>| {
+                    let leaf = Leaf("popup", Size::new(3_f32, 3_f32)).move_anchor();
+                    visitor.device(SocketName::default(), leaf);
+                },
+            );
+
+            let leaf = Leaf("base", Size::new(1_f32, 1_f32)).move_anchor();
+            visitor.device(SocketName::default(), leaf);
+        };
+
+        ctx.device_tree(ctx.max_size(), Overlay.move_anchor(), tree)
+    }
+
+    #[test]
+    fn layers() {
+        let canvas = render(Root(layers_tree), Size::new(5_f32, 5_f32));
+        let window = region(0_f32, 0_f32, 5_f32, 5_f32);
+        assert_eq!(
+            canvas,
+            vec![
+                ("min", region(0_f32, 0_f32, 3_f32, 3_f32)),
+                ("base", window),
+                ("popup", window),
+                ("tooltip", window),
+                ("in tooltip", window),
+            ]
+        );
+    }
+}
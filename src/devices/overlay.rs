@@ -0,0 +1,84 @@
+use crate::prelude::*;
+
+// Draws the children in its default socket on top of each other, in order, each filling the region.
+pub struct Overlay;
+
+impl Device for Overlay {
+    fn type_id() -> TypeId {
+        TypeId::new(0x6c15d380_ee35_4264_9906_cf5f821ff85f)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "Overlay"
+    }
+}
+
+pub struct OverlayRenderer;
+
+impl<'frm, C: 'static> Renderer<'frm, C> for OverlayRenderer {
+    type Device = Overlay;
+    type Layout = Vec<LayoutNode>;
+
+    fn layout<'thrd>(
+        &self,
+        device: Overlay,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<Vec<LayoutNode>> {
+        let mut children = Vec::new();
+        ctx.socket(SocketName::default(), ctx.max_size(), &mut children);
+        if ctx.is_pending() {
+            return ctx.defer(device);
+        }
+
+        let min_size = children
+            .iter()
+            .fold(Size::zero(), |size, child| size.max(child.min_size));
+        ctx.layout(min_size, children)
+    }
+
+    fn render<'ctx>(
+        &self,
+        layout: Vec<LayoutNode>,
+        ctx: RenderContext<'ctx, 'frm, C>,
+        canvas: &mut C,
+    ) {
+        for child in layout {
+            ctx.render(child, ctx.region(), canvas);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::fixtures::*;
+    use crate::util::ref_move::Ext;
+
+    #[test]
+    fn overlay() {
+        let root = Root(|ctx, _| {
+            let tree = |mut visitor: LayoutTreeVisitor<Canvas>| {
+                let back = Leaf("back", Size::new(2_f32, 8_f32)).move_anchor();
+                let front = Leaf("front", Size::new(6_f32, 3_f32)).move_anchor();
+                visitor.device(SocketName::default(), back);
+                visitor.device(SocketName::default(), front);
+            };
+            ctx.device_tree(ctx.max_size(), Overlay.move_anchor(), tree)
+        });
+
+        // Every child fills the region, drawn in order, and the minimum size fits all of them
+        let canvas = render(root, Size::new(20_f32, 50_f32));
+        assert_eq!(
+            canvas,
+            vec![
+                ("min", region(0_f32, 0_f32, 6_f32, 8_f32)),
+                ("back", region(0_f32, 0_f32, 20_f32, 50_f32)),
+                ("front", region(0_f32, 0_f32, 20_f32, 50_f32)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::prelude::*;
+
+// Surrounds the child in its default socket with empty space. Since it only affects the space given
+// to its child, this works as a margin as well.
+pub struct Padding(pub Thickness);
+
+impl Device for Padding {
+    fn type_id() -> TypeId {
+        TypeId::new(0x9a589b0d_a149_48b1_af1a_9636cb6c7db9)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "Padding"
+    }
+}
+
+pub struct PaddingLayout {
+    thickness: Thickness,
+    child: Option<LayoutNode>,
+}
+
+pub struct PaddingRenderer;
+
+impl<'frm, C: 'static> Renderer<'frm, C> for PaddingRenderer {
+    type Device = Padding;
+    type Layout = PaddingLayout;
+
+    fn layout<'thrd>(
+        &self,
+        device: Padding,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<PaddingLayout> {
+        let Padding(thickness) = device;
+
+        let mut child = None;
+        ctx.socket(
+            SocketName::default(),
+            ctx.max_size().shrink(thickness),
+            &mut child,
+        );
+        if ctx.is_pending() {
+            return ctx.defer(device);
+        }
+
+        let min_size = child
+            .as_ref()
+            .map_or(Size::zero(), |child| child.min_size)
+            .grow(thickness);
+        ctx.layout(min_size, PaddingLayout { thickness, child })
+    }
+
+    fn render<'ctx>(
+        &self,
+        layout: PaddingLayout,
+        ctx: RenderContext<'ctx, 'frm, C>,
+        canvas: &mut C,
+    ) {
+        if let Some(child) = layout.child {
+            ctx.render(child, ctx.region().shrink(layout.thickness), canvas);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::fixtures::*;
+    use crate::util::ref_move::Ext;
+
+    #[test]
+    fn padding() {
+        let root = Root(|ctx, _| {
+            let tree = |mut visitor: LayoutTreeVisitor<Canvas>| {
+                let leaf = Leaf("inner", Size::new(10_f32, 10_f32)).move_anchor();
+                visitor.device(SocketName::default(), leaf);
+            };
+            let padding = Padding(Thickness::new(1_f32, 2_f32, 3_f32, 4_f32)).move_anchor();
+            ctx.device_tree(ctx.max_size(), padding, tree)
+        });
+
+        let canvas = render(root, Size::new(20_f32, 50_f32));
+        assert_eq!(
+            canvas,
+            vec![
+                ("min", region(0_f32, 0_f32, 14_f32, 16_f32)),
+                ("inner", region(1_f32, 2_f32, 16_f32, 44_f32)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use crate::canvas::{Color, Draw, Path, Stroke, TextRun};
+use crate::prelude::*;
+use std::rc::Rc;
+
+const FONT_SIZE: f32 = 12_f32;
+const INSET: f32 = 4_f32;
+
+// Draws the 'MissingDevice' that 'MissingRendererPolicy::Placeholder' puts in place of a device without
+// a renderer, as an outlined box (filling whatever it's given) labelled with the missing type's name.
+pub struct PlaceholderRenderer;
+
+impl<'frm, C: Draw + 'static> Renderer<'frm, C> for PlaceholderRenderer {
+    type Device = MissingDevice;
+    type Layout = &'static str;
+
+    fn layout<'thrd>(
+        &self,
+        device: MissingDevice,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<&'static str> {
+        ctx.layout(Size::zero(), device.type_name)
+    }
+
+    fn render<'ctx>(
+        &self,
+        type_name: &'static str,
+        ctx: RenderContext<'ctx, 'frm, C>,
+        canvas: &mut C,
+    ) {
+        let Region { pos, size } = ctx.region();
+        let outline = Path::new()
+            .move_to(pos)
+            .line_to(Point::new(pos.x + size.width, pos.y))
+            .line_to(Point::new(pos.x + size.width, pos.y + size.height))
+            .line_to(Point::new(pos.x, pos.y + size.height))
+            .close();
+        let color = Color::rgb(255, 0, 255);
+        canvas.draw_path(
+            outline,
+            Some(color.with_opacity(0.25)),
+            Some(Stroke::new(color, 1_f32)),
+        );
+        canvas.draw_text(TextRun {
+            text: type_name.to_string(),
+            origin: Point::new(pos.x + INSET, pos.y + INSET + FONT_SIZE),
+            font_size: FONT_SIZE,
+            color,
+        });
+    }
+}
+
+impl<C: Draw + 'static> GuiContext<C> {
+    // A context for a canvas that can be drawn to, with 'PlaceholderRenderer' already registered.
+    pub fn new() -> Self {
+        let mut gui = GuiContext::default();
+        gui.register::<MissingDevice>(Rc::new(PlaceholderRenderer));
+        gui
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Command, DisplayList};
+    use crate::util::ref_move::Ext;
+
+    struct Unregistered;
+
+    impl Device for Unregistered {
+        fn type_id() -> TypeId {
+            TypeId::new(0x2d0fa3b4_61c5_4c8e_9a57_0e43b1d9f6a2)
+        }
+
+        fn package_name() -> &'static str {
+            "buoy"
+        }
+
+        fn type_name() -> &'static str {
+            "Unregistered"
+        }
+    }
+
+    #[test]
+    fn placeholder_for_missing_renderer() {
+        let mut gui = GuiContext::<DisplayList>::new();
+        let window = Region::new(Point::new(5_f32, 5_f32), Size::new(40_f32, 20_f32));
+        let error = gui.render_window(window, Unregistered.move_anchor(), &mut DisplayList::new());
+        assert_eq!(
+            error,
+            Err(BuoyError::MissingRenderer(DeviceInfo::of::<Unregistered>()))
+        );
+
+        // The device is drawn as a box filling the window, labelled with its type name
+        gui.set_missing_renderer_policy(MissingRendererPolicy::Placeholder);
+        let mut canvas = DisplayList::new();
+        gui.render_window(window, Unregistered.move_anchor(), &mut canvas)
+            .unwrap();
+        let commands = canvas.commands();
+        assert_eq!(commands.len(), 2);
+        match &commands[0] {
+            Command::Path { path, .. } => assert_eq!(path.commands().len(), 5),
+            command => panic!("expected the outline, got {:?}", command),
+        }
+        match &commands[1] {
+            Command::Text(run) => {
+                assert_eq!(run.text, "Unregistered");
+                assert_eq!(run.origin, Point::new(9_f32, 21_f32));
+            }
+            command => panic!("expected the label, got {:?}", command),
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::canvas::Clip;
+use crate::input::scroll_into_view_id;
+use crate::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScrollAxis {
+    Vertical,
+    Horizontal,
+    Both,
+}
+
+impl ScrollAxis {
+    fn horizontal(self) -> bool {
+        self != ScrollAxis::Vertical
+    }
+
+    fn vertical(self) -> bool {
+        self != ScrollAxis::Horizontal
+    }
+}
+
+// Shows the child in its default socket through a clipped viewport, scrolled by an offset.
+// The child is laid out with an unbounded size along the scrolled axes.
+//
+// The scroll state lives in messages keyed by the device's Id, rather than in the device:
+// - 'offset_id' (Vector) is written every frame with the current offset,
+// - 'content_size_id' and 'viewport_size_id' (Size) are written every frame for things like scrollbars,
+// - 'scroll_to_id' (Vector) can be written by anything else to change the offset on the next frame.
+// It also scrolls to show anything inside it that gets focused by the keyboard.
+pub struct Scroll {
+    pub id: Id,
+    pub axis: ScrollAxis,
+}
+
+impl Scroll {
+    pub fn new(id: Id, axis: ScrollAxis) -> Self {
+        Scroll { id, axis }
+    }
+
+    pub fn offset_id(id: Id) -> Id {
+        id.append("offset")
+    }
+
+    pub fn content_size_id(id: Id) -> Id {
+        id.append("content_size")
+    }
+
+    pub fn viewport_size_id(id: Id) -> Id {
+        id.append("viewport_size")
+    }
+
+    pub fn scroll_to_id(id: Id) -> Id {
+        id.append("scroll_to")
+    }
+
+    // Scrolls the viewport with the given Id on the next frame. The offset is clamped to the content.
+    pub fn scroll_to<C: 'static>(gui: &mut GuiContext<C>, id: Id, offset: Vector) {
+        let outbox = gui.message(Scroll::scroll_to_id(id));
+        outbox.inbox();
+        gui.write_message(outbox, offset);
+    }
+}
+
+impl Device for Scroll {
+    fn type_id() -> TypeId {
+        TypeId::new(0xe036e168_9141_4ab3_8d1f_9db11ed014b1)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "Scroll"
+    }
+}
+
+pub struct ScrollLayout {
+    id: Id,
+    axis: ScrollAxis,
+    offset: Vector,
+    // Where the content was scrolled to last frame, and something it contained that should be shown
+    previous: Vector,
+    into_view: Option<Region>,
+    content_size: Size,
+    child: Option<LayoutNode>,
+}
+
+pub struct ScrollRenderer;
+
+impl<'frm, C: Clip + 'static> Renderer<'frm, C> for ScrollRenderer {
+    type Device = Scroll;
+    type Layout = ScrollLayout;
+
+    fn layout<'thrd>(
+        &self,
+        device: Scroll,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<ScrollLayout> {
+        let mut max_size = ctx.max_size();
+        if device.axis.horizontal() {
+            max_size.width = Size::infinite().width;
+        }
+        if device.axis.vertical() {
+            max_size.height = Size::infinite().height;
+        }
+
+        let mut child = None;
+        ctx.socket(SocketName::default(), max_size, &mut child);
+        if ctx.is_pending() {
+            return ctx.defer(device);
+        }
+
+        // A request to scroll takes precedence over where it was last frame
+        let scroll_to = ctx.message::<Vector>(Scroll::scroll_to_id(device.id));
+        let offset = ctx.message::<Vector>(Scroll::offset_id(device.id));
+        let previous = ctx.read_message(&offset).unwrap_or_else(Vector::zero);
+        let offset = ctx.read_message(&scroll_to).unwrap_or(previous);
+        let into_view = ctx.message::<Region>(scroll_into_view_id());
+        let into_view = ctx.read_message(&into_view);
+
+        let content_size = child.as_ref().map_or(Size::zero(), |child| child.min_size);
+        // Outboxes need to be observed for their messages to be kept for the next frame
+        let outbox = ctx.message(Scroll::content_size_id(device.id));
+        outbox.inbox();
+        ctx.write_message(outbox, content_size);
+
+        // The viewport can shrink to nothing along the scrolled axes
+        let mut min_size = content_size;
+        if device.axis.horizontal() {
+            min_size.width = 0_f32;
+        }
+        if device.axis.vertical() {
+            min_size.height = 0_f32;
+        }
+
+        let layout = ScrollLayout {
+            id: device.id,
+            axis: device.axis,
+            offset,
+            previous,
+            into_view,
+            content_size,
+            child,
+        };
+        ctx.layout(min_size, layout)
+    }
+
+    fn render<'ctx>(
+        &self,
+        layout: ScrollLayout,
+        ctx: RenderContext<'ctx, 'frm, C>,
+        canvas: &mut C,
+    ) {
+        let viewport = ctx.region();
+
+        let mut requested = layout.offset;
+        if let Some(target) = layout.into_view {
+            // The region is from last frame, so it's relative to the content as it was scrolled then
+            let pos = Point::new(
+                target.pos.x - viewport.pos.x + layout.previous.x,
+                target.pos.y - viewport.pos.y + layout.previous.y,
+            );
+            let content = Region::new(Point::zero(), viewport.size.max(layout.content_size));
+            let end = Point::new(pos.x + target.size.width, pos.y + target.size.height);
+            if content.contains(pos) && content.contains(end) {
+                requested.x = reveal(requested.x, pos.x, target.size.width, viewport.size.width);
+                requested.y = reveal(requested.y, pos.y, target.size.height, viewport.size.height);
+            }
+        }
+
+        // Don't let the content scroll out of view, now that the size of the viewport is known
+        let mut offset = Vector::zero();
+        let mut content = Region::new(viewport.pos, viewport.size.max(layout.content_size));
+        if layout.axis.horizontal() {
+            let max = (layout.content_size.width - viewport.size.width).max(0_f32);
+            offset.x = requested.x.max(0_f32).min(max);
+            content.pos.x -= offset.x;
+        } else {
+            content.size.width = viewport.size.width;
+        }
+        if layout.axis.vertical() {
+            let max = (layout.content_size.height - viewport.size.height).max(0_f32);
+            offset.y = requested.y.max(0_f32).min(max);
+            content.pos.y -= offset.y;
+        } else {
+            content.size.height = viewport.size.height;
+        }
+
+        let outbox = ctx.message(Scroll::offset_id(layout.id));
+        outbox.inbox();
+        ctx.write_message(outbox, offset);
+
+        let outbox = ctx.message(Scroll::viewport_size_id(layout.id));
+        outbox.inbox();
+        ctx.write_message(outbox, viewport.size);
+
+        if let Some(child) = layout.child {
+            canvas.push_clip(viewport);
+            ctx.render(child, content, canvas);
+            canvas.pop_clip();
+        }
+    }
+}
+
+// The smallest change to the offset that shows the span from 'start' (if it fits in the viewport)
+fn reveal(offset: f32, start: f32, length: f32, viewport: f32) -> f32 {
+    if start < offset {
+        start
+    } else if start + length > offset + viewport {
+        (start + length - viewport).min(start)
+    } else {
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::fixtures::*;
+    use crate::devices::Stack;
+    use crate::util::ref_move::Ext;
+
+    fn scroll_tree(ctx: &mut LayoutContext<Canvas>, extra: &mut Canvas) -> LayoutResult<()> {
+        // Report what the viewport said about itself last frame
+        let id = Id::from("list");
+        let content_size = ctx.message::<Size>(Scroll::content_size_id(id)).inbox();
+        let viewport_size = ctx.message::<Size>(Scroll::viewport_size_id(id)).inbox();
+        if let Some(size) = ctx.read_message(content_size) {
+            extra.push(("content", Region::new(Point::zero(), size)));
+        }
+        if let Some(size) = ctx.read_message(viewport_size) {
+            extra.push(("viewport", Region::new(Point::zero(), size)));
+        }
+
+        let tree = |mut visitor: LayoutTreeVisitor<Canvas>| {
+            let stack = Stack::vertical(0_f32).move_anchor();
+            visitor.device_tree(
+                SocketName::default(),
+                stack,
+                |mut visitor: LayoutTreeVisitor<Canvas>| {
+                    for name in ["one", "two", "three"].iter() {
+                        let leaf = Leaf(name, Size::new(10_f32, 20_f32)).move_anchor();
+                        visitor.device(SocketName::default(), leaf);
+                    }
+                },
+            );
+        };
+
+        let scroll = Scroll::new(id, ScrollAxis::Vertical).move_anchor();
+        ctx.device_tree(ctx.max_size(), scroll, tree)
+    }
+
+    #[test]
+    fn offset() {
+        let mut gui = gui();
+        let window = Size::new(30_f32, 25_f32);
+        let unclip = region(0_f32, 0_f32, 0_f32, 0_f32);
+        let frame = |gui: &mut GuiContext<Canvas>, content: Option<Canvas>, y: f32| {
+            let mut expected = vec![("min", region(0_f32, 0_f32, 10_f32, 0_f32))];
+            expected.extend(content.unwrap_or_default());
+            expected.extend(vec![
+                ("clip", region(0_f32, 0_f32, 30_f32, 25_f32)),
+                ("one", region(0_f32, y, 30_f32, 20_f32)),
+                ("two", region(0_f32, y + 20_f32, 30_f32, 20_f32)),
+                ("three", region(0_f32, y + 40_f32, 30_f32, 20_f32)),
+                ("unclip", unclip),
+            ]);
+            assert_eq!(render_frame(gui, Root(scroll_tree), window), expected);
+        };
+        let sizes = vec![
+            ("content", region(0_f32, 0_f32, 10_f32, 60_f32)),
+            ("viewport", region(0_f32, 0_f32, 30_f32, 25_f32)),
+        ];
+
+        frame(&mut gui, None, 0_f32);
+
+        // Scrolling past the end stops at the end, and stays there
+        Scroll::scroll_to(&mut gui, Id::from("list"), Vector::new(0_f32, 100_f32));
+        frame(&mut gui, Some(sizes.clone()), -35_f32);
+        frame(&mut gui, Some(sizes), -35_f32);
+    }
+
+    #[test]
+    fn scroll_into_view() {
+        let mut gui = gui();
+        let window = Size::new(30_f32, 25_f32);
+        let leaf =
+            |canvas: &Canvas, name: &str| canvas.iter().find(|(n, _)| *n == name).unwrap().1.pos.y;
+
+        render_frame(&mut gui, Root(scroll_tree), window);
+
+        gui.focus(Id::from("three"));
+        let canvas = render_frame(&mut gui, Root(scroll_tree), window);
+        assert_eq!(gui.focused(), Some(Id::from("three")));
+        assert_eq!(leaf(&canvas, "three"), 5_f32);
+
+        // Only scrolls as far as it needs to
+        gui.move_focus(FocusMove::Previous);
+        let canvas = render_frame(&mut gui, Root(scroll_tree), window);
+        assert_eq!(gui.focused(), Some(Id::from("two")));
+        assert_eq!(leaf(&canvas, "two"), 0_f32);
+
+        gui.move_focus(FocusMove::Next);
+        let canvas = render_frame(&mut gui, Root(scroll_tree), window);
+        assert_eq!(leaf(&canvas, "three"), 5_f32);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Direction {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+// Places the children in its default socket one after another, with 'spacing' between each of them.
+// Each child gets its minimum size along the stacking direction, and the full region across it.
+pub struct Stack {
+    pub direction: Direction,
+    pub spacing: f32,
+}
+
+impl Stack {
+    pub fn vertical(spacing: f32) -> Self {
+        Stack {
+            direction: Direction::Vertical,
+            spacing,
+        }
+    }
+
+    pub fn horizontal(spacing: f32) -> Self {
+        Stack {
+            direction: Direction::Horizontal,
+            spacing,
+        }
+    }
+}
+
+impl Device for Stack {
+    fn type_id() -> TypeId {
+        TypeId::new(0x05765a71_72da_46ff_9ad3_250682c527b4)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "Stack"
+    }
+}
+
+pub struct StackLayout {
+    direction: Direction,
+    spacing: f32,
+    children: Vec<LayoutNode>,
+}
+
+pub struct StackRenderer;
+
+impl<'frm, C: 'static> Renderer<'frm, C> for StackRenderer {
+    type Device = Stack;
+    type Layout = StackLayout;
+
+    fn layout<'thrd>(
+        &self,
+        device: Stack,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<StackLayout> {
+        let mut children = Vec::new();
+        ctx.socket(SocketName::default(), ctx.max_size(), &mut children);
+        if ctx.is_pending() {
+            return ctx.defer(device);
+        }
+
+        let mut min_size = Size::zero();
+        for child in &children {
+            match device.direction {
+                Direction::Vertical => {
+                    min_size.width = min_size.width.max(child.min_size.width);
+                    min_size.height += child.min_size.height;
+                }
+                Direction::Horizontal => {
+                    min_size.width += child.min_size.width;
+                    min_size.height = min_size.height.max(child.min_size.height);
+                }
+            }
+        }
+
+        let spacing = device.spacing * children.len().saturating_sub(1) as f32;
+        match device.direction {
+            Direction::Vertical => min_size.height += spacing,
+            Direction::Horizontal => min_size.width += spacing,
+        }
+
+        let layout = StackLayout {
+            direction: device.direction,
+            spacing: device.spacing,
+            children,
+        };
+        ctx.layout(min_size, layout)
+    }
+
+    fn render<'ctx>(&self, layout: StackLayout, ctx: RenderContext<'ctx, 'frm, C>, canvas: &mut C) {
+        let mut region = ctx.region();
+        for child in layout.children {
+            let mut child_region = region;
+            match layout.direction {
+                Direction::Vertical => {
+                    child_region.size.height = child.min_size.height;
+                    region.pos.y += child.min_size.height + layout.spacing;
+                }
+                Direction::Horizontal => {
+                    child_region.size.width = child.min_size.width;
+                    region.pos.x += child.min_size.width + layout.spacing;
+                }
+            }
+
+            ctx.render(child, child_region, canvas);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::fixtures::*;
+    use crate::util::ref_move::Ext;
+
+    #[test]
+    fn vertical() {
+        let root = Root(|ctx, _| {
+            let tree = |mut visitor: LayoutTreeVisitor<Canvas>| {
+                let top = Leaf("top", Size::new(10_f32, 10_f32)).move_anchor();
+                let bottom = Leaf("bottom", Size::new(4_f32, 6_f32)).move_anchor();
+                visitor.device(SocketName::default(), top);
+                visitor.device(SocketName::default(), bottom);
+            };
+            ctx.device_tree(ctx.max_size(), Stack::vertical(5_f32).move_anchor(), tree)
+        });
+
+        // Each child gets its own height and the full width, with the spacing counted in the minimum
+        let canvas = render(root, Size::new(20_f32, 50_f32));
+        assert_eq!(
+            canvas,
+            vec![
+                ("min", region(0_f32, 0_f32, 10_f32, 21_f32)),
+                ("top", region(0_f32, 0_f32, 20_f32, 10_f32)),
+                ("bottom", region(0_f32, 15_f32, 20_f32, 6_f32)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,557 @@
+use crate::canvas::{Color, Draw, TextRun};
+use crate::prelude::*;
+use std::rc::Rc;
+
+mod editor;
+pub use self::editor::{Movement, TextEditor};
+
+// Gives text inputs access to the system clipboard, which only the window layer knows how to reach
+pub trait Clipboard {
+    fn read(&self) -> Option<String>;
+    fn write(&self, text: &str);
+}
+
+// An editable box of text, with a caret and selection, that takes keyboard focus when clicked or
+// tabbed to. There's no text layout yet, so every character is assumed to be the same width.
+//
+// Like 'Scroll', its state lives in messages keyed by the device's Id, since the device itself is
+// rebuilt every frame:
+// - 'state_id' (TextEditor) is written every frame with the content, caret, selection and undo history,
+// - 'text_id' (String) is written every frame with just the content, for anything else to read,
+// - 'set_text_id' (String) can be written by anything else to replace the content on the next frame.
+// The initial text is only used on the first frame, before there's any state.
+pub struct TextInput {
+    pub id: Id,
+    pub multiline: bool,
+    pub initial_text: String,
+    pub font_size: f32,
+    pub color: Color,
+    pub selection_color: Color,
+}
+
+impl TextInput {
+    pub fn new(id: Id) -> Self {
+        TextInput {
+            id,
+            multiline: false,
+            initial_text: String::new(),
+            font_size: 16_f32,
+            color: Color::BLACK,
+            selection_color: Color::rgba(51, 142, 255, 96),
+        }
+    }
+
+    pub fn multiline(id: Id) -> Self {
+        TextInput {
+            multiline: true,
+            ..TextInput::new(id)
+        }
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.initial_text = text.to_string();
+        self
+    }
+
+    pub fn state_id(id: Id) -> Id {
+        id.append("state")
+    }
+
+    pub fn text_id(id: Id) -> Id {
+        id.append("text")
+    }
+
+    pub fn set_text_id(id: Id) -> Id {
+        id.append("set_text")
+    }
+
+    // Replaces the content of the text input with the given Id on the next frame, as a step that can be
+    // undone
+    pub fn set_text<C: 'static>(gui: &mut GuiContext<C>, id: Id, text: &str) {
+        let outbox = gui.message(TextInput::set_text_id(id));
+        outbox.inbox();
+        gui.write_message(outbox, text.to_string());
+    }
+}
+
+impl Device for TextInput {
+    fn type_id() -> TypeId {
+        TypeId::new(0x69ab77a2_f4d6_4c3c_9a8f_0f8b4b3d5e61)
+    }
+
+    fn package_name() -> &'static str {
+        "buoy"
+    }
+
+    fn type_name() -> &'static str {
+        "TextInput"
+    }
+}
+
+pub struct TextInputLayout {
+    id: Id,
+    font_size: f32,
+    color: Color,
+    selection_color: Color,
+    editor: TextEditor,
+    focused: bool,
+    preedit: Option<Preedit>,
+    pointer: Option<PointerInput>,
+}
+
+#[derive(Default)]
+pub struct TextInputRenderer {
+    clipboard: Option<Rc<dyn Clipboard>>,
+}
+
+impl TextInputRenderer {
+    // Without a clipboard, copying, cutting and pasting don't do anything (though cutting still
+    // removes the selection)
+    pub fn new() -> Self {
+        TextInputRenderer::default()
+    }
+
+    pub fn with_clipboard(clipboard: Rc<dyn Clipboard>) -> Self {
+        TextInputRenderer {
+            clipboard: Some(clipboard),
+        }
+    }
+
+    fn handle(&self, editor: &mut TextEditor, event: &KeyEvent) {
+        let (key, modifiers) = match event {
+            KeyEvent::Down { key, modifiers, .. } => (*key, *modifiers),
+            KeyEvent::Text(text) => {
+                // Keys like Enter and Backspace are handled when they go down instead
+                let text: String = text.chars().filter(|c| !c.is_control()).collect();
+                return editor.insert(&text);
+            }
+            _ => return,
+        };
+
+        // Ctrl on most systems, Cmd on macOS (where Alt moves by word)
+        let command = modifiers.ctrl || modifiers.meta;
+        let word = modifiers.ctrl || modifiers.alt;
+        let select = modifiers.shift;
+        let by_word = |by_word: Movement, by_char: Movement| match word {
+            true => by_word,
+            false => by_char,
+        };
+
+        match key {
+            Key::ArrowLeft => {
+                editor.move_caret(by_word(Movement::WordLeft, Movement::Left), select)
+            }
+            Key::ArrowRight => {
+                editor.move_caret(by_word(Movement::WordRight, Movement::Right), select)
+            }
+            Key::ArrowUp => editor.move_caret(Movement::Up, select),
+            Key::ArrowDown => editor.move_caret(Movement::Down, select),
+            Key::Home if command => editor.move_caret(Movement::Start, select),
+            Key::Home => editor.move_caret(Movement::LineStart, select),
+            Key::End if command => editor.move_caret(Movement::End, select),
+            Key::End => editor.move_caret(Movement::LineEnd, select),
+            Key::PageUp => editor.move_caret(Movement::Start, select),
+            Key::PageDown => editor.move_caret(Movement::End, select),
+            Key::Backspace => editor.delete(by_word(Movement::WordLeft, Movement::Left)),
+            Key::Delete => editor.delete(by_word(Movement::WordRight, Movement::Right)),
+            Key::Enter if editor.is_multiline() => editor.insert("\n"),
+            Key::Character(c) if command => match c.to_ascii_lowercase() {
+                'a' => editor.select_all(),
+                'c' => {
+                    if let (Some(clipboard), true) = (&self.clipboard, editor.has_selection()) {
+                        clipboard.write(editor.selected_text());
+                    }
+                }
+                'x' => {
+                    if let (Some(text), Some(clipboard)) = (editor.cut(), &self.clipboard) {
+                        clipboard.write(&text);
+                    }
+                }
+                'v' => {
+                    if let Some(text) = self.clipboard.as_ref().and_then(|c| c.read()) {
+                        editor.insert(&text);
+                    }
+                }
+                'z' if select => {
+                    editor.redo();
+                }
+                'z' => {
+                    editor.undo();
+                }
+                'y' => {
+                    editor.redo();
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+}
+
+impl<'frm, C: Draw + 'static> Renderer<'frm, C> for TextInputRenderer {
+    type Device = TextInput;
+    type Layout = TextInputLayout;
+
+    fn layout<'thrd>(
+        &self,
+        device: TextInput,
+        ctx: &mut LayoutContext<'thrd, 'frm, C>,
+    ) -> LayoutResult<TextInputLayout> {
+        // When the window is rendered in more than one pass, an earlier pass will already have applied
+        // this frame's input (keys, clicks and replaced text) to the state
+        let state = ctx.message::<TextEditor>(TextInput::state_id(device.id));
+        let (mut editor, applied) = match ctx.read_pass_message(&state) {
+            Some(editor) => (editor, true),
+            None => match ctx.read_message(&state) {
+                Some(editor) => (editor, false),
+                None => (
+                    TextEditor::new(&device.initial_text, device.multiline),
+                    false,
+                ),
+            },
+        };
+
+        let set_text = ctx.message::<String>(TextInput::set_text_id(device.id));
+        if let (Some(text), false) = (ctx.read_message(&set_text), applied) {
+            editor.set_text(&text);
+        }
+
+        // Keys only reach whatever is focused, so there's no need to check the focus here
+        let keyboard = ctx.message::<KeyboardInput>(keyboard_id(device.id));
+        let keyboard = ctx.read_message(&keyboard);
+        if !applied {
+            for event in keyboard.iter().flat_map(|keyboard| &keyboard.events) {
+                self.handle(&mut editor, event);
+            }
+        }
+
+        let pointer = ctx.message::<PointerInput>(pointer_id(device.id));
+        let pointer = ctx.read_message(&pointer).filter(|_| !applied);
+        let focused = ctx.focused() == Some(device.id);
+        let preedit = keyboard.and_then(|keyboard| keyboard.preedit);
+
+        // Enough room to show all of the text (and the caret after it)
+        let (lines, columns) = editor
+            .text()
+            .split('\n')
+            .fold((0, 0), |(lines, columns), line| {
+                (lines + 1, columns.max(line.chars().count()))
+            });
+        let metrics = Metrics::new(device.font_size);
+        let min_size = Size::new(
+            (columns as f32 + 1_f32) * metrics.advance,
+            lines as f32 * metrics.line_height,
+        );
+
+        let layout = TextInputLayout {
+            id: device.id,
+            font_size: device.font_size,
+            color: device.color,
+            selection_color: device.selection_color,
+            editor,
+            focused,
+            preedit,
+            pointer,
+        };
+        ctx.layout(min_size, layout)
+    }
+
+    fn render<'ctx>(
+        &self,
+        mut layout: TextInputLayout,
+        ctx: RenderContext<'ctx, 'frm, C>,
+        canvas: &mut C,
+    ) {
+        let region = ctx.region();
+        let metrics = Metrics::new(layout.font_size);
+        ctx.hit_region(layout.id, region);
+        ctx.focusable(layout.id, region);
+
+        // Clicking moves the caret, and dragging selects
+        if let Some(pointer) = &layout.pointer {
+            let left = MouseButton::Left;
+            let mut dragging = pointer.held.contains(&left) && !pointer.pressed(left);
+            let mut position = pointer.position;
+            for event in &pointer.events {
+                match event {
+                    PointerEvent::Move(pos) => {
+                        position = Some(*pos);
+                        if dragging {
+                            let caret = metrics.position_at(&layout.editor, region, *pos);
+                            layout.editor.set_caret(caret, true);
+                        }
+                    }
+                    PointerEvent::Press(button) if *button == left => {
+                        if let Some(pos) = position {
+                            let caret = metrics.position_at(&layout.editor, region, pos);
+                            layout.editor.set_caret(caret, false);
+                        }
+                        dragging = true;
+                    }
+                    PointerEvent::Release(button) if *button == left => dragging = false,
+                    _ => (),
+                }
+            }
+        }
+
+        let editor = &layout.editor;
+        let text = editor.text().to_string();
+        let outbox = ctx.message(TextInput::text_id(layout.id));
+        outbox.inbox();
+        ctx.write_message(outbox, text.clone());
+
+        canvas.push_clip(region);
+
+        // Text being composed by an input method is shown where it'll go, underlined
+        let caret = editor.caret();
+        let (text, caret, composing) = match (&layout.preedit, layout.focused) {
+            (Some(preedit), true) => {
+                let mut text = text;
+                text.insert_str(caret, &preedit.text);
+                let cursor = preedit.cursor.map_or(preedit.text.len(), |(_, end)| end);
+                let composing = (caret, caret + preedit.text.len());
+                (text, caret + cursor, Some(composing))
+            }
+            _ => (text, caret, None),
+        };
+
+        if composing.is_none() && editor.has_selection() {
+            let (start, end) = editor.selection();
+            let mut line_start = 0;
+            for (i, line) in text.split('\n').enumerate() {
+                let line_end = line_start + line.len();
+                if start <= line_end && end > line_start {
+                    let from = start.max(line_start) - line_start;
+                    let to = end.min(line_end) - line_start;
+                    let mut columns = line[from..to].chars().count() as f32;
+                    // Show that the line break is selected
+                    if end > line_end {
+                        columns += 0.5_f32;
+                    }
+                    let pos = metrics.point(region, i, line[..from].chars().count());
+                    let size = Size::new(columns * metrics.advance, metrics.line_height);
+                    canvas.fill_rect(Region::new(pos, size), layout.selection_color);
+                }
+                line_start = line_end + 1;
+            }
+        }
+
+        for (i, line) in text.split('\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let pos = metrics.point(region, i, 0);
+            canvas.draw_text(TextRun {
+                text: line.to_string(),
+                origin: Point::new(pos.x, pos.y + metrics.baseline),
+                font_size: layout.font_size,
+                color: layout.color,
+            });
+        }
+
+        if let Some((start, end)) = composing {
+            let (line, column) = line_and_column(&text, start);
+            let pos = metrics.point(region, line, column);
+            let columns = text[start..end].chars().count() as f32;
+            let underline = Region::new(
+                Point::new(pos.x, pos.y + metrics.baseline + 1_f32),
+                Size::new(columns * metrics.advance, 1_f32),
+            );
+            canvas.fill_rect(underline, layout.color);
+        }
+
+        if layout.focused {
+            let (line, column) = line_and_column(&text, caret);
+            let pos = metrics.point(region, line, column);
+            let size = Size::new(1_f32, metrics.line_height);
+            canvas.fill_rect(Region::new(pos, size), layout.color);
+        }
+
+        canvas.pop_clip();
+
+        let outbox = ctx.message(TextInput::state_id(layout.id));
+        outbox.inbox();
+        ctx.write_message(outbox, layout.editor);
+    }
+}
+
+// Where characters go, with every character the same width
+struct Metrics {
+    advance: f32,
+    line_height: f32,
+    // From the top of the line
+    baseline: f32,
+}
+
+impl Metrics {
+    fn new(font_size: f32) -> Self {
+        Metrics {
+            advance: font_size * 0.6_f32,
+            line_height: font_size * 1.25_f32,
+            baseline: font_size,
+        }
+    }
+
+    // The top left corner of a character
+    fn point(&self, region: Region, line: usize, column: usize) -> Point {
+        Point::new(
+            region.pos.x + column as f32 * self.advance,
+            region.pos.y + line as f32 * self.line_height,
+        )
+    }
+
+    // The closest place to put the caret to a point
+    fn position_at(&self, editor: &TextEditor, region: Region, point: Point) -> usize {
+        let line = ((point.y - region.pos.y) / self.line_height).max(0_f32) as usize;
+        let column = ((point.x - region.pos.x) / self.advance).round().max(0_f32) as usize;
+        editor.position_at(line, column)
+    }
+}
+
+fn line_and_column(text: &str, position: usize) -> (usize, usize) {
+    let before = &text[..position];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line = before.matches('\n').count();
+    (line, before[line_start..].chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Command, DisplayList};
+    use crate::util::ref_move::Ext;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct TestClipboard(RefCell<Option<String>>);
+
+    impl Clipboard for TestClipboard {
+        fn read(&self) -> Option<String> {
+            self.0.borrow().clone()
+        }
+
+        fn write(&self, text: &str) {
+            *self.0.borrow_mut() = Some(text.to_string());
+        }
+    }
+
+    // The lines of text drawn, and where the caret was (if it was drawn)
+    fn frame(gui: &mut GuiContext<DisplayList>) -> (Vec<String>, Option<Point>) {
+        let mut canvas = DisplayList::new();
+        let window = Region::new(Point::zero(), Size::new(200_f32, 100_f32));
+        let input = TextInput::multiline(Id::from("input")).with_text("hello");
+        gui.render_window(window, input.move_anchor(), &mut canvas)
+            .unwrap();
+
+        let mut lines = Vec::new();
+        let mut caret = None;
+        for command in canvas.commands() {
+            match command {
+                Command::Text(run) => lines.push(run.text.clone()),
+                Command::Rect { region, .. } if region.size.width == 1_f32 => {
+                    caret = Some(region.pos)
+                }
+                _ => (),
+            }
+        }
+        (lines, caret)
+    }
+
+    #[test]
+    fn typing() {
+        let clipboard = Rc::new(TestClipboard::default());
+        let renderer = TextInputRenderer::with_clipboard(clipboard.clone());
+        let mut gui = GuiContext::default();
+        gui.register::<TextInput>(Rc::new(renderer));
+
+        // The caret is only shown once it's focused, which clicking does
+        assert_eq!(frame(&mut gui), (vec!["hello".to_string()], None));
+        gui.pointer_move(Point::new(20_f32, 5_f32));
+        gui.pointer_down(MouseButton::Left);
+        gui.pointer_up(MouseButton::Left);
+        let caret = Point::new(2_f32 * 9.6_f32, 0_f32);
+        assert_eq!(frame(&mut gui), (vec!["hello".to_string()], Some(caret)));
+
+        let no = Modifiers::NONE;
+        gui.text_input("y");
+        gui.key_down(Key::End, no);
+        gui.key_down(Key::Enter, no);
+        gui.text_input("world");
+        gui.key_down(Key::ArrowLeft, Modifiers::SHIFT);
+        gui.key_down(Key::ArrowLeft, Modifiers::SHIFT);
+        gui.key_down(Key::Character('x'), Modifiers::CTRL);
+        let caret = Point::new(3_f32 * 9.6_f32, 20_f32);
+        let lines = vec!["heyllo".to_string(), "wor".to_string()];
+        assert_eq!(frame(&mut gui), (lines, Some(caret)));
+
+        gui.key_down(Key::Home, Modifiers::CTRL);
+        gui.key_down(Key::Character('v'), Modifiers::CTRL);
+        gui.key_down(Key::Backspace, no);
+        let lines = vec!["heyllo".to_string(), "wor".to_string()];
+        frame(&mut gui);
+        assert_eq!(clipboard.read(), Some("ld".to_string()));
+
+        // Undoing goes back through the paste and the deletion
+        gui.key_down(Key::Character('z'), Modifiers::CTRL);
+        let pasted = vec!["ldheyllo".to_string(), "wor".to_string()];
+        assert_eq!(frame(&mut gui).0, pasted);
+        gui.key_down(Key::Character('z'), Modifiers::CTRL);
+        assert_eq!(frame(&mut gui).0, lines);
+        let redo = Modifiers {
+            shift: true,
+            ..Modifiers::CTRL
+        };
+        gui.key_down(Key::Character('z'), redo);
+        assert_eq!(frame(&mut gui).0, pasted);
+
+        // The composition is shown at the caret until it's committed
+        gui.key_down(Key::End, Modifiers::CTRL);
+        gui.ime_preedit("か", None);
+        let composing = vec!["ldheyllo".to_string(), "worか".to_string()];
+        assert_eq!(frame(&mut gui).0, composing);
+        gui.ime_commit("花");
+        let committed = vec!["ldheyllo".to_string(), "wor花".to_string()];
+        assert_eq!(frame(&mut gui).0, committed);
+
+        TextInput::set_text(&mut gui, Id::from("input"), "replaced");
+        assert_eq!(frame(&mut gui).0, vec!["replaced".to_string()]);
+    }
+
+    #[test]
+    fn typing_over_passes() {
+        let mut gui = GuiContext::default();
+        gui.register::<TextInput>(Rc::new(TextInputRenderer::new()));
+
+        let mut canvas = DisplayList::new();
+        let window = Region::new(Point::zero(), Size::new(200_f32, 100_f32));
+        let input = |_| {
+            TextInput::new(Id::from("input"))
+                .with_text("hi")
+                .move_anchor()
+        };
+        gui.render_window_passes(window, 2, input, &mut canvas)
+            .unwrap();
+
+        // Clicking focuses it, and what's typed only goes in once however many passes there are
+        gui.pointer_move(Point::new(20_f32, 5_f32));
+        gui.pointer_down(MouseButton::Left);
+        gui.pointer_up(MouseButton::Left);
+        gui.render_window_passes(window, 2, input, &mut canvas)
+            .unwrap();
+        gui.key_down(Key::End, Modifiers::NONE);
+        gui.text_input("a");
+        let mut canvas = DisplayList::new();
+        gui.render_window_passes(window, 3, input, &mut canvas)
+            .unwrap();
+
+        let drawn: Vec<_> = canvas
+            .commands()
+            .iter()
+            .filter_map(|command| match command {
+                Command::Text(run) => Some(run.text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(drawn, vec!["hia"; 3]);
+    }
+}
@@ -0,0 +1,416 @@
+// How far an edit or a caret movement reaches from the caret
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Movement {
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+    Up,
+    Down,
+    Start,
+    End,
+}
+
+// Edits that are undone together when they follow each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Snapshot {
+    text: String,
+    caret: usize,
+    anchor: usize,
+}
+
+// Undo steps kept for each text input
+const HISTORY: usize = 100;
+
+// The content of a text input, with its caret, selection and undo history. Positions are byte offsets
+// into the text, always on character boundaries. The selection runs from the anchor to the caret, so
+// it's empty when they're the same.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEditor {
+    text: String,
+    caret: usize,
+    anchor: usize,
+    multiline: bool,
+    // The column Up and Down keep returning to, from before they moved through shorter lines
+    column: Option<usize>,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_edit: Option<EditKind>,
+}
+
+impl TextEditor {
+    // Single-line editors turn any line breaks they're given into spaces
+    pub fn new(text: &str, multiline: bool) -> Self {
+        let text = clean(text, multiline);
+        let end = text.len();
+        TextEditor {
+            text,
+            caret: end,
+            anchor: end,
+            multiline,
+            column: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_multiline(&self) -> bool {
+        self.multiline
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    pub fn anchor(&self) -> usize {
+        self.anchor
+    }
+
+    // The start and end of the selection, in order
+    pub fn selection(&self) -> (usize, usize) {
+        (self.caret.min(self.anchor), self.caret.max(self.anchor))
+    }
+
+    pub fn selected_text(&self) -> &str {
+        let (start, end) = self.selection();
+        &self.text[start..end]
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.caret != self.anchor
+    }
+
+    // Replaces all of the text, as a single step that can be undone
+    pub fn set_text(&mut self, text: &str) {
+        let text = clean(text, self.multiline);
+        if text == self.text {
+            return;
+        }
+        self.checkpoint(EditKind::Other);
+        self.text = text;
+        self.caret = self.text.len();
+        self.anchor = self.caret;
+    }
+
+    // Moves the caret to a position (snapped back to a character boundary), extending the selection
+    // from where it was if 'select' is set
+    pub fn set_caret(&mut self, position: usize, select: bool) {
+        let mut position = position.min(self.text.len());
+        while !self.text.is_char_boundary(position) {
+            position -= 1;
+        }
+        self.caret = position;
+        if !select {
+            self.anchor = position;
+        }
+        self.column = None;
+        self.last_edit = None;
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = 0;
+        self.caret = self.text.len();
+        self.column = None;
+        self.last_edit = None;
+    }
+
+    // Moving without 'select' collapses a selection to whichever end is in that direction
+    pub fn move_caret(&mut self, movement: Movement, select: bool) {
+        let column = self.column.unwrap_or_else(|| self.column_of(self.caret));
+        let target = match movement {
+            Movement::Left if self.has_selection() && !select => self.selection().0,
+            Movement::Right if self.has_selection() && !select => self.selection().1,
+            _ => self.position(movement),
+        };
+        self.set_caret(target, select);
+
+        if let Movement::Up | Movement::Down = movement {
+            self.column = Some(column);
+        }
+    }
+
+    // Puts the text in place of the selection, as a typed edit (so typing a word is undone at once)
+    pub fn insert(&mut self, text: &str) {
+        let text = clean(text, self.multiline);
+        if text.is_empty() && !self.has_selection() {
+            return;
+        }
+
+        // Replacing a selection is a step of its own
+        let kind = match self.has_selection() {
+            true => EditKind::Other,
+            false => EditKind::Insert,
+        };
+        self.checkpoint(kind);
+        self.replace_selection(&text);
+    }
+
+    // Removes the selection, or the text between the caret and the movement if nothing is selected
+    pub fn delete(&mut self, movement: Movement) {
+        if !self.has_selection() {
+            self.anchor = self.position(movement);
+            if !self.has_selection() {
+                return;
+            }
+        }
+        self.checkpoint(EditKind::Delete);
+        self.replace_selection("");
+    }
+
+    // Removes the selection, returning what was removed
+    pub fn cut(&mut self) -> Option<String> {
+        if !self.has_selection() {
+            return None;
+        }
+        let text = self.selected_text().to_string();
+        self.checkpoint(EditKind::Other);
+        self.replace_selection("");
+        Some(text)
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.redo.push(current);
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.undo.push(current);
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The line (counting from 0) and the column (in characters) of a position
+    pub fn line_and_column(&self, position: usize) -> (usize, usize) {
+        let line = self.text[..position].matches('\n').count();
+        (line, self.column_of(position))
+    }
+
+    // The position at a line and column, clamped to the text
+    pub fn position_at(&self, line: usize, column: usize) -> usize {
+        let start = match line {
+            0 => 0,
+            _ => match self.text.match_indices('\n').nth(line - 1) {
+                Some((i, _)) => i + 1,
+                None => return self.text.len(),
+            },
+        };
+        let end = self.line_end(start);
+        self.text[start..end]
+            .char_indices()
+            .nth(column)
+            .map_or(end, |(i, _)| start + i)
+    }
+
+    fn column_of(&self, position: usize) -> usize {
+        let start = self.line_start(position);
+        self.text[start..position].chars().count()
+    }
+
+    // Where the caret would end up after the movement
+    fn position(&self, movement: Movement) -> usize {
+        let caret = self.caret;
+        match movement {
+            Movement::Left => self.text[..caret]
+                .char_indices()
+                .next_back()
+                .map_or(0, |(i, _)| i),
+            Movement::Right => self.text[caret..]
+                .chars()
+                .next()
+                .map_or(caret, |c| caret + c.len_utf8()),
+            Movement::WordLeft => {
+                // Skip back over any spaces and punctuation, then to the start of the word before them
+                let before = &self.text[..caret];
+                let word = before.trim_end_matches(|c: char| !is_word(c));
+                word.trim_end_matches(is_word).len()
+            }
+            Movement::WordRight => {
+                let after = &self.text[caret..];
+                let word = after.trim_start_matches(|c: char| !is_word(c));
+                self.text.len() - word.trim_start_matches(is_word).len()
+            }
+            Movement::LineStart => self.line_start(caret),
+            Movement::LineEnd => self.line_end(caret),
+            Movement::Up | Movement::Down => {
+                let (line, column) = self.line_and_column(caret);
+                let column = self.column.unwrap_or(column);
+                match movement {
+                    Movement::Up if line == 0 => 0,
+                    Movement::Up => self.position_at(line - 1, column),
+                    _ if self.line_end(caret) == self.text.len() => self.text.len(),
+                    _ => self.position_at(line + 1, column),
+                }
+            }
+            Movement::Start => 0,
+            Movement::End => self.text.len(),
+        }
+    }
+
+    fn line_start(&self, position: usize) -> usize {
+        self.text[..position].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self, position: usize) -> usize {
+        self.text[position..]
+            .find('\n')
+            .map_or(self.text.len(), |i| position + i)
+    }
+
+    fn replace_selection(&mut self, text: &str) {
+        let (start, end) = self.selection();
+        self.text.replace_range(start..end, text);
+        self.caret = start + text.len();
+        self.anchor = self.caret;
+        self.column = None;
+    }
+
+    // Saves the state before an edit, unless it continues the last one
+    fn checkpoint(&mut self, kind: EditKind) {
+        if kind == EditKind::Other || self.last_edit != Some(kind) {
+            let snapshot = self.snapshot();
+            self.undo.push(snapshot);
+            if self.undo.len() > HISTORY {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.last_edit = Some(kind);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            caret: self.caret,
+            anchor: self.anchor,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.caret = snapshot.caret;
+        self.anchor = snapshot.anchor;
+        self.column = None;
+        self.last_edit = None;
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn clean(text: &str, multiline: bool) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    match multiline {
+        true => text,
+        false => text.replace('\n', " "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editing() {
+        let mut editor = TextEditor::new("hello world", false);
+        editor.move_caret(Movement::WordLeft, false);
+        assert_eq!(editor.caret(), 6);
+        editor.move_caret(Movement::WordLeft, true);
+        assert_eq!(editor.selected_text(), "hello ");
+
+        // Typing replaces the selection, and the typing after that is undone together
+        editor.insert("big");
+        editor.insert(" ");
+        editor.insert("bad ");
+        assert_eq!(editor.text(), "big bad world");
+        editor.delete(Movement::WordLeft);
+        assert_eq!(editor.text(), "big world");
+        editor.undo();
+        assert_eq!(editor.text(), "big bad world");
+        editor.undo();
+        assert_eq!(editor.text(), "bigworld");
+        editor.undo();
+        assert_eq!(editor.text(), "hello world");
+        assert_eq!(editor.selected_text(), "hello ");
+        editor.redo();
+        assert_eq!(editor.text(), "bigworld");
+
+        // A new edit can't be redone past
+        editor.insert("!");
+        assert!(!editor.redo());
+
+        editor.select_all();
+        assert_eq!(editor.cut(), Some("big!world".to_string()));
+        assert_eq!(editor.cut(), None);
+
+        // Line breaks aren't allowed on a single line
+        editor.insert("a\nb");
+        assert_eq!(editor.text(), "a b");
+        editor.move_caret(Movement::Start, false);
+        editor.move_caret(Movement::Right, true);
+        editor.move_caret(Movement::Left, false);
+        assert_eq!((editor.caret(), editor.has_selection()), (0, false));
+    }
+
+    #[test]
+    fn lines() {
+        let mut editor = TextEditor::new("first line\nab\nthird line", true);
+        assert_eq!(editor.line_and_column(editor.caret()), (2, 10));
+
+        // Up and Down stay in the same column, even after passing through a shorter line
+        editor.move_caret(Movement::WordLeft, false);
+        assert_eq!(editor.line_and_column(editor.caret()), (2, 6));
+        editor.move_caret(Movement::Up, false);
+        assert_eq!(editor.line_and_column(editor.caret()), (1, 2));
+        editor.move_caret(Movement::Up, false);
+        assert_eq!(editor.line_and_column(editor.caret()), (0, 6));
+        editor.move_caret(Movement::Up, false);
+        assert_eq!(editor.caret(), 0);
+
+        editor.move_caret(Movement::LineEnd, false);
+        editor.move_caret(Movement::Down, true);
+        assert_eq!(editor.selected_text(), "\nab");
+        editor.move_caret(Movement::Down, true);
+        editor.move_caret(Movement::Down, true);
+        assert_eq!(editor.caret(), editor.text().len());
+
+        assert_eq!(editor.position_at(1, 1), 12);
+        assert_eq!(editor.position_at(1, 9), 13);
+        assert_eq!(editor.position_at(5, 0), editor.text().len());
+
+        // Positions are bytes, but movement is by character
+        let mut editor = TextEditor::new("añb", false);
+        editor.move_caret(Movement::Left, false);
+        editor.delete(Movement::Left);
+        assert_eq!(editor.text(), "ab");
+        editor.set_caret(2, false);
+        assert_eq!(editor.caret(), 2);
+    }
+}
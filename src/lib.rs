@@ -1,18 +1,34 @@
+// Lets 'buoy-derive' output refer to '::buoy' from inside this crate as well
+extern crate self as buoy;
+
 #[macro_use]
 pub mod util;
 pub mod space;
 
+pub mod canvas;
+pub mod devices;
+pub mod text;
+
 mod core;
-pub use self::core::{context::*, device, id, message};
+pub use self::core::{context::*, device, error, id, input, message, persist, state, topic, trace};
 
 pub mod prelude {
     pub use crate::device::*;
+    pub use crate::error::BuoyError;
     pub use crate::id::Id;
+    pub use crate::input::{
+        focused_id, global_keyboard_id, keyboard_id, pointer_id, FocusMove, Key, KeyEvent,
+        KeyboardInput, Modifiers, MouseButton, PointerEvent, PointerInput, Preedit,
+    };
     pub use crate::message::*;
+    pub use crate::persist::Persist;
     pub use crate::space::*;
+    pub use crate::state::{State, StateValue};
+    pub use crate::topic::{Topic, TopicEvent};
+    pub use crate::trace::{FrameTrace, MessageTrace, TraceKind, TraceRecord};
 
     pub use crate::{
-        FrameContext, GuiContext, LayoutContext, LayoutNode, LayoutResult, LayoutTree,
-        LayoutTreeVisitor, RenderContext, ThreadContext,
+        FrameContext, GuiContext, Layer, LayoutContext, LayoutNode, LayoutResult, LayoutTree,
+        LayoutTreeVisitor, MissingRendererPolicy, RenderContext, RenderHooks, ThreadContext,
     };
 }
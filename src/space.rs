@@ -25,6 +25,14 @@ impl Region {
 
         true
     }
+
+    // Moves each edge inwards by the given thickness, without letting the size go negative
+    pub fn shrink(self, thickness: Thickness) -> Self {
+        Region {
+            pos: Point::new(self.pos.x + thickness.left, self.pos.y + thickness.top),
+            size: self.size.shrink(thickness),
+        }
+    }
 }
 
 #[repr(C)]
@@ -144,6 +152,20 @@ impl Size {
             height: self.height.max(other.height),
         }
     }
+
+    pub fn shrink(self, thickness: Thickness) -> Self {
+        Size {
+            width: (self.width - thickness.horizontal()).max(0_f32),
+            height: (self.height - thickness.vertical()).max(0_f32),
+        }
+    }
+
+    pub fn grow(self, thickness: Thickness) -> Self {
+        Size {
+            width: self.width + thickness.horizontal(),
+            height: self.height + thickness.vertical(),
+        }
+    }
 }
 
 impl Default for Size {
@@ -152,6 +174,49 @@ impl Default for Size {
     }
 }
 
+// The width of each edge of a border around a region, used for padding and margins
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Thickness {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Thickness {
+    pub fn new(left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Thickness {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn uniform(thickness: f32) -> Self {
+        Thickness::new(thickness, thickness, thickness, thickness)
+    }
+
+    pub fn symmetric(horizontal: f32, vertical: f32) -> Self {
+        Thickness::new(horizontal, vertical, horizontal, vertical)
+    }
+
+    pub fn zero() -> Self {
+        Thickness::uniform(0_f32)
+    }
+
+    // The combined thickness of the left and right edges
+    pub fn horizontal(&self) -> f32 {
+        self.left + self.right
+    }
+
+    // The combined thickness of the top and bottom edges
+    pub fn vertical(&self) -> f32 {
+        self.top + self.bottom
+    }
+}
+
 #[derive(Copy, Clone)]
 enum Align {
     Start,
@@ -176,9 +241,10 @@ fn align<T: Into<Align>>(align: T, size_dim: f32, region_dim: f32, start: f32) -
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum HAlign {
     Center,
+    #[default]
     Left,
     Right,
     LeftOffsetAbs(f32),
@@ -195,15 +261,9 @@ impl HAlign {
     }
 }
 
-impl Default for HAlign {
-    fn default() -> Self {
-        HAlign::Left
-    }
-}
-
-impl Into<Align> for HAlign {
-    fn into(self) -> Align {
-        match self {
+impl From<HAlign> for Align {
+    fn from(align: HAlign) -> Self {
+        match align {
             HAlign::Left => Align::Start,
             HAlign::LeftOffsetAbs(x) => Align::StartOffsetAbs(x),
             HAlign::LeftOffsetPct(x) => Align::StartOffsetPct(x),
@@ -216,8 +276,9 @@ impl Into<Align> for HAlign {
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum VAlign {
+    #[default]
     Top,
     TopOffsetAbs(f32),
     TopOffsetPct(f32),
@@ -235,15 +296,9 @@ impl VAlign {
     }
 }
 
-impl Default for VAlign {
-    fn default() -> Self {
-        VAlign::Top
-    }
-}
-
-impl Into<Align> for VAlign {
-    fn into(self) -> Align {
-        match self {
+impl From<VAlign> for Align {
+    fn from(align: VAlign) -> Self {
+        match align {
             VAlign::Top => Align::Start,
             VAlign::TopOffsetAbs(x) => Align::StartOffsetAbs(x),
             VAlign::TopOffsetPct(x) => Align::StartOffsetPct(x),
@@ -0,0 +1,21 @@
+// Text layout in pure Rust: fonts read from TrueType and OpenType files, bidirectional text and line
+// breaking, producing positioned glyphs for canvases to draw with 'Draw::draw_glyphs'.
+//
+// Shaping is limited to what Latin, Cyrillic, Greek, Hebrew, Arabic and CJK text need: pair kerning
+// (from 'GPOS' or 'kern') and the joining forms of Arabic letters (from 'GSUB', or the Arabic
+// Presentation Forms). There are no ligatures or mark positioning, and each character is drawn with one
+// glyph, so scripts that need more (like the Indic scripts) don't come out right.
+
+mod bidi;
+pub use self::bidi::{levels, paragraph_direction, visual_order, TextDirection};
+
+mod font;
+#[cfg(test)]
+pub(crate) use self::font::test_font;
+pub use self::font::{Font, FontCollection, FontError, FontId};
+
+mod joining;
+pub use self::joining::{joining_forms, JoiningForm};
+
+mod layout;
+pub use self::layout::{Glyph, Line, TextLayout};
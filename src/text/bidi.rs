@@ -0,0 +1,178 @@
+// A simplified version of the Unicode Bidirectional Algorithm (UAX #9), enough to lay out paragraphs
+// that mix left-to-right and right-to-left scripts. It resolves each character's embedding level from
+// the strong characters around it, but doesn't support explicit embeddings, isolates or mirroring.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl TextDirection {
+    pub(super) fn level(self) -> u8 {
+        match self {
+            TextDirection::LeftToRight => 0,
+            TextDirection::RightToLeft => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Class {
+    // Strong left-to-right, like Latin letters
+    Left,
+    // Strong right-to-left, like Hebrew and Arabic letters
+    Right,
+    Number,
+    // Spaces, punctuation and anything else that takes its direction from what's around it
+    Neutral,
+}
+
+fn class(c: char) -> Class {
+    match c as u32 {
+        // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, Mandaic and their presentation forms
+        0x0590..=0x08ff | 0xfb1d..=0xfdff | 0xfe70..=0xfefe => match c.is_numeric() {
+            true => Class::Number,
+            false if c.is_alphabetic() => Class::Right,
+            false => Class::Neutral,
+        },
+        0x10800..=0x10fff | 0x1e800..=0x1efff => Class::Right,
+        _ if c.is_numeric() => Class::Number,
+        _ if c.is_alphabetic() => Class::Left,
+        _ => Class::Neutral,
+    }
+}
+
+// The direction of the first strong character, for paragraphs that don't have one set
+pub fn paragraph_direction(text: &str) -> Option<TextDirection> {
+    text.chars().find_map(|c| match class(c) {
+        Class::Left => Some(TextDirection::LeftToRight),
+        Class::Right => Some(TextDirection::RightToLeft),
+        _ => None,
+    })
+}
+
+// The embedding level of each character in a paragraph, where odd levels are right-to-left
+pub fn levels(text: &str, direction: TextDirection) -> Vec<u8> {
+    let base = direction.level();
+    let classes: Vec<Class> = text.chars().map(class).collect();
+
+    // Numbers after right-to-left text count as right-to-left when resolving neutrals (W7), but are
+    // still drawn left-to-right
+    let mut strong = Vec::with_capacity(classes.len());
+    let mut last = direction;
+    for class in &classes {
+        let resolved = match class {
+            Class::Left => Some(TextDirection::LeftToRight),
+            Class::Right => Some(TextDirection::RightToLeft),
+            Class::Number => Some(last),
+            Class::Neutral => None,
+        };
+        if let Some(direction) = resolved {
+            last = direction;
+        }
+        strong.push(resolved);
+    }
+
+    // Neutrals between characters of the same direction take it, otherwise the paragraph's (N1, N2)
+    let mut i = 0;
+    while i < strong.len() {
+        if strong[i].is_some() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < strong.len() && strong[i].is_none() {
+            i += 1;
+        }
+        let before = match start {
+            0 => direction,
+            _ => strong[start - 1].unwrap(),
+        };
+        let after = strong.get(i).copied().flatten().unwrap_or(direction);
+        let resolved = match before == after {
+            true => before,
+            false => direction,
+        };
+        for value in &mut strong[start..i] {
+            *value = Some(resolved);
+        }
+    }
+
+    // Levels go up from the paragraph's to fit each direction (I1, I2)
+    classes
+        .iter()
+        .zip(strong)
+        .map(|(class, direction)| {
+            let rtl = direction == Some(TextDirection::RightToLeft);
+            match (base, class, rtl) {
+                (0, Class::Number, true) => 2,
+                (0, _, true) => 1,
+                (0, _, false) => 0,
+                (_, Class::Right, _) => 1,
+                (_, Class::Neutral, true) => 1,
+                _ => 2,
+            }
+        })
+        .collect()
+}
+
+// The order to draw a line's characters in from left to right, given their levels: every run at or
+// above each odd level is reversed, highest level first (L2)
+pub fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = levels.iter().copied().filter(|l| l % 2 == 1).min();
+    let lowest_odd = match lowest_odd {
+        Some(level) => level,
+        None if highest > 0 => 1,
+        None => return order,
+    };
+
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visual(text: &str, direction: TextDirection) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let levels = levels(text, direction);
+        visual_order(&levels)
+            .into_iter()
+            .map(|i| chars[i])
+            .collect()
+    }
+
+    #[test]
+    fn reorder() {
+        let ltr = TextDirection::LeftToRight;
+        let rtl = TextDirection::RightToLeft;
+        assert_eq!(paragraph_direction("123 abc"), Some(ltr));
+        assert_eq!(paragraph_direction("- שלום"), Some(rtl));
+        assert_eq!(paragraph_direction("123"), None);
+
+        assert_eq!(visual("abc def", ltr), "abc def");
+        assert_eq!(visual("abc אבג דהו ghi", ltr), "abc והד גבא ghi");
+        // Numbers keep their order inside right-to-left text
+        assert_eq!(visual("אב 123 גד", rtl), "דג 123 בא");
+        assert_eq!(visual("אבג abc 12", rtl), "abc 12 גבא");
+        assert_eq!(levels("a אב", ltr), vec![0, 0, 1, 1]);
+        assert_eq!(levels("א ab 1", rtl), vec![1, 1, 2, 2, 2, 2]);
+    }
+}
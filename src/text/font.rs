@@ -0,0 +1,954 @@
+use super::joining::{self, JoiningForm};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(io::Error),
+    // The file isn't a font, or is cut short or inconsistent
+    Malformed(&'static str),
+    // The file is a font, but uses something this parser can't read (like a collection)
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Io(error) => write!(f, "Couldn't read the font: {}", error),
+            FontError::Malformed(what) => write!(f, "Malformed font: {}", what),
+            FontError::Unsupported(what) => write!(f, "Unsupported font: {}", what),
+        }
+    }
+}
+
+impl Error for FontError {}
+
+impl From<io::Error> for FontError {
+    fn from(error: io::Error) -> Self {
+        FontError::Io(error)
+    }
+}
+
+// Which font in a 'FontCollection' a glyph comes from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FontId(pub u32);
+
+#[derive(Clone, Copy, Debug)]
+enum Cmap {
+    // Segments mapping ranges of BMP characters
+    Format4(usize),
+    // Groups mapping ranges of any characters
+    Format12(usize),
+}
+
+// A TrueType or OpenType font, read for what's needed to lay out text: which glyph draws each
+// character, how far apart glyphs go, and the vertical metrics. Pair kerning comes from the 'kern'
+// feature in the 'GPOS' table, or the older 'kern' table if there isn't one, and the joining forms for
+// Arabic from the 'isol', 'init', 'medi' and 'fina' features in the 'GSUB' table. Nothing else in those
+// tables is read (no ligatures or mark positioning), and features apply whatever the script and
+// language. Glyph outlines aren't read either, since drawing them is up to the backend.
+// Metrics are in ems, so they're multiplied by the font size.
+pub struct Font {
+    data: Vec<u8>,
+    units_per_em: f32,
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+    glyph_count: u16,
+    metric_count: u16,
+    hmtx: usize,
+    cmap: Cmap,
+    kerning: HashMap<(u16, u16), i16>,
+    // The pair adjustment lookups of the 'kern' feature in 'GPOS', as the offsets of their subtables
+    pair_lookups: Vec<Vec<usize>>,
+    // The single substitution lookups of the features for each joining form in 'GSUB', in the same way
+    form_lookups: [Vec<Vec<usize>>; 4],
+}
+
+impl Font {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Font, FontError> {
+        Font::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, FontError> {
+        let tables = tables(&data)?;
+        let table = |tag: &[u8; 4]| {
+            tables
+                .get(tag)
+                .copied()
+                .ok_or(FontError::Malformed("missing a required table"))
+        };
+
+        let head = table(b"head")?;
+        let units_per_em = read_u16(&data, head + 18)?;
+        if units_per_em == 0 {
+            return Err(FontError::Malformed("units per em is zero"));
+        }
+
+        let hhea = table(b"hhea")?;
+        let ascent = read_i16(&data, hhea + 4)?;
+        let descent = read_i16(&data, hhea + 6)?;
+        let line_gap = read_i16(&data, hhea + 8)?;
+        let metric_count = read_u16(&data, hhea + 34)?;
+        if metric_count == 0 {
+            return Err(FontError::Malformed("no horizontal metrics"));
+        }
+
+        let glyph_count = read_u16(&data, table(b"maxp")? + 4)?;
+        let hmtx = table(b"hmtx")?;
+        read_u16(&data, hmtx + metric_count as usize * 4 - 2)?;
+
+        let cmap = cmap(&data, table(b"cmap")?)?;
+        let kerning = match tables.get(b"kern") {
+            Some(kern) => kerning(&data, *kern)?,
+            None => HashMap::new(),
+        };
+        let pair_lookups = match tables.get(b"GPOS") {
+            Some(gpos) => feature_lookups(&data, *gpos, b"kern", PAIR_ADJUSTMENT, 9)?,
+            None => Vec::new(),
+        };
+        let mut form_lookups: [Vec<Vec<usize>>; 4] = Default::default();
+        if let Some(gsub) = tables.get(b"GSUB") {
+            for (i, form) in FORMS.iter().enumerate() {
+                form_lookups[i] =
+                    feature_lookups(&data, *gsub, form.feature(), SINGLE_SUBSTITUTION, 7)?;
+            }
+        }
+
+        let units = units_per_em as f32;
+        Ok(Font {
+            data,
+            units_per_em: units,
+            ascent: ascent as f32 / units,
+            descent: descent as f32 / units,
+            line_gap: line_gap as f32 / units,
+            glyph_count,
+            metric_count,
+            hmtx,
+            cmap,
+            kerning,
+            pair_lookups,
+            form_lookups,
+        })
+    }
+
+    // How far above the baseline the font reaches
+    pub fn ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    // How far below the baseline the font reaches, as a negative number
+    pub fn descent(&self) -> f32 {
+        self.descent
+    }
+
+    pub fn line_gap(&self) -> f32 {
+        self.line_gap
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+
+    pub fn glyph_count(&self) -> u16 {
+        self.glyph_count
+    }
+
+    // The glyph for a character, if the font has one (glyph 0 is the font's "missing" glyph)
+    pub fn glyph_id(&self, c: char) -> Option<u16> {
+        let c = c as u32;
+        let glyph = match self.cmap {
+            Cmap::Format4(offset) => lookup_format4(&self.data, offset, c),
+            Cmap::Format12(offset) => lookup_format12(&self.data, offset, c),
+        };
+        glyph.filter(|glyph| *glyph != 0 && *glyph < self.glyph_count)
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.glyph_id(c).is_some()
+    }
+
+    pub fn advance(&self, glyph: u16) -> f32 {
+        // Glyphs past the last metric all share its advance
+        let index = glyph.min(self.metric_count - 1) as usize;
+        let advance = read_u16(&self.data, self.hmtx + index * 4).unwrap_or(0);
+        advance as f32 / self.units_per_em
+    }
+
+    // The adjustment to the space between two glyphs (in the order they're in the text), on top of the
+    // first one's advance
+    pub fn kerning(&self, first: u16, second: u16) -> f32 {
+        if !self.pair_lookups.is_empty() {
+            // Each lookup adds its adjustment from the first of its subtables with the pair in it
+            let units: i32 = self
+                .pair_lookups
+                .iter()
+                .filter_map(|subtables| {
+                    subtables
+                        .iter()
+                        .find_map(|subtable| pair_adjustment(&self.data, *subtable, first, second))
+                })
+                .map(i32::from)
+                .sum();
+            return units as f32 / self.units_per_em;
+        }
+
+        match self.kerning.get(&(first, second)) {
+            Some(value) => *value as f32 / self.units_per_em,
+            None => 0_f32,
+        }
+    }
+
+    // The glyph that draws a character in one of its joining forms, given the glyph for the character
+    // itself. Fonts without any of the joining features in 'GSUB' fall back to the character for the
+    // form in the Arabic Presentation Forms, if they have it.
+    pub fn form_glyph(&self, c: char, glyph: u16, form: JoiningForm) -> u16 {
+        if self.form_lookups.iter().all(Vec::is_empty) {
+            return joining::presentation_form(c, form)
+                .and_then(|c| self.glyph_id(c))
+                .unwrap_or(glyph);
+        }
+
+        let index = FORMS.iter().position(|f| *f == form).unwrap();
+        self.form_lookups[index]
+            .iter()
+            .fold(glyph, |glyph, subtables| {
+                subtables
+                    .iter()
+                    .find_map(|subtable| single_substitution(&self.data, *subtable, glyph))
+                    .unwrap_or(glyph)
+            })
+    }
+}
+
+// Fonts to lay out text with, in the order they're tried in. Each character uses the first font that
+// has a glyph for it, or the first font if none of them do.
+#[derive(Default)]
+pub struct FontCollection {
+    fonts: Vec<Font>,
+}
+
+impl FontCollection {
+    pub fn new() -> Self {
+        FontCollection::default()
+    }
+
+    pub fn add(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() as u32 - 1)
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<FontId, FontError> {
+        Ok(self.add(Font::from_file(path)?))
+    }
+
+    pub fn get(&self, id: FontId) -> Option<&Font> {
+        self.fonts.get(id.0 as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fonts.len()
+    }
+
+    pub fn font_for(&self, c: char) -> Option<(FontId, &Font)> {
+        let index = self
+            .fonts
+            .iter()
+            .position(|font| font.has_glyph(c))
+            .unwrap_or(0);
+        let font = self.fonts.get(index)?;
+        Some((FontId(index as u32), font))
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, FontError> {
+    match data.get(offset..offset + 2) {
+        Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+        None => Err(FontError::Malformed("unexpected end of data")),
+    }
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Result<i16, FontError> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, FontError> {
+    match data.get(offset..offset + 4) {
+        Some(bytes) => Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        None => Err(FontError::Malformed("unexpected end of data")),
+    }
+}
+
+// Where each table starts, by tag
+fn tables(data: &[u8]) -> Result<HashMap<[u8; 4], usize>, FontError> {
+    match read_u32(data, 0)? {
+        0x0001_0000 | 0x4f54_544f | 0x7472_7565 => (),
+        0x7474_6366 => return Err(FontError::Unsupported("font collections")),
+        _ => return Err(FontError::Malformed("not a TrueType or OpenType font")),
+    }
+
+    let count = read_u16(data, 4)? as usize;
+    let mut tables = HashMap::new();
+    for i in 0..count {
+        let record = 12 + i * 16;
+        let tag = read_u32(data, record)?.to_be_bytes();
+        let offset = read_u32(data, record + 8)? as usize;
+        let length = read_u32(data, record + 12)? as usize;
+        if offset
+            .checked_add(length)
+            .is_none_or(|end| end > data.len())
+        {
+            return Err(FontError::Malformed("table out of bounds"));
+        }
+        tables.insert(tag, offset);
+    }
+    Ok(tables)
+}
+
+// Picks the best Unicode subtable: full repertoire first, then the BMP
+fn cmap(data: &[u8], cmap: usize) -> Result<Cmap, FontError> {
+    let count = read_u16(data, cmap + 2)? as usize;
+    let mut best: Option<(u8, Cmap)> = None;
+    for i in 0..count {
+        let record = cmap + 4 + i * 8;
+        let platform = read_u16(data, record)?;
+        let encoding = read_u16(data, record + 2)?;
+        let offset = cmap + read_u32(data, record + 4)? as usize;
+
+        if !matches!((platform, encoding), (0, _) | (3, 1) | (3, 10)) {
+            continue;
+        }
+        let candidate = match read_u16(data, offset)? {
+            12 => (2, Cmap::Format12(offset)),
+            4 => (1, Cmap::Format4(offset)),
+            _ => continue,
+        };
+        if best.as_ref().is_none_or(|(rank, _)| candidate.0 > *rank) {
+            best = Some(candidate);
+        }
+    }
+
+    match best {
+        Some((_, cmap)) => Ok(cmap),
+        None => Err(FontError::Unsupported("no Unicode character map")),
+    }
+}
+
+fn lookup_format4(data: &[u8], offset: usize, c: u32) -> Option<u16> {
+    if c > 0xffff {
+        return None;
+    }
+    let c = c as u16;
+    let segments = read_u16(data, offset + 6).ok()? as usize / 2;
+    let ends = offset + 14;
+    let starts = ends + segments * 2 + 2;
+    let deltas = starts + segments * 2;
+    let range_offsets = deltas + segments * 2;
+
+    for i in 0..segments {
+        let end = read_u16(data, ends + i * 2).ok()?;
+        if end < c {
+            continue;
+        }
+        let start = read_u16(data, starts + i * 2).ok()?;
+        if start > c {
+            return None;
+        }
+
+        let delta = read_u16(data, deltas + i * 2).ok()?;
+        let range_offset = read_u16(data, range_offsets + i * 2).ok()?;
+        if range_offset == 0 {
+            return Some(c.wrapping_add(delta));
+        }
+        // The offset is from where it's stored, into the glyph array that follows
+        let address = range_offsets + i * 2 + range_offset as usize + (c - start) as usize * 2;
+        let glyph = read_u16(data, address).ok()?;
+        return match glyph {
+            0 => None,
+            glyph => Some(glyph.wrapping_add(delta)),
+        };
+    }
+    None
+}
+
+fn lookup_format12(data: &[u8], offset: usize, c: u32) -> Option<u16> {
+    let groups = read_u32(data, offset + 12).ok()? as usize;
+    for i in 0..groups {
+        let group = offset + 16 + i * 12;
+        let start = read_u32(data, group).ok()?;
+        let end = read_u32(data, group + 4).ok()?;
+        if (start..=end).contains(&c) {
+            let glyph = read_u32(data, group + 8).ok()? + (c - start);
+            return u16::try_from(glyph).ok();
+        }
+    }
+    None
+}
+
+// Reads the pairs from the horizontal format 0 subtables of a (Microsoft style) 'kern' table
+fn kerning(data: &[u8], kern: usize) -> Result<HashMap<(u16, u16), i16>, FontError> {
+    let mut pairs = HashMap::new();
+    if read_u16(data, kern)? != 0 {
+        return Ok(pairs);
+    }
+
+    let count = read_u16(data, kern + 2)?;
+    let mut subtable = kern + 4;
+    for _ in 0..count {
+        let length = read_u16(data, subtable + 2)? as usize;
+        let coverage = read_u16(data, subtable + 4)?;
+        let (horizontal, format) = (coverage & 1 != 0, coverage >> 8);
+        if horizontal && format == 0 {
+            let count = read_u16(data, subtable + 6)? as usize;
+            for i in 0..count {
+                let pair = subtable + 14 + i * 6;
+                let left = read_u16(data, pair)?;
+                let right = read_u16(data, pair + 2)?;
+                pairs.insert((left, right), read_i16(data, pair + 4)?);
+            }
+        }
+        subtable += length;
+    }
+    Ok(pairs)
+}
+
+const FORMS: [JoiningForm; 4] = [
+    JoiningForm::Isolated,
+    JoiningForm::Initial,
+    JoiningForm::Medial,
+    JoiningForm::Final,
+];
+
+const SINGLE_SUBSTITUTION: u16 = 1;
+const PAIR_ADJUSTMENT: u16 = 2;
+
+// The lookups a 'GSUB' or 'GPOS' table runs for a feature (under any script), in the order they're
+// run, each as the offsets of its subtables. Lookups of other types are left out, apart from the
+// extension lookups wrapping the given type.
+fn feature_lookups(
+    data: &[u8],
+    table: usize,
+    feature: &[u8; 4],
+    lookup_type: u16,
+    extension_type: u16,
+) -> Result<Vec<Vec<usize>>, FontError> {
+    let features = table + read_u16(data, table + 6)? as usize;
+    let lookups = table + read_u16(data, table + 8)? as usize;
+    let lookup_count = read_u16(data, lookups)?;
+
+    let mut indices = Vec::new();
+    for i in 0..read_u16(data, features)? as usize {
+        let record = features + 2 + i * 6;
+        if read_u32(data, record)?.to_be_bytes() != *feature {
+            continue;
+        }
+        let offset = features + read_u16(data, record + 4)? as usize;
+        for j in 0..read_u16(data, offset + 2)? as usize {
+            let index = read_u16(data, offset + 4 + j * 2)?;
+            if index >= lookup_count {
+                return Err(FontError::Malformed("lookup index out of bounds"));
+            }
+            indices.push(index as usize);
+        }
+    }
+    indices.sort_unstable();
+    indices.dedup();
+
+    let mut result = Vec::new();
+    for index in indices {
+        let lookup = lookups + read_u16(data, lookups + 2 + index * 2)? as usize;
+        let kind = read_u16(data, lookup)?;
+        let mut subtables = Vec::new();
+        for i in 0..read_u16(data, lookup + 4)? as usize {
+            let mut subtable = lookup + read_u16(data, lookup + 6 + i * 2)? as usize;
+            let mut subtable_kind = kind;
+            if kind == extension_type {
+                subtable_kind = read_u16(data, subtable + 2)?;
+                subtable += read_u32(data, subtable + 4)? as usize;
+            }
+            if subtable_kind == lookup_type {
+                read_u16(data, subtable)?;
+                subtables.push(subtable);
+            }
+        }
+        if !subtables.is_empty() {
+            result.push(subtables);
+        }
+    }
+    Ok(result)
+}
+
+// Where a glyph is in a coverage table, if it's there
+fn coverage(data: &[u8], offset: usize, glyph: u16) -> Option<usize> {
+    let count = read_u16(data, offset + 2).ok()? as usize;
+    match read_u16(data, offset).ok()? {
+        1 => (0..count).find(|i| read_u16(data, offset + 4 + i * 2).ok() == Some(glyph)),
+        2 => (0..count).find_map(|i| {
+            let range = offset + 4 + i * 6;
+            let start = read_u16(data, range).ok()?;
+            let end = read_u16(data, range + 2).ok()?;
+            if !(start..=end).contains(&glyph) {
+                return None;
+            }
+            let index = read_u16(data, range + 4).ok()?;
+            Some((index + glyph - start) as usize)
+        }),
+        _ => None,
+    }
+}
+
+// The class a class definition table puts a glyph in (0 for any it leaves out)
+fn class(data: &[u8], offset: usize, glyph: u16) -> u16 {
+    let lookup = || match read_u16(data, offset).ok()? {
+        1 => {
+            let start = read_u16(data, offset + 2).ok()?;
+            let count = read_u16(data, offset + 4).ok()?;
+            let index = glyph.checked_sub(start).filter(|index| *index < count)?;
+            read_u16(data, offset + 6 + index as usize * 2).ok()
+        }
+        2 => (0..read_u16(data, offset + 2).ok()? as usize).find_map(|i| {
+            let range = offset + 4 + i * 6;
+            let start = read_u16(data, range).ok()?;
+            let end = read_u16(data, range + 2).ok()?;
+            match (start..=end).contains(&glyph) {
+                true => read_u16(data, range + 4).ok(),
+                false => None,
+            }
+        }),
+        _ => None,
+    };
+    lookup().unwrap_or(0)
+}
+
+// The horizontal advance adjustment in a value record, which comes after the placements (if they're
+// there) when it's in the record at all
+fn x_advance(data: &[u8], record: usize, format: u16) -> Option<i16> {
+    if format & 0x0004 == 0 {
+        return Some(0);
+    }
+    read_i16(data, record + 2 * (format & 0x0003).count_ones() as usize).ok()
+}
+
+// The advance adjustment for the first glyph of a pair, from a pair adjustment subtable (of either
+// format). 'None' if the subtable doesn't cover the pair.
+fn pair_adjustment(data: &[u8], subtable: usize, first: u16, second: u16) -> Option<i16> {
+    let index = coverage(
+        data,
+        subtable + read_u16(data, subtable + 2).ok()? as usize,
+        first,
+    )?;
+    let format1 = read_u16(data, subtable + 4).ok()?;
+    let format2 = read_u16(data, subtable + 6).ok()?;
+    let record_size = 2 * (format1 & 0xff).count_ones() as usize;
+    let pair_size = record_size + 2 * (format2 & 0xff).count_ones() as usize;
+
+    match read_u16(data, subtable).ok()? {
+        // Pairs listed out for each first glyph
+        1 => {
+            let set = subtable + read_u16(data, subtable + 10 + index * 2).ok()? as usize;
+            let count = read_u16(data, set).ok()? as usize;
+            let pair = (0..count)
+                .map(|i| set + 2 + i * (2 + pair_size))
+                .find(|pair| read_u16(data, *pair).ok() == Some(second))?;
+            x_advance(data, pair + 2, format1)
+        }
+        // Adjustments for pairs of glyph classes
+        2 => {
+            let first = class(
+                data,
+                subtable + read_u16(data, subtable + 8).ok()? as usize,
+                first,
+            );
+            let second = class(
+                data,
+                subtable + read_u16(data, subtable + 10).ok()? as usize,
+                second,
+            );
+            let first_count = read_u16(data, subtable + 12).ok()?;
+            let second_count = read_u16(data, subtable + 14).ok()?;
+            if first >= first_count || second >= second_count {
+                return None;
+            }
+            let pair = first as usize * second_count as usize + second as usize;
+            x_advance(data, subtable + 16 + pair * pair_size, format1)
+        }
+        _ => None,
+    }
+}
+
+// The glyph a single substitution subtable (of either format) replaces one with, if it covers it
+fn single_substitution(data: &[u8], subtable: usize, glyph: u16) -> Option<u16> {
+    let index = coverage(
+        data,
+        subtable + read_u16(data, subtable + 2).ok()? as usize,
+        glyph,
+    )?;
+    match read_u16(data, subtable).ok()? {
+        1 => Some(glyph.wrapping_add(read_u16(data, subtable + 4).ok()?)),
+        2 => read_u16(data, subtable + 6 + index * 2).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+fn push16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+#[cfg(test)]
+fn push32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+// Builds a minimal font for tests: 1000 units per em, an ascent of 800 and a descent of 200, and a
+// glyph with the given advance for each character (in order, starting from glyph 1)
+#[cfg(test)]
+pub(crate) fn test_font(glyphs: &[(char, u16)], kerning: &[(char, char, i16)]) -> Vec<u8> {
+    test_font_with(glyphs, kerning, Vec::new())
+}
+
+// The same, with more tables (like the ones from 'test_layout_table')
+#[cfg(test)]
+pub(crate) fn test_font_with(
+    glyphs: &[(char, u16)],
+    kerning: &[(char, char, i16)],
+    extra: Vec<(&[u8; 4], Vec<u8>)>,
+) -> Vec<u8> {
+    let glyph_count = glyphs.len() as u16 + 1;
+    let glyph = |c: char| glyphs.iter().position(|(g, _)| *g == c).unwrap() as u16 + 1;
+
+    let mut head = vec![0; 54];
+    head[18..20].copy_from_slice(&1000_u16.to_be_bytes());
+
+    let mut hhea = vec![0; 36];
+    hhea[4..6].copy_from_slice(&800_i16.to_be_bytes());
+    hhea[6..8].copy_from_slice(&(-200_i16).to_be_bytes());
+    hhea[34..36].copy_from_slice(&glyph_count.to_be_bytes());
+
+    let mut maxp = vec![0, 0, 0x50, 0];
+    push16(&mut maxp, glyph_count);
+
+    let mut hmtx = vec![0; 4];
+    for (_, advance) in glyphs {
+        push16(&mut hmtx, *advance);
+        push16(&mut hmtx, 0);
+    }
+
+    // A format 12 subtable, with a group for each character
+    let mut cmap = Vec::new();
+    push16(&mut cmap, 0);
+    push16(&mut cmap, 1);
+    push16(&mut cmap, 3);
+    push16(&mut cmap, 10);
+    push32(&mut cmap, 12);
+    push16(&mut cmap, 12);
+    push16(&mut cmap, 0);
+    push32(&mut cmap, 16 + glyphs.len() as u32 * 12);
+    push32(&mut cmap, 0);
+    push32(&mut cmap, glyphs.len() as u32);
+    for (c, _) in glyphs {
+        push32(&mut cmap, *c as u32);
+        push32(&mut cmap, *c as u32);
+        push32(&mut cmap, glyph(*c) as u32);
+    }
+
+    let mut kern = Vec::new();
+    push16(&mut kern, 0);
+    push16(&mut kern, 1);
+    push16(&mut kern, 0);
+    push16(&mut kern, 14 + kerning.len() as u16 * 6);
+    push16(&mut kern, 1);
+    push16(&mut kern, kerning.len() as u16);
+    kern.extend_from_slice(&[0; 6]);
+    for (left, right, value) in kerning {
+        push16(&mut kern, glyph(*left));
+        push16(&mut kern, glyph(*right));
+        push16(&mut kern, *value as u16);
+    }
+
+    let mut tables: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"cmap", cmap),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"kern", kern),
+        (b"maxp", maxp),
+    ];
+    tables.extend(extra);
+    tables.sort_by_key(|(tag, _)| **tag);
+    let mut font = Vec::new();
+    push32(&mut font, 0x0001_0000);
+    push16(&mut font, tables.len() as u16);
+    font.extend_from_slice(&[0; 6]);
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, table) in &tables {
+        font.extend_from_slice(*tag);
+        push32(&mut font, 0);
+        push32(&mut font, offset as u32);
+        push32(&mut font, table.len() as u32);
+        offset += table.len();
+    }
+    for (_, table) in &tables {
+        font.extend_from_slice(table);
+    }
+    font
+}
+
+// Builds a 'GSUB' or 'GPOS' table for tests, with a feature for each lookup (of the given type, and
+// with one subtable)
+#[cfg(test)]
+pub(crate) fn test_layout_table(lookups: &[(&[u8; 4], u16, Vec<u8>)]) -> Vec<u8> {
+    let count = lookups.len() as u16;
+    let mut table = Vec::new();
+    push32(&mut table, 0x0001_0000);
+    push16(&mut table, 10);
+    push16(&mut table, 12);
+    push16(&mut table, 12 + 2 + count * 12);
+    // No scripts, since they aren't read
+    push16(&mut table, 0);
+
+    push16(&mut table, count);
+    for (i, (tag, _, _)) in lookups.iter().enumerate() {
+        table.extend_from_slice(*tag);
+        push16(&mut table, 2 + count * 6 + i as u16 * 6);
+    }
+    for i in 0..count {
+        push16(&mut table, 0);
+        push16(&mut table, 1);
+        push16(&mut table, i);
+    }
+
+    push16(&mut table, count);
+    let mut offset = 2 + count * 2;
+    for (_, _, subtable) in lookups {
+        push16(&mut table, offset);
+        offset += 8 + subtable.len() as u16;
+    }
+    for (_, kind, subtable) in lookups {
+        push16(&mut table, *kind);
+        push16(&mut table, 0);
+        push16(&mut table, 1);
+        push16(&mut table, 8);
+        table.extend_from_slice(subtable);
+    }
+    table
+}
+
+// A coverage table (in format 1) for glyphs in order
+#[cfg(test)]
+fn test_coverage(glyphs: &[u16]) -> Vec<u8> {
+    let mut coverage = Vec::new();
+    push16(&mut coverage, 1);
+    push16(&mut coverage, glyphs.len() as u16);
+    for glyph in glyphs {
+        push16(&mut coverage, *glyph);
+    }
+    coverage
+}
+
+// A pair adjustment subtable (in format 1) adjusting the advance of the first glyph of each pair
+#[cfg(test)]
+pub(crate) fn test_pair_pos(pairs: &[(u16, u16, i16)]) -> Vec<u8> {
+    let mut firsts: Vec<u16> = pairs.iter().map(|(first, _, _)| *first).collect();
+    firsts.sort_unstable();
+    firsts.dedup();
+
+    let mut sets = Vec::new();
+    let mut set_offsets = Vec::new();
+    for first in &firsts {
+        set_offsets.push(sets.len());
+        let set: Vec<_> = pairs.iter().filter(|(f, _, _)| f == first).collect();
+        push16(&mut sets, set.len() as u16);
+        for (_, second, value) in set {
+            push16(&mut sets, *second);
+            push16(&mut sets, *value as u16);
+        }
+    }
+
+    let header = 10 + firsts.len() * 2;
+    let mut subtable = Vec::new();
+    push16(&mut subtable, 1);
+    push16(&mut subtable, (header + sets.len()) as u16);
+    push16(&mut subtable, 0x0004);
+    push16(&mut subtable, 0);
+    push16(&mut subtable, firsts.len() as u16);
+    for offset in set_offsets {
+        push16(&mut subtable, (header + offset) as u16);
+    }
+    subtable.extend(sets);
+    subtable.extend(test_coverage(&firsts));
+    subtable
+}
+
+// A pair adjustment subtable (in format 2) adjusting the advance of any of the first glyphs followed by
+// any of the second ones. The first glyphs are covered by ranges, and put in a class by a format 1
+// class definition, and the second ones by a format 2 class definition.
+#[cfg(test)]
+pub(crate) fn test_class_pair_pos(first: &[u16], second: &[u16], value: i16) -> Vec<u8> {
+    let start = *first.iter().min().unwrap();
+    let end = *first.iter().max().unwrap();
+
+    let mut coverage = Vec::new();
+    push16(&mut coverage, 2);
+    push16(&mut coverage, first.len() as u16);
+    let mut sorted = first.to_vec();
+    sorted.sort_unstable();
+    for (i, glyph) in sorted.iter().enumerate() {
+        push16(&mut coverage, *glyph);
+        push16(&mut coverage, *glyph);
+        push16(&mut coverage, i as u16);
+    }
+
+    let mut first_classes = Vec::new();
+    push16(&mut first_classes, 1);
+    push16(&mut first_classes, start);
+    push16(&mut first_classes, end - start + 1);
+    for glyph in start..=end {
+        push16(&mut first_classes, first.contains(&glyph) as u16);
+    }
+
+    let mut second_classes = Vec::new();
+    push16(&mut second_classes, 2);
+    push16(&mut second_classes, second.len() as u16);
+    for glyph in second {
+        push16(&mut second_classes, *glyph);
+        push16(&mut second_classes, *glyph);
+        push16(&mut second_classes, 1);
+    }
+
+    // Two classes each (class 0 being every other glyph), and an adjustment for classes 1 and 1
+    let header = 16 + 4 * 2;
+    let mut subtable = Vec::new();
+    push16(&mut subtable, 2);
+    push16(&mut subtable, header as u16);
+    push16(&mut subtable, 0x0004);
+    push16(&mut subtable, 0);
+    push16(&mut subtable, (header + coverage.len()) as u16);
+    push16(
+        &mut subtable,
+        (header + coverage.len() + first_classes.len()) as u16,
+    );
+    push16(&mut subtable, 2);
+    push16(&mut subtable, 2);
+    for class in 0..4 {
+        push16(&mut subtable, if class == 3 { value as u16 } else { 0 });
+    }
+    subtable.extend(coverage);
+    subtable.extend(first_classes);
+    subtable.extend(second_classes);
+    subtable
+}
+
+// A single substitution subtable (in format 2) replacing each of the glyphs
+#[cfg(test)]
+pub(crate) fn test_single_subst(substitutions: &[(u16, u16)]) -> Vec<u8> {
+    let mut substitutions = substitutions.to_vec();
+    substitutions.sort_unstable();
+    let glyphs: Vec<u16> = substitutions.iter().map(|(glyph, _)| *glyph).collect();
+
+    let mut subtable = Vec::new();
+    push16(&mut subtable, 2);
+    push16(&mut subtable, 6 + glyphs.len() as u16 * 2);
+    push16(&mut subtable, glyphs.len() as u16);
+    for (_, substitute) in &substitutions {
+        push16(&mut subtable, *substitute);
+    }
+    subtable.extend(test_coverage(&glyphs));
+    subtable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let data = test_font(&[('a', 500), ('b', 600), ('日', 1000)], &[('a', 'b', -50)]);
+        let font = Font::from_bytes(data).unwrap();
+        assert_eq!((font.ascent(), font.descent()), (0.8_f32, -0.2_f32));
+        assert_eq!(font.line_height(), 1_f32);
+
+        let a = font.glyph_id('a').unwrap();
+        let b = font.glyph_id('b').unwrap();
+        assert_eq!(font.glyph_id('日'), Some(3));
+        assert_eq!(font.glyph_id('c'), None);
+        assert_eq!(font.advance(b), 0.6_f32);
+        assert_eq!(font.kerning(a, b), -0.05_f32);
+        assert_eq!(font.kerning(b, a), 0_f32);
+
+        assert!(matches!(
+            Font::from_bytes(vec![0, 1, 0, 0]),
+            Err(FontError::Malformed(_))
+        ));
+        assert!(matches!(
+            Font::from_bytes(b"ttcf".to_vec()),
+            Err(FontError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn fallback() {
+        let mut fonts = FontCollection::new();
+        assert!(fonts.font_for('a').is_none());
+        let latin = fonts.add(Font::from_bytes(test_font(&[('a', 500)], &[])).unwrap());
+        let cjk = fonts.add(Font::from_bytes(test_font(&[('日', 1000)], &[])).unwrap());
+
+        assert_eq!(fonts.font_for('a').unwrap().0, latin);
+        assert_eq!(fonts.font_for('日').unwrap().0, cjk);
+        // Nothing has it, so the first font's missing glyph is used
+        assert_eq!(fonts.font_for('?').unwrap().0, latin);
+    }
+
+    #[test]
+    fn gpos_kerning() {
+        let glyphs = [('a', 500), ('b', 600), ('c', 700), ('d', 800)];
+        let gpos = test_layout_table(&[
+            (
+                b"kern",
+                PAIR_ADJUSTMENT,
+                test_pair_pos(&[(1, 2, -40), (3, 4, 25)]),
+            ),
+            (
+                b"kern",
+                PAIR_ADJUSTMENT,
+                test_class_pair_pos(&[1, 3], &[2, 4], -10),
+            ),
+            (b"liga", PAIR_ADJUSTMENT, test_pair_pos(&[(2, 1, 100)])),
+        ]);
+        let data = test_font_with(&glyphs, &[('b', 'a', -70)], vec![(b"GPOS", gpos)]);
+        let font = Font::from_bytes(data).unwrap();
+
+        // Both lookups apply, and the 'kern' table is ignored when there's a 'GPOS' one
+        assert_eq!(font.kerning(1, 2), -0.05_f32);
+        assert_eq!(font.kerning(3, 4), 0.015_f32);
+        assert_eq!(font.kerning(1, 4), -0.01_f32);
+        assert_eq!(font.kerning(2, 1), 0_f32);
+        assert_eq!(font.kerning(4, 3), 0_f32);
+    }
+
+    #[test]
+    fn joining_forms() {
+        let glyphs = [('ب', 500), ('\u{fe91}', 300), ('x', 400), ('y', 400)];
+        let gsub = test_layout_table(&[
+            (b"init", SINGLE_SUBSTITUTION, test_single_subst(&[(1, 3)])),
+            (b"fina", SINGLE_SUBSTITUTION, test_single_subst(&[(1, 4)])),
+        ]);
+        let font = Font::from_bytes(test_font_with(&glyphs, &[], vec![(b"GSUB", gsub)])).unwrap();
+        assert_eq!(font.form_glyph('ب', 1, JoiningForm::Initial), 3);
+        assert_eq!(font.form_glyph('ب', 1, JoiningForm::Final), 4);
+        assert_eq!(font.form_glyph('ب', 1, JoiningForm::Medial), 1);
+
+        // Without the features, the presentation form is used if the font has it
+        let font = Font::from_bytes(test_font(&glyphs, &[])).unwrap();
+        assert_eq!(font.form_glyph('ب', 1, JoiningForm::Initial), 2);
+        assert_eq!(font.form_glyph('ب', 1, JoiningForm::Final), 1);
+    }
+}
@@ -0,0 +1,195 @@
+// Cursive joining for Arabic script, which draws each letter in a different form depending on whether
+// it connects to the letters on either side of it. Fonts provide the forms through their 'isol', 'init',
+// 'medi' and 'fina' features, or as the Arabic Presentation Forms in their character map.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JoiningForm {
+    // Connected on neither side
+    Isolated,
+    // Only connected to the letter after it (on its left)
+    Initial,
+    // Connected on both sides
+    Medial,
+    // Only connected to the letter before it (on its right)
+    Final,
+}
+
+impl JoiningForm {
+    // The OpenType feature that substitutes glyphs with this form
+    pub fn feature(self) -> &'static [u8; 4] {
+        match self {
+            JoiningForm::Isolated => b"isol",
+            JoiningForm::Initial => b"init",
+            JoiningForm::Medial => b"medi",
+            JoiningForm::Final => b"fina",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Joining {
+    // Doesn't connect to anything, like spaces and non-Arabic letters
+    None,
+    // Only connects to the letter before it
+    Right,
+    // Connects on both sides
+    Dual,
+    // Connects to letters on both sides without changing its own shape, like tatweel and ZWJ
+    Causing,
+    // Skipped over, like vowel marks, so the letters on either side of it still connect
+    Transparent,
+}
+
+// How many forms each letter from U+0621 to U+064A has in the Arabic Presentation Forms-B block, which
+// lists them in order as isolated, final, initial and medial: 1 for letters that don't join, 2 for
+// those that only join to the letter before them and 4 for those that join on both sides. The letters
+// from U+063B to U+0640 don't have any.
+const FORMS: [(u32, u8); 36] = [
+    (0x0621, 1),
+    (0x0622, 2),
+    (0x0623, 2),
+    (0x0624, 2),
+    (0x0625, 2),
+    (0x0626, 4),
+    (0x0627, 2),
+    (0x0628, 4),
+    (0x0629, 2),
+    (0x062a, 4),
+    (0x062b, 4),
+    (0x062c, 4),
+    (0x062d, 4),
+    (0x062e, 4),
+    (0x062f, 2),
+    (0x0630, 2),
+    (0x0631, 2),
+    (0x0632, 2),
+    (0x0633, 4),
+    (0x0634, 4),
+    (0x0635, 4),
+    (0x0636, 4),
+    (0x0637, 4),
+    (0x0638, 4),
+    (0x0639, 4),
+    (0x063a, 4),
+    (0x0641, 4),
+    (0x0642, 4),
+    (0x0643, 4),
+    (0x0644, 4),
+    (0x0645, 4),
+    (0x0646, 4),
+    (0x0647, 4),
+    (0x0648, 2),
+    (0x0649, 2),
+    (0x064a, 4),
+];
+
+fn joining(c: char) -> Joining {
+    let c = c as u32;
+    if let Some((_, forms)) = FORMS.iter().find(|(letter, _)| *letter == c) {
+        return match forms {
+            1 => Joining::None,
+            2 => Joining::Right,
+            _ => Joining::Dual,
+        };
+    }
+
+    match c {
+        0x0640 | 0x200d => Joining::Causing,
+        // The rest of the letters that join used for Persian and Urdu
+        0x063b..=0x063f | 0x067e | 0x0686 | 0x06a9 | 0x06af | 0x06cc => Joining::Dual,
+        0x0698 => Joining::Right,
+        0x0610..=0x061a | 0x064b..=0x065f | 0x0670 | 0x06d6..=0x06dc | 0x06df..=0x06e4 => {
+            Joining::Transparent
+        }
+        0x06e7 | 0x06e8 | 0x06ea..=0x06ed => Joining::Transparent,
+        _ => Joining::None,
+    }
+}
+
+// The form each character in the text takes, in order, or 'None' for those that don't join (which
+// includes everything outside of Arabic script). Letters join across the marks between them.
+pub fn joining_forms(text: &str) -> Vec<Option<JoiningForm>> {
+    let types: Vec<Joining> = text.chars().map(joining).collect();
+    let solid: Vec<usize> = (0..types.len())
+        .filter(|&i| types[i] != Joining::Transparent)
+        .collect();
+
+    let mut forms = vec![None; types.len()];
+    for (k, &i) in solid.iter().enumerate() {
+        if !matches!(types[i], Joining::Dual | Joining::Right) {
+            continue;
+        }
+
+        let previous = k.checked_sub(1).map(|k| types[solid[k]]);
+        let next = solid.get(k + 1).map(|&j| types[j]);
+        let joins_previous = matches!(previous, Some(Joining::Dual | Joining::Causing));
+        let joins_next = types[i] == Joining::Dual
+            && matches!(
+                next,
+                Some(Joining::Dual | Joining::Right | Joining::Causing)
+            );
+        forms[i] = Some(match (joins_previous, joins_next) {
+            (true, true) => JoiningForm::Medial,
+            (true, false) => JoiningForm::Final,
+            (false, true) => JoiningForm::Initial,
+            (false, false) => JoiningForm::Isolated,
+        });
+    }
+    forms
+}
+
+// The character for a letter's form in the Arabic Presentation Forms-B block, for fonts without the
+// OpenType features for them
+pub(crate) fn presentation_form(c: char, form: JoiningForm) -> Option<char> {
+    let mut start = 0xfe80;
+    for (letter, forms) in FORMS.iter() {
+        if *letter == c as u32 {
+            let index = match (form, forms) {
+                (JoiningForm::Isolated, _) => 0,
+                (JoiningForm::Final, 2 | 4) => 1,
+                (JoiningForm::Initial, 4) => 2,
+                (JoiningForm::Medial, 4) => 3,
+                _ => return None,
+            };
+            return char::from_u32(start + index);
+        }
+        start += *forms as u32;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forms() {
+        use JoiningForm::*;
+
+        // Beh, teh and alef join up, and alef (which only joins to the letter before it) ends the word
+        assert_eq!(
+            joining_forms("بتا بب"),
+            vec![
+                Some(Initial),
+                Some(Medial),
+                Some(Final),
+                None,
+                Some(Initial),
+                Some(Final)
+            ]
+        );
+
+        // Marks don't break the join, but letters outside the script do
+        assert_eq!(
+            joining_forms("بَبaب"),
+            vec![Some(Initial), None, Some(Final), None, Some(Isolated)]
+        );
+
+        assert_eq!(presentation_form('ب', Isolated), Some('\u{fe8f}'));
+        assert_eq!(presentation_form('ب', Medial), Some('\u{fe92}'));
+        assert_eq!(presentation_form('ي', Initial), Some('\u{fef3}'));
+        assert_eq!(presentation_form('ا', Final), Some('\u{fe8e}'));
+        assert_eq!(presentation_form('ا', Initial), None);
+        assert_eq!(presentation_form('a', Isolated), None);
+    }
+}
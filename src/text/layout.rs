@@ -0,0 +1,352 @@
+use super::bidi::{self, TextDirection};
+use super::font::{FontCollection, FontId};
+use super::joining;
+use crate::canvas::{Color, GlyphRun, PositionedGlyph};
+use crate::space::{Point, Region, Size};
+use std::ops::Range;
+
+// A glyph placed on a line. Each character gets one glyph from the first font that has it (in its
+// joining form, for Arabic letters), since there are no ligatures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    pub font: FontId,
+    pub id: u16,
+    pub ch: char,
+    // The byte offset of the character in the text
+    pub cluster: usize,
+    // From the left of the line
+    pub x: f32,
+    pub advance: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    // In the order they're drawn in, from left to right. Spaces at the end of the line are left out.
+    pub glyphs: Vec<Glyph>,
+    // The bytes of the text on the line, including the spaces at the end
+    pub range: Range<usize>,
+    pub width: f32,
+    // From the top of the text
+    pub baseline: f32,
+    pub direction: TextDirection,
+}
+
+// Text broken into lines that fit a width, with every glyph positioned. Lines break between words (and
+// between CJK characters), or anywhere in a word that doesn't fit on a line by itself, and at each line
+// break in the text. Each paragraph (the text between line breaks) takes its direction from its first
+// strong character, and is reordered for display line by line. Arabic letters are drawn in the forms
+// that join them to the letters on either side, and glyphs next to each other from the same font are
+// kerned (in either direction).
+//
+// Line spacing comes from the first font in the collection, whichever fonts the glyphs are from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLayout {
+    pub lines: Vec<Line>,
+    pub size: Size,
+    pub font_size: f32,
+}
+
+impl TextLayout {
+    pub fn new(fonts: &FontCollection, text: &str, font_size: f32, max_width: f32) -> Self {
+        let primary = match fonts.get(FontId(0)) {
+            Some(font) => font,
+            None => {
+                return TextLayout {
+                    lines: Vec::new(),
+                    size: Size::zero(),
+                    font_size,
+                };
+            }
+        };
+        let line_height = primary.line_height() * font_size;
+        let ascent = primary.ascent() * font_size;
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for paragraph in text.split('\n') {
+            for mut line in layout_paragraph(fonts, paragraph, start, font_size, max_width) {
+                line.baseline = lines.len() as f32 * line_height + ascent;
+                lines.push(line);
+            }
+            start += paragraph.len() + 1;
+        }
+
+        let width = lines
+            .iter()
+            .fold(0_f32, |width, line| width.max(line.width));
+        let size = Size::new(width, lines.len() as f32 * line_height);
+        TextLayout {
+            lines,
+            size,
+            font_size,
+        }
+    }
+
+    // The glyphs to draw for the text placed in a region, in runs from the same font. Right-to-left
+    // paragraphs are aligned to the right of the region.
+    pub fn glyph_runs(&self, region: Region, color: Color) -> Vec<GlyphRun> {
+        let mut runs: Vec<GlyphRun> = Vec::new();
+        let mut run_font = None;
+        for line in &self.lines {
+            let left = match line.direction {
+                TextDirection::LeftToRight => region.pos.x,
+                TextDirection::RightToLeft => region.pos.x + region.size.width - line.width,
+            };
+            for glyph in &line.glyphs {
+                if run_font != Some(glyph.font) {
+                    runs.push(GlyphRun {
+                        font: glyph.font,
+                        font_size: self.font_size,
+                        color,
+                        glyphs: Vec::new(),
+                    });
+                    run_font = Some(glyph.font);
+                }
+                let origin = Point::new(left + glyph.x, region.pos.y + line.baseline);
+                runs.last_mut().unwrap().glyphs.push(PositionedGlyph {
+                    id: glyph.id,
+                    ch: glyph.ch,
+                    origin,
+                });
+            }
+        }
+        runs
+    }
+}
+
+fn layout_paragraph(
+    fonts: &FontCollection,
+    text: &str,
+    offset: usize,
+    font_size: f32,
+    max_width: f32,
+) -> Vec<Line> {
+    let direction = bidi::paragraph_direction(text).unwrap_or(TextDirection::LeftToRight);
+    let mut levels = bidi::levels(text, direction);
+
+    let forms = joining::joining_forms(text);
+    let mut glyphs: Vec<Glyph> = Vec::new();
+    for (i, (cluster, ch)) in text.char_indices().enumerate() {
+        let (font, face) = fonts.font_for(ch).unwrap();
+        let mut id = face.glyph_id(ch).unwrap_or(0);
+        if let Some(form) = forms[i] {
+            id = face.form_glyph(ch, id, form);
+        }
+        let mut advance = face.advance(id) * font_size;
+
+        // Kerning only applies between glyphs from the same font in the same run. In right-to-left runs
+        // the glyph is drawn to the left of the one before it, so it's its own advance that's adjusted.
+        if let Some(previous) = glyphs.last_mut() {
+            if previous.font == font && levels[i - 1] == levels[i] {
+                let kerning = face.kerning(previous.id, id) * font_size;
+                match levels[i].is_multiple_of(2) {
+                    true => previous.advance += kerning,
+                    false => advance += kerning,
+                }
+            }
+        }
+
+        glyphs.push(Glyph {
+            font,
+            id,
+            ch,
+            cluster: offset + cluster,
+            x: 0_f32,
+            advance,
+        });
+    }
+
+    if glyphs.is_empty() {
+        return vec![Line {
+            glyphs,
+            range: offset..offset,
+            width: 0_f32,
+            baseline: 0_f32,
+            direction,
+        }];
+    }
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < glyphs.len() {
+        let end = line_end(&glyphs, start, max_width);
+
+        // Spaces at the end of the line hang past its edge, and aren't drawn
+        let mut visible = end;
+        while visible > start && glyphs[visible - 1].ch.is_whitespace() {
+            visible -= 1;
+        }
+        for level in &mut levels[visible..end] {
+            *level = direction.level();
+        }
+
+        let mut x = 0_f32;
+        let mut line_glyphs = Vec::with_capacity(visible - start);
+        for i in bidi::visual_order(&levels[start..visible]) {
+            let mut glyph = glyphs[start + i];
+            glyph.x = x;
+            x += glyph.advance;
+            line_glyphs.push(glyph);
+        }
+
+        let range_end = match glyphs.get(end) {
+            Some(next) => next.cluster,
+            None => offset + text.len(),
+        };
+        lines.push(Line {
+            glyphs: line_glyphs,
+            range: glyphs[start].cluster..range_end,
+            width: x,
+            baseline: 0_f32,
+            direction,
+        });
+        start = end;
+    }
+    lines
+}
+
+// Where the line starting at a glyph ends: at the last break that fits, or in the middle of the word
+// if there isn't one. Every line gets at least one glyph.
+fn line_end(glyphs: &[Glyph], start: usize, max_width: f32) -> usize {
+    let mut width = 0_f32;
+    let mut last_break = None;
+    for i in start..glyphs.len() {
+        if i > start && can_break_before(glyphs, i) {
+            last_break = Some(i);
+        }
+        let next = width + glyphs[i].advance;
+        if next > max_width && i > start && !glyphs[i].ch.is_whitespace() {
+            return last_break.unwrap_or(i);
+        }
+        width = next;
+    }
+    glyphs.len()
+}
+
+fn can_break_before(glyphs: &[Glyph], i: usize) -> bool {
+    let (before, after) = (glyphs[i - 1].ch, glyphs[i].ch);
+    if after.is_whitespace() {
+        return false;
+    }
+    before.is_whitespace() || before == '-' || is_ideographic(before) || is_ideographic(after)
+}
+
+// Scripts that are written without spaces, and can break between any two characters
+fn is_ideographic(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x3040..=0x30ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xac00..=0xd7af | 0xf900..=0xfaff
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::font::{test_font, test_font_with, test_layout_table, test_pair_pos};
+    use crate::text::font::{test_single_subst, Font};
+
+    fn fonts() -> FontCollection {
+        let mut fonts = FontCollection::new();
+        let latin = [('a', 500), ('b', 500), ('c', 500), (' ', 250), ('-', 250)];
+        let latin = test_font(&latin, &[('a', 'b', -100)]);
+        fonts.add(Font::from_bytes(latin).unwrap());
+        let other = test_font(&[('日', 1000), ('本', 1000), ('א', 500), ('ב', 500)], &[]);
+        fonts.add(Font::from_bytes(other).unwrap());
+        fonts
+    }
+
+    fn lines(layout: &TextLayout) -> Vec<String> {
+        let line = |line: &Line| line.glyphs.iter().map(|glyph| glyph.ch).collect();
+        layout.lines.iter().map(line).collect()
+    }
+
+    #[test]
+    fn wrapping() {
+        let fonts = fonts();
+        let layout = TextLayout::new(&fonts, "abc cab", 10_f32, f32::INFINITY);
+        assert_eq!(lines(&layout), vec!["abc cab"]);
+        // The 'ab' pair is kerned closer together
+        assert_eq!(layout.size, Size::new(30.5_f32, 10_f32));
+        assert_eq!(layout.lines[0].baseline, 8_f32);
+        let xs: Vec<f32> = layout.lines[0].glyphs.iter().map(|glyph| glyph.x).collect();
+        assert_eq!(
+            xs,
+            vec![0_f32, 4_f32, 9_f32, 14_f32, 16.5_f32, 21.5_f32, 25.5_f32]
+        );
+
+        // The space hangs off the end of the first line, rather than being part of the width
+        let layout = TextLayout::new(&fonts, "abc cab", 10_f32, 15_f32);
+        assert_eq!(lines(&layout), vec!["abc", "cab"]);
+        assert_eq!(layout.lines[0].range, 0..4);
+        assert_eq!(layout.lines[0].width, 14_f32);
+        assert_eq!(layout.size, Size::new(14_f32, 20_f32));
+
+        // Words that don't fit are broken anywhere, and hyphens and CJK text can be broken after
+        let layout = TextLayout::new(&fonts, "aaaa-bb\n\n日本日本", 10_f32, 13_f32);
+        assert_eq!(
+            lines(&layout),
+            vec!["aa", "aa-", "bb", "", "日", "本", "日", "本"]
+        );
+        assert_eq!(layout.lines[3].range, 8..8);
+        assert_eq!(layout.lines[7].baseline, 78_f32);
+
+        // Characters the first font doesn't have fall back to the next one
+        let runs = layout.glyph_runs(Region::new(Point::zero(), layout.size), Color::BLACK);
+        assert_eq!(runs.len(), 2);
+        assert_eq!((runs[0].font, runs[0].glyphs.len()), (FontId(0), 7));
+        assert_eq!((runs[1].font, runs[1].glyphs.len()), (FontId(1), 4));
+        assert_eq!(runs[1].glyphs[1].origin, Point::new(0_f32, 58_f32));
+    }
+
+    #[test]
+    fn bidi() {
+        let fonts = fonts();
+        let layout = TextLayout::new(&fonts, "ab אב", 10_f32, f32::INFINITY);
+        assert_eq!(lines(&layout), vec!["ab בא"]);
+        assert_eq!(layout.lines[0].direction, TextDirection::LeftToRight);
+
+        // Right-to-left paragraphs are aligned to the right
+        let layout = TextLayout::new(&fonts, "אב ab", 10_f32, f32::INFINITY);
+        assert_eq!(lines(&layout), vec!["ab בא"]);
+        assert_eq!(layout.lines[0].direction, TextDirection::RightToLeft);
+        let region = Region::new(Point::new(10_f32, 0_f32), Size::new(100_f32, 10_f32));
+        let runs = layout.glyph_runs(region, Color::BLACK);
+        let right = runs.last().unwrap().glyphs.last().unwrap().origin.x + 5_f32;
+        assert_eq!(right, 110_f32);
+    }
+
+    #[test]
+    fn arabic() {
+        let ids = |layout: &TextLayout| -> Vec<u16> {
+            layout.lines[0]
+                .glyphs
+                .iter()
+                .map(|glyph| glyph.id)
+                .collect()
+        };
+        let glyphs = [('ب', 500), ('ا', 300), ('\u{fe91}', 400), ('\u{fe90}', 600)];
+
+        // Beh joins to the alef after it, which kerns closer to it (so its own advance is shortened,
+        // since it's drawn to the left), and the second beh stands alone
+        let gsub = test_layout_table(&[
+            (b"init", 1, test_single_subst(&[(1, 3)])),
+            (b"fina", 1, test_single_subst(&[(1, 4)])),
+        ]);
+        let gpos = test_layout_table(&[(b"kern", 2, test_pair_pos(&[(3, 2, -100)]))]);
+        let font = test_font_with(&glyphs, &[], vec![(b"GSUB", gsub), (b"GPOS", gpos)]);
+        let mut fonts = FontCollection::new();
+        fonts.add(Font::from_bytes(font).unwrap());
+        let layout = TextLayout::new(&fonts, "باب", 10_f32, f32::INFINITY);
+        assert_eq!(ids(&layout), vec![1, 2, 3]);
+        let xs: Vec<f32> = layout.lines[0].glyphs.iter().map(|glyph| glyph.x).collect();
+        assert_eq!(xs, vec![0_f32, 5_f32, 7_f32]);
+        assert_eq!(layout.size.width, 11_f32);
+
+        // Fonts without the features use the presentation forms
+        let mut fonts = FontCollection::new();
+        fonts.add(Font::from_bytes(test_font(&glyphs, &[])).unwrap());
+        let layout = TextLayout::new(&fonts, "بب", 10_f32, f32::INFINITY);
+        assert_eq!(ids(&layout), vec![4, 3]);
+        assert_eq!(layout.size.width, 10_f32);
+    }
+}
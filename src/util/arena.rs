@@ -2,7 +2,7 @@ use std::any::Any;
 use std::cell::UnsafeCell;
 use std::convert::From;
 use std::marker::PhantomData;
-use std::mem::{align_of, size_of};
+use std::mem::{align_of, size_of, MaybeUninit};
 use std::ops::{Deref, DerefMut};
 use std::ptr::NonNull; // TODO: Switch to std::ptr::Unique when stabilized
 
@@ -12,14 +12,14 @@ const BUFFER_SIZE: usize = 1_usize << 16;
 
 #[repr(align(16))]
 struct Node {
-    buf: [u8; BUFFER_SIZE],
+    buf: [MaybeUninit<u8>; BUFFER_SIZE],
     prev: Option<Box<Node>>,
 }
 
 impl Node {
     fn alloc() -> Box<Self> {
         Box::new(Self {
-            buf: unsafe { core::mem::MaybeUninit::uninit().assume_init() },
+            buf: unsafe { MaybeUninit::uninit().assume_init() },
             prev: None,
         })
     }
@@ -3,7 +3,14 @@ pub trait Array {
     type Item;
 
     fn len(&self) -> usize;
+
+    /// # Safety
+    /// Same requirements as `Vec::set_len`.
     unsafe fn set_len(&mut self, len: usize);
+
+    /// # Safety
+    /// `index` must be less than the array's capacity,
+    /// and the element must be initialized before it is read.
     unsafe fn get_mut_unchecked(&mut self, index: usize) -> &mut Self::Item;
 }
 
@@ -19,7 +26,8 @@ impl<T> Array for Vec<T> {
     }
 
     unsafe fn get_mut_unchecked(&mut self, index: usize) -> &mut Self::Item {
-        self.get_unchecked_mut(index)
+        // The length is zeroed while draining, so this can't go through the slice
+        &mut *self.as_mut_ptr().add(index)
     }
 }
 
@@ -73,11 +81,17 @@ where
 }
 
 pub trait DrainFilter: Sized + Array {
-    fn buoy_drain_filter<F: FnMut(&mut Self::Item) -> bool>(&mut self, filter: F) -> Iter<Self, F>;
+    fn buoy_drain_filter<F: FnMut(&mut Self::Item) -> bool>(
+        &mut self,
+        filter: F,
+    ) -> Iter<'_, Self, F>;
 }
 
 impl<T: Array> DrainFilter for T {
-    fn buoy_drain_filter<F: FnMut(&mut Self::Item) -> bool>(&mut self, filter: F) -> Iter<Self, F> {
+    fn buoy_drain_filter<F: FnMut(&mut Self::Item) -> bool>(
+        &mut self,
+        filter: F,
+    ) -> Iter<'_, Self, F> {
         let len = self.len();
         unsafe {
             self.set_len(0);
@@ -1,12 +1,10 @@
-use std::usize;
-
 pub trait Fill<T> {
     fn remaining_capacity(&self) -> usize;
 
     fn push(&mut self, item: T);
 }
 
-impl<'a, T, F: Fill<T> + ?Sized> Fill<T> for &'a mut F {
+impl<T, F: Fill<T> + ?Sized> Fill<T> for &mut F {
     fn remaining_capacity(&self) -> usize {
         F::remaining_capacity(*self)
     }
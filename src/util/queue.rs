@@ -1,5 +1,4 @@
 use std::ops::{Deref, DerefMut};
-use std::usize;
 
 use super::arena::{ABox, Arena};
 use super::fill::Fill;
@@ -87,11 +86,8 @@ impl<'buf, T> Queue<'buf, T> {
         self.tail_next = other.tail_next;
     }
 
-    pub fn pop_front_node(&mut self) -> Option<ABox<QNode<'buf, T>>> {
-        let mut node = match self.head.take() {
-            Some(node) => node,
-            None => return None,
-        };
+    pub fn pop_front_node(&mut self) -> Option<ABox<'_, QNode<'buf, T>>> {
+        let mut node = self.head.take()?;
 
         // Update head and tail pointer
         self.head = node.next.take();
@@ -24,6 +24,8 @@ impl<T> Ext for T {
 }
 
 pub trait Anchor<T: ?Sized>: DerefMut<Target = T> {
+    /// # Safety
+    /// The anchored value must already have been moved out, since its destructor will not be run.
     unsafe fn dealloc(self);
 }
 
@@ -52,6 +54,7 @@ impl<A: ?Sized, T: Upcast<A>> Anchor<A> for StackAnchor<T, A> {
     }
 }
 
+#[allow(dead_code)]
 struct BoxAnchor<T: ?Sized, A: ?Sized> {
     b: Box<T>,
     _p: PhantomData<A>,
@@ -78,7 +81,7 @@ impl<A: ?Sized, T: ?Sized + Upcast<A>> Anchor<A> for BoxAnchor<T, A> {
     unsafe fn dealloc(self) {
         // Create a Box to ManuallyDrop<T> and let it fall out of scope.
         // The memory behind the Box will be deallocated, but T's destructor will not be called.
-        Box::from_raw(Box::into_raw(self.b) as *mut ManuallyDrop<T>);
+        drop(Box::from_raw(Box::into_raw(self.b) as *mut ManuallyDrop<T>));
     }
 }
 
@@ -162,6 +165,8 @@ impl<'a, T: ?Sized> RefMove<'a, T> {
     }
 
     // TODO: Checked variant of this
+    /// # Safety
+    /// The referenced value must actually be of type `A`.
     pub unsafe fn downcast_unchecked<A>(x: Self) -> RefMove<'a, A> {
         let result = RefMove {
             ptr: NonNull::new_unchecked(x.ptr.as_ptr() as *mut A),
@@ -193,6 +198,7 @@ impl<'a, T: ?Sized> DerefMut for RefMove<'a, T> {
     }
 }
 
+#[allow(dead_code)]
 struct RefMoveAnchor<'a, T: ?Sized> {
     ref_move: RefMove<'a, T>,
 }